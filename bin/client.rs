@@ -2,19 +2,22 @@ use clap::{Parser, Subcommand};
 use mcp_rs::{
     client::{Client, ClientInfo},
     error::McpError,
-    transport::{SseTransport, StdioTransport},
+    transport::{SseTransport, StdioTransport, StreamableHttpTransport, WebSocketTransport},
 };
 use serde_json::json;
-use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
 
 #[derive(Parser, Debug)]
 #[command(name = "mcp-client", version, about = "MCP Client CLI")]
 struct Cli {
-    /// Server URL for SSE transport
+    /// Server URL for SSE, WebSocket and Streamable HTTP transports
     #[arg(short, long)]
     server: Option<String>,
 
-    /// Transport type (stdio, sse)
+    /// Bearer token for servers that require authentication
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Transport type (stdio, sse, ws, http)
     #[arg(short, long, default_value = "stdio")]  // Changed default to stdio
     transport: String,
 
@@ -96,32 +99,34 @@ async fn main() -> Result<(), McpError> {
                     return Err(e);
                 }
             }
-    
-            tracing::info!("Initializing client...");
-            match client.initialize(ClientInfo {
-                name: "mcp-cli".to_string(),
-                version: env!("CARGO_PKG_VERSION").to_string(),
-            }).await {
-                Ok(result) => {
-                    tracing::info!("Successfully initialized. Server info: {:?}", result.server_info);
-                }
-                Err(e) => {
-                    tracing::error!("Failed to initialize: {}", e);
-                    return Err(e);
-                }
-            }
         }
-        "sse" => {
-            let server_url = args.server.unwrap_or("http://127.0.0.1".to_string());
+        "sse" | "ws" | "http" => {
+            let server_url = args.server.clone().unwrap_or("http://127.0.0.1".to_string());
             // Parse server URL to get host and port
-            let url = url::Url::parse(&server_url).unwrap();
+            let url = url::Url::parse(&server_url)
+                .map_err(|e| McpError::InvalidRequest(format!("Invalid server URL: {}", e)))?;
             let host = url.host_str().unwrap_or("127.0.0.1").to_string();
             let port = url.port().unwrap_or(3000);
-            
-            let transport = SseTransport::new_client(host, port, 32);
-            client.connect(transport).await?;
-
 
+            if args.transport == "ws" {
+                let mut transport = WebSocketTransport::new_client(host, port, 32);
+                if let Some(token) = args.token.clone() {
+                    transport = transport.with_bearer_token(token);
+                }
+                client.connect(transport).await?;
+            } else if args.transport == "http" {
+                let mut transport = StreamableHttpTransport::new_client(host, port, 32);
+                if let Some(token) = args.token.clone() {
+                    transport = transport.with_bearer_token(token);
+                }
+                client.connect(transport).await?;
+            } else {
+                let mut transport = SseTransport::new_client(host, port, 32);
+                if let Some(token) = args.token.clone() {
+                    transport = transport.with_bearer_token(token);
+                }
+                client.connect(transport).await?;
+            }
         }
         _ => {
             return Err(McpError::InvalidRequest(
@@ -132,7 +137,7 @@ async fn main() -> Result<(), McpError> {
 
     // Initialize with better error handling and debugging
     tracing::debug!("Sending initialize request...");
-    let init_result = match tokio::time::timeout(
+    match tokio::time::timeout(
         std::time::Duration::from_secs(30), // Increased from 5 to 30 seconds
         client.initialize(ClientInfo {
             name: "mcp-cli".to_string(),
@@ -141,7 +146,6 @@ async fn main() -> Result<(), McpError> {
     ).await {
         Ok(Ok(result)) => {
             tracing::info!("Connected to server: {:?}", result.server_info);
-            result
         }
         Ok(Err(e)) => {
             tracing::error!("Failed to initialize: {}", e);
@@ -154,7 +158,7 @@ async fn main() -> Result<(), McpError> {
     };
 
     // Execute command
-    let result = match args.command {
+    match args.command {
         Commands::ListResources { cursor } => {
             let res = client.list_resources(cursor).await?;
             println!("{}", json!(res));
@@ -165,8 +169,8 @@ async fn main() -> Result<(), McpError> {
             println!("{}", json!(res));
         }
         Commands::Subscribe { uri } => {
-            let res = client.subscribe_to_resource(uri).await?;
-            println!("{}", json!(res));
+            client.subscribe_to_resource(uri).await?;
+            println!("{}", json!({}));
         }
 
         Commands::ListPrompts { cursor } => {
@@ -203,7 +207,7 @@ async fn main() -> Result<(), McpError> {
     };
 
     // Remove the Ctrl+C wait for stdio transport
-    if args.transport == "sse" {
+    if args.transport != "stdio" {
         tracing::info!("Client connected. Press Ctrl+C to exit...");
         tokio::signal::ctrl_c().await?;
     }
@@ -8,7 +8,10 @@ use mcp_rs::{
         config::{ResourceSettings, ServerConfig, ServerSettings, TransportType},
         McpServer,
     },
-    tools::calculator::CalculatorTool,
+    tools::{
+        approval::{AutoApprove, PolicyApprover, TerminalApprover},
+        calculator::CalculatorTool,
+    },
 };
 use std::{path::PathBuf, sync::Arc};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -28,9 +31,18 @@ struct Args {
     #[arg(short, long, default_value = "3000")]
     port: u16,
 
-    /// Transport type (stdio, sse, ws)
+    /// Transport type (stdio, sse, ws, http)
     #[arg(short, long)]
     transport: Option<String>,
+
+    /// Approve tool calls by the rules in this JSON policy file instead of asking
+    #[arg(long)]
+    tool_policy: Option<PathBuf>,
+
+    /// Run every tool call without asking. Over stdio, tool calls are denied
+    /// unless this or a policy is given.
+    #[arg(long, conflicts_with = "tool_policy")]
+    auto_approve: bool,
 }
 
 #[tokio::main]
@@ -81,6 +93,7 @@ async fn main() -> Result<(), McpError> {
             TransportType::Stdio => "STDIO",
             TransportType::Sse => "SSE",
             TransportType::WebSocket => "WebSocket",
+            TransportType::StreamableHttp => "Streamable HTTP",
         }
     );
 
@@ -122,6 +135,29 @@ async fn main() -> Result<(), McpError> {
     let calculator = Arc::new(CalculatorTool::new());
     server.tool_manager.register_tool(calculator).await;
 
+    // Decide who approves tool calls. The terminal can only be asked when
+    // stdin is not the transport; without an approver every call is denied.
+    if server.tool_manager.settings.require_confirmation {
+        if let Some(policy_path) = args.tool_policy {
+            let policy = PolicyApprover::from_file(&policy_path)?;
+            server.tool_manager.set_approver(Arc::new(policy)).await;
+            tracing::info!("Tool calls are approved by policy {}", policy_path.display());
+        } else if args.auto_approve {
+            server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
+            tracing::warn!("Tool calls are approved automatically");
+        } else if matches!(transport, TransportType::Stdio) {
+            tracing::warn!(
+                "Tool calls cannot be confirmed over stdio and are denied; pass --tool-policy or --auto-approve"
+            );
+        } else {
+            server
+                .tool_manager
+                .set_approver(Arc::new(TerminalApprover::stdio()))
+                .await;
+            tracing::info!("Tool calls must be approved on this terminal");
+        }
+    }
+
     // Register some example prompts
     let code_review_prompt = Prompt {
         name: "code_review".to_string(),
@@ -214,7 +250,34 @@ async fn main() -> Result<(), McpError> {
             }
         }
         TransportType::WebSocket => {
-            unimplemented!("WebSocket transport not implemented");
+            tracing::info!("Starting server with WebSocket transport");
+
+            // Run server and wait for shutdown
+            tokio::select! {
+                result = server.run_websocket_transport() => {
+                    if let Err(e) = result {
+                        tracing::error!("Server error: {}", e);
+                    }
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    tracing::info!("Shutting down server...");
+                }
+            }
+        }
+        TransportType::StreamableHttp => {
+            tracing::info!("Starting server with Streamable HTTP transport");
+
+            // Run server and wait for shutdown
+            tokio::select! {
+                result = server.run_streamable_http_transport() => {
+                    if let Err(e) = result {
+                        tracing::error!("Server error: {}", e);
+                    }
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    tracing::info!("Shutting down server...");
+                }
+            }
         }
     }
 
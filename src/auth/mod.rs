@@ -0,0 +1,359 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use warp::{
+    http::{header, StatusCode},
+    reject::Reject,
+    reply::Response,
+    Filter, Rejection, Reply,
+};
+
+use crate::{error::McpError, server::config::SecuritySettings};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a signed token grants its bearer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// Who the token was issued to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    /// Expiry as seconds since the Unix epoch; tokens without one never expire
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid bearer token")]
+    InvalidToken,
+    #[error("Bearer token expired")]
+    Expired,
+    #[error("Bearer token lacks scope {0}")]
+    InsufficientScope(String),
+}
+
+impl Reject for AuthError {}
+
+impl AuthError {
+    /// 403 for a valid token that may not do this, 401 otherwise
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuthError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    /// The `WWW-Authenticate` challenge telling the client what went wrong (RFC 6750)
+    fn challenge(&self) -> String {
+        match self {
+            AuthError::MissingToken => "Bearer".to_string(),
+            AuthError::InvalidToken | AuthError::Expired => {
+                format!("Bearer error=\"invalid_token\", error_description=\"{}\"", self)
+            }
+            AuthError::InsufficientScope(scope) => {
+                format!("Bearer error=\"insufficient_scope\", scope=\"{}\"", scope)
+            }
+        }
+    }
+}
+
+impl Reply for AuthError {
+    fn into_response(self) -> Response {
+        let challenge = self.challenge();
+        let reply = warp::reply::with_status(self.to_string(), self.status());
+        warp::reply::with_header(reply, header::WWW_AUTHENTICATE, challenge).into_response()
+    }
+}
+
+// Bearer Token Authentication
+//
+// Accepts a static token, tokens signed with a separate signing key, or both.
+// Signed tokens are `base64url(claims JSON).base64url(HMAC-SHA256 of the first
+// part)`; they can expire and carry scopes. The static token grants every
+// required scope. The two secrets must differ, or holders of the static token
+// could sign tokens of their own.
+#[derive(Clone, Default)]
+pub struct BearerAuth {
+    /// SHA-256 of the static token. Digests are compared so that neither the
+    /// token's bytes nor its length show in the timing.
+    static_token: Option<[u8; 32]>,
+    signing_key: Option<Vec<u8>>,
+    required_scopes: Vec<String>,
+}
+
+impl BearerAuth {
+    /// An authenticator that accepts no tokens until some are configured
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `token` as is
+    pub fn with_static_token(mut self, token: impl AsRef<[u8]>) -> Self {
+        self.static_token = Some(Sha256::digest(token.as_ref()).into());
+        self
+    }
+
+    /// Accept tokens signed with `key`
+    pub fn with_signing_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.signing_key = Some(key.into());
+        self
+    }
+
+    /// Only accept signed tokens that carry all of `scopes`
+    pub fn with_required_scopes(mut self, scopes: Vec<String>) -> Self {
+        self.required_scopes = scopes;
+        self
+    }
+
+    /// The authenticator `settings` ask for, or `None` when auth is disabled
+    pub fn from_settings(settings: &SecuritySettings) -> Result<Option<Self>, McpError> {
+        if !settings.enable_auth {
+            return Ok(None);
+        }
+        let configured = |secret: &Option<String>| secret.clone().filter(|secret| !secret.is_empty());
+        let static_token = configured(&settings.token_secret);
+        let signing_key = configured(&settings.signing_secret);
+        if static_token.is_none() && signing_key.is_none() {
+            return Err(McpError::InvalidRequest(
+                "Auth is enabled but neither token_secret nor signing_secret is set".to_string(),
+            ));
+        }
+        if static_token.is_some() && static_token == signing_key {
+            return Err(McpError::InvalidRequest(
+                "token_secret and signing_secret must differ".to_string(),
+            ));
+        }
+
+        let mut auth = Self::new().with_required_scopes(settings.required_scopes.clone());
+        if let Some(token) = static_token {
+            auth = auth.with_static_token(token);
+        }
+        if let Some(key) = signing_key {
+            auth = auth.with_signing_key(key);
+        }
+        Ok(Some(auth))
+    }
+
+    /// Sign `claims` into a token this authenticator accepts, if it has a signing key
+    pub fn issue(&self, claims: &TokenClaims) -> Option<String> {
+        let key = self.signing_key.as_ref()?;
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap());
+        let signature = URL_SAFE_NO_PAD.encode(Self::mac(key, &payload).finalize().into_bytes());
+        Some(format!("{}.{}", payload, signature))
+    }
+
+    /// Check a bearer token, returning what it grants
+    pub fn validate(&self, token: &str) -> Result<TokenClaims, AuthError> {
+        if self.is_static_token(token) {
+            return Ok(TokenClaims {
+                scopes: self.required_scopes.clone(),
+                ..Default::default()
+            });
+        }
+
+        let key = self.signing_key.as_ref().ok_or(AuthError::InvalidToken)?;
+        let (payload, signature) = token.split_once('.').ok_or(AuthError::InvalidToken)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| AuthError::InvalidToken)?;
+        Self::mac(key, payload)
+            .verify_slice(&signature)
+            .map_err(|_| AuthError::InvalidToken)?;
+
+        let claims: TokenClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(AuthError::InvalidToken)?;
+
+        if let Some(exp) = claims.exp {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            if exp <= now {
+                return Err(AuthError::Expired);
+            }
+        }
+        if let Some(missing) = self
+            .required_scopes
+            .iter()
+            .find(|scope| !claims.scopes.contains(scope))
+        {
+            return Err(AuthError::InsufficientScope(missing.clone()));
+        }
+        Ok(claims)
+    }
+
+    /// Check the value of an `Authorization` header
+    pub fn authorize(&self, header: Option<&str>) -> Result<TokenClaims, AuthError> {
+        let header = header.ok_or(AuthError::MissingToken)?;
+        match header.split_once(' ') {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                self.validate(token.trim())
+            }
+            _ => Err(AuthError::InvalidToken),
+        }
+    }
+
+    fn mac(key: &[u8], payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    /// Compare against the static token in constant time
+    fn is_static_token(&self, token: &str) -> bool {
+        let Some(expected) = &self.static_token else {
+            return false;
+        };
+        let digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        digest
+            .iter()
+            .zip(expected)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
+/// An HTTP client that sends `token`, if any, as a bearer token on every request
+pub(crate) fn http_client(token: Option<&str>) -> reqwest::Client {
+    use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+
+    let mut headers = HeaderMap::new();
+    if let Some(token) = token {
+        match HeaderValue::from_str(&format!("Bearer {}", token)) {
+            Ok(mut value) => {
+                value.set_sensitive(true);
+                headers.insert(AUTHORIZATION, value);
+            }
+            Err(e) => tracing::error!("Bearer token is not a valid header value: {}", e),
+        }
+    }
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Reject requests without a valid bearer token, if `auth` is set
+pub(crate) fn require_bearer(
+    auth: Option<BearerAuth>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>(header::AUTHORIZATION.as_str())
+        .and_then(move |header: Option<String>| {
+            let auth = auth.clone();
+            async move {
+                match auth {
+                    Some(auth) => auth
+                        .authorize(header.as_deref())
+                        .map(|_| ())
+                        .map_err(warp::reject::custom),
+                    None => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Answer auth rejections with 401 or 403, leaving other rejections to warp
+pub(crate) async fn recover(rejection: Rejection) -> Result<Response, Rejection> {
+    match rejection.find::<AuthError>() {
+        Some(e) => Ok(e.clone().into_response()),
+        None => Err(rejection),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn test_bearer_tokens() {
+        let auth = BearerAuth::new()
+            .with_static_token("s3cret")
+            .with_signing_key("signing-key")
+            .with_required_scopes(vec!["mcp".to_string()]);
+
+        // The static token
+        assert!(auth.authorize(Some("Bearer s3cret")).is_ok());
+        assert_eq!(auth.authorize(None), Err(AuthError::MissingToken));
+        assert_eq!(auth.authorize(Some("Basic s3cret")), Err(AuthError::InvalidToken));
+        assert_eq!(auth.validate("s3cre"), Err(AuthError::InvalidToken));
+        assert_eq!(auth.validate("signing-key"), Err(AuthError::InvalidToken));
+
+        // Signed tokens
+        let claims = TokenClaims {
+            sub: Some("alice".to_string()),
+            exp: Some(now() + 60),
+            scopes: vec!["mcp".to_string()],
+        };
+        let token = auth.issue(&claims).unwrap();
+        assert_eq!(auth.validate(&token), Ok(claims.clone()));
+
+        let other = BearerAuth::new().with_signing_key("other").issue(&claims).unwrap();
+        assert_eq!(auth.validate(&other), Err(AuthError::InvalidToken));
+
+        // Holders of the static token cannot sign their own tokens
+        let forged = BearerAuth::new().with_signing_key("s3cret").issue(&claims).unwrap();
+        assert_eq!(auth.validate(&forged), Err(AuthError::InvalidToken));
+
+        let expired = auth
+            .issue(&TokenClaims {
+                exp: Some(now() - 1),
+                ..claims.clone()
+            })
+            .unwrap();
+        assert_eq!(auth.validate(&expired), Err(AuthError::Expired));
+
+        let unscoped = auth
+            .issue(&TokenClaims {
+                scopes: vec![],
+                ..claims.clone()
+            })
+            .unwrap();
+        let error = auth.validate(&unscoped).unwrap_err();
+        assert_eq!(error, AuthError::InsufficientScope("mcp".to_string()));
+        assert_eq!(error.status(), StatusCode::FORBIDDEN);
+
+        // Without a signing key only the static token works
+        let static_only = BearerAuth::new().with_static_token("s3cret");
+        assert!(static_only.issue(&claims).is_none());
+        assert_eq!(static_only.validate(&token), Err(AuthError::InvalidToken));
+        assert!(static_only.validate("s3cret").is_ok());
+    }
+
+    #[test]
+    fn test_from_settings() {
+        let mut settings = SecuritySettings::default();
+        assert!(BearerAuth::from_settings(&settings).unwrap().is_none());
+
+        settings.enable_auth = true;
+        assert!(BearerAuth::from_settings(&settings).is_err());
+
+        settings.token_secret = Some("s3cret".to_string());
+        let auth = BearerAuth::from_settings(&settings).unwrap().unwrap();
+        assert!(auth.validate("s3cret").is_ok());
+        assert!(auth.issue(&TokenClaims::default()).is_none());
+
+        // One secret may not do both jobs
+        settings.signing_secret = Some("s3cret".to_string());
+        assert!(BearerAuth::from_settings(&settings).is_err());
+
+        settings.signing_secret = Some("signing-key".to_string());
+        let auth = BearerAuth::from_settings(&settings).unwrap().unwrap();
+        let token = auth.issue(&TokenClaims::default()).unwrap();
+        assert!(auth.validate(&token).is_ok());
+    }
+}
@@ -1,92 +1,146 @@
 use crate::{
     error::McpError,
-    logging::LoggingCapabilities,
-    prompts::{
-        GetPromptRequest, ListPromptsRequest, ListPromptsResponse, PromptCapabilities, PromptResult,
+    prompts::{GetPromptRequest, ListPromptsRequest, ListPromptsResponse, PromptResult},
+    protocol::{
+        HeartbeatOptions, JsonRpcNotification, PeerCapabilities, Protocol, ProtocolHandle,
+        ProtocolOptions, SUPPORTED_PROTOCOL_VERSIONS,
     },
-    protocol::{JsonRpcNotification, Protocol, ProtocolHandle, ProtocolOptions},
     resource::{
         ListResourcesRequest, ListResourcesResponse, ReadResourceRequest, ReadResourceResponse,
-        ResourceCapabilities,
+        SubscribeRequest,
     },
-    tools::{CallToolRequest, ListToolsRequest, ListToolsResponse, ToolCapabilities, ToolResult},
+    roots::{ListRootsResult, Root},
+    sampling::{CreateMessageRequest, SamplingHandler},
+    tools::{CallToolRequest, ListToolsRequest, ListToolsResponse, ToolResult},
     transport::{Transport, TransportCommand},
 };
-use serde::{Deserialize, Serialize};
 use std::{
     sync::{atomic::{AtomicBool, Ordering}, Arc},
     time::Duration,
 };
 use tokio::sync::RwLock;
 
-// Client capabilities and info structs
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RootsCapabilities {
-    pub list_changed: bool,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SamplingCapabilities {}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ClientCapabilities {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub roots: Option<RootsCapabilities>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub sampling: Option<SamplingCapabilities>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ClientInfo {
-    pub name: String,
-    pub version: String,
-}
-
-// Server response structs
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ServerCapabilities {
-    pub logging: Option<LoggingCapabilities>,
-    pub prompts: Option<PromptCapabilities>,
-    pub resources: Option<ResourceCapabilities>,
-    pub tools: Option<ToolCapabilities>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ServerInfo {
-    pub name: String,
-    pub version: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InitializeParams {
-    pub protocol_version: String,
-    pub capabilities: ClientCapabilities,
-    pub client_info: ClientInfo,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InitializeResult {
-    pub protocol_version: String,
-    pub capabilities: ServerCapabilities,
-    pub server_info: ServerInfo,
-}
+pub use crate::types::{
+    ClientCapabilities, ClientInfo, InitializeParams, InitializeResult, RootsCapabilities,
+    SamplingCapabilities, ServerCapabilities, ServerInfo,
+};
 
 pub struct Client {
     protocol: Protocol,
     initialized: Arc<RwLock<bool>>,
     server_capabilities: Arc<RwLock<Option<ServerCapabilities>>>,
+    sampling_handler: Option<Arc<dyn SamplingHandler>>,
+    roots: Arc<RwLock<Option<Vec<Root>>>>,
+    supported_versions: Vec<String>,
+    protocol_version: Arc<RwLock<Option<String>>>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Client {
     pub fn new() -> Self {
+        let roots: Arc<RwLock<Option<Vec<Root>>>> = Arc::new(RwLock::new(None));
+        let protocol = Protocol::builder(Some(ProtocolOptions {
+            enforce_strict_capabilities: true,
+            ..Default::default()
+        }))
+        .with_request_handler("roots/list", {
+            let roots = Arc::clone(&roots);
+            Box::new(move |_request, _extra| {
+                let roots = Arc::clone(&roots);
+                Box::pin(async move {
+                    let roots = roots
+                        .read()
+                        .await
+                        .clone()
+                        .ok_or_else(|| McpError::CapabilityNotSupported("roots".to_string()))?;
+                    Ok(serde_json::to_value(ListRootsResult { roots })?)
+                })
+            })
+        })
+        .build();
+
         Self {
-            protocol: Protocol::builder(Some(ProtocolOptions {
-                enforce_strict_capabilities: true,
-            }))
-            .build(),
+            protocol,
             initialized: Arc::new(RwLock::new(false)),
             server_capabilities: Arc::new(RwLock::new(None)),
+            sampling_handler: None,
+            roots,
+            supported_versions: SUPPORTED_PROTOCOL_VERSIONS
+                .iter()
+                .map(|version| version.to_string())
+                .collect(),
+            protocol_version: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Limit the protocol versions this client accepts. It asks for the newest.
+    pub fn set_supported_versions(&mut self, versions: Vec<String>) {
+        self.supported_versions = versions;
+    }
+
+    /// The protocol version agreed with the server, once initialized
+    pub async fn protocol_version(&self) -> Option<String> {
+        self.protocol_version.read().await.clone()
+    }
+
+    /// Share `roots` with the server. The roots capability is only declared if
+    /// roots are set before `initialize`; later calls notify the server.
+    pub async fn set_roots(&self, roots: Vec<Root>) -> Result<(), McpError> {
+        *self.roots.write().await = Some(roots);
+        if *self.initialized.read().await {
+            self.protocol
+                .notification("notifications/roots/list_changed", Option::<()>::None)
+                .await?;
         }
+        Ok(())
+    }
+
+    /// Answer the server's `sampling/createMessage` requests with `handler`.
+    /// The sampling capability is only declared once a handler is set, so set
+    /// it before `initialize`; afterwards this fails with `CapabilityNotSupported`
+    /// unless sampling was declared.
+    pub async fn set_sampling_handler(
+        &mut self,
+        handler: Arc<dyn SamplingHandler>,
+    ) -> Result<(), McpError> {
+        let sampler = Arc::clone(&handler);
+        self.protocol
+            .set_request_handler(
+                "sampling/createMessage",
+                Box::new(move |request, _extra| {
+                    let handler = Arc::clone(&sampler);
+                    Box::pin(async move {
+                        let params: CreateMessageRequest = request.parse_params()?;
+                        let result = handler.create_message(params).await?;
+                        Ok(serde_json::to_value(result)?)
+                    })
+                }),
+            )
+            .await?;
+        self.sampling_handler = Some(handler);
+        Ok(())
+    }
+
+    /// Ping the server every `heartbeat.interval` once connected, and close the
+    /// connection when it stops answering. Set it before `connect`.
+    pub fn set_heartbeat(&mut self, heartbeat: HeartbeatOptions) {
+        self.protocol.options.heartbeat = Some(heartbeat);
+    }
+
+    /// Call `handler` when the heartbeat gives up on the server. Set it before `connect`.
+    pub fn on_heartbeat_failure<F>(&mut self, handler: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.protocol.heartbeat_failed_handler = Some(Arc::new(Box::new(move || {
+            handler();
+            Box::pin(async {})
+        })));
     }
 
     pub async fn connect<T: Transport>(&mut self, transport: T) -> Result<ProtocolHandle, McpError> {
@@ -108,36 +162,60 @@ impl Client {
             ));
         }
 
+        let requested_version = self
+            .supported_versions
+            .iter()
+            .max()
+            .cloned()
+            .ok_or_else(|| McpError::InvalidRequest("No supported protocol versions".to_string()))?;
+
         // Prepare initialization parameters
         let params = InitializeParams {
-            protocol_version: "2024-11-05".to_string(),
+            protocol_version: requested_version,
             capabilities: ClientCapabilities {
-                roots: Some(RootsCapabilities { list_changed: true }),
-                sampling: Some(SamplingCapabilities {}),
+                roots: self
+                    .roots
+                    .read()
+                    .await
+                    .as_ref()
+                    .map(|_| RootsCapabilities { list_changed: true }),
+                sampling: self.sampling_handler.as_ref().map(|_| SamplingCapabilities {}),
             },
             client_info,
         };
 
+        let capabilities = params.capabilities.clone();
+
         // Send initialize request
         let result: InitializeResult = self
             .protocol
             .request("initialize", Some(params), None)
             .await?;
 
-        // Validate protocol version
-        if result.protocol_version != "2024-11-05" {
+        // The server may answer with an older version; we can only go on if we speak it
+        if !self.supported_versions.contains(&result.protocol_version) {
             return Err(McpError::InvalidRequest(format!(
-                "Unsupported protocol version: {}",
-                result.protocol_version
+                "Server chose protocol version {}, but this client only supports {}",
+                result.protocol_version,
+                self.supported_versions.join(", ")
             )));
         }
+        *self.protocol_version.write().await = Some(result.protocol_version.clone());
 
         // Store server capabilities
         *self.server_capabilities.write().await = Some(result.capabilities.clone());
 
+        // From here on the protocol holds both sides to what they declared
+        self.protocol
+            .set_local_capabilities(PeerCapabilities::Client(capabilities))
+            .await;
+        self.protocol
+            .set_remote_capabilities(None, PeerCapabilities::Server(result.capabilities.clone()))
+            .await;
+
         // Send initialized notification
         self.protocol
-            .notification("initialized", Option::<()>::None)
+            .notification("notifications/initialized", Option::<()>::None)
             .await?;
 
         // Mark as initialized
@@ -176,9 +254,21 @@ impl Client {
         self.assert_initialized().await?;
         self.assert_capability("resources").await?;
 
+        // The server acknowledges with an empty result object
         self.protocol
-            .request("resources/subscribe", Some(uri), None)
+            .request::<_, serde_json::Value>("resources/subscribe", Some(SubscribeRequest { uri }), None)
             .await
+            .map(|_| ())
+    }
+
+    pub async fn unsubscribe_from_resource(&self, uri: String) -> Result<(), McpError> {
+        self.assert_initialized().await?;
+        self.assert_capability("resources").await?;
+
+        self.protocol
+            .request::<_, serde_json::Value>("resources/unsubscribe", Some(SubscribeRequest { uri }), None)
+            .await
+            .map(|_| ())
     }
 
     // Prompt methods
@@ -238,18 +328,38 @@ impl Client {
             .await
     }
 
+    /// Check that the server is still responsive. Allowed before `initialize`.
+    pub async fn ping(&self) -> Result<(), McpError> {
+        self.protocol.ping(None).await
+    }
+
     // Logging methods
     pub async fn set_log_level(&self, level: String) -> Result<(), McpError> {
         self.assert_initialized().await?;
         self.assert_capability("logging").await?;
 
         self.protocol
-            .request(
+            .request::<_, serde_json::Value>(
                 "logging/setLevel",
                 Some(serde_json::json!({ "level": level })),
                 None,
             )
             .await
+            .map(|_| ())
+    }
+
+    /// Call `handler` for every `method` notification the server sends
+    pub async fn on_notification<F>(&self, method: &str, handler: F)
+    where
+        F: Fn(JsonRpcNotification) + Send + Sync + 'static,
+    {
+        self.protocol.notification_handlers.write().await.insert(
+            method.to_string(),
+            Box::new(move |notification, _extra| {
+                handler(notification);
+                Box::pin(async { Ok(()) })
+            }),
+        );
     }
 
     /// Waits for the server to acknowledge shutdown request
@@ -262,7 +372,7 @@ impl Client {
             let ack = shutdown_ack.clone();
             handlers.insert(
                 "shutdown/ack".to_string(),
-                Box::new(move |_notification| {
+                Box::new(move |_notification, _extra| {
                     let ack = ack.clone();
                     Box::pin(async move {
                         ack.store(true, Ordering::SeqCst);
@@ -403,33 +513,49 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transport::StdioTransport;
+    use crate::{
+        prompts::Prompt,
+        resource::FileSystemProvider,
+        server::{config::ServerConfig, McpServer},
+        testing::TestHarness,
+    };
 
     #[tokio::test]
     async fn test_client_lifecycle() -> Result<(), McpError> {
-        let mut client = Client::new();
-
-        // Connect using stdio transport
-        let transport = StdioTransport::new(None);
-        client.connect(transport).await?;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("hello.txt"), "hello").unwrap();
+
+        let mut config = ServerConfig::default();
+        config.resources.root_path = temp_dir.path().to_path_buf();
+        config.prompts = vec![Prompt {
+            name: "test-prompt".to_string(),
+            description: "Test prompt".to_string(),
+            arguments: vec![],
+        }];
+        let server = McpServer::new(config).await;
+        server
+            .resource_manager
+            .register_provider(
+                "file".to_string(),
+                Arc::new(FileSystemProvider::new(temp_dir.path())),
+            )
+            .await;
 
-        // Initialize client
-        let result = client
-            .initialize(ClientInfo {
-                name: "test-client".to_string(),
-                version: "1.0.0".to_string(),
-            })
-            .await?;
+        // Connects and initializes the client over an in-memory transport
+        let harness = TestHarness::start(server).await?;
 
         // Test some requests
-        let resources = client.list_resources(None).await?;
+        let resources = harness.client.list_resources(None).await?;
         assert!(!resources.resources.is_empty());
 
-        let prompts = client.list_prompts(None).await?;
+        let prompts = harness.client.list_prompts(None).await?;
         assert!(!prompts.prompts.is_empty());
+        harness
+            .assert_prompt_text("test-prompt", None, "Using prompt: test-prompt")
+            .await;
 
         // Shutdown
-        client.shutdown().await?;
+        harness.shutdown().await?;
 
         Ok(())
     }
@@ -1,4 +1,6 @@
-use std::fmt;
+use std::{fmt, time::Duration};
+
+use crate::protocol::JsonRpcError;
 
 // Core error types
 #[derive(Debug)]
@@ -14,13 +16,21 @@ pub enum McpError {
     NotConnected,
     ConnectionClosed,
     RequestTimeout,
+    RequestCancelled,
     ResourceNotFound(String),
     InvalidResource(String),
     AccessDenied(String),
     IoError,
     CapabilityNotSupported(String),
     ToolExecutionError(String),
-    Custom { code: i32, message: String },
+    /// Too many requests; the same request may succeed after `retry_after`
+    RateLimited { retry_after: Duration },
+    /// Any other error, with the optional `data` of its JSON-RPC error object
+    Custom {
+        code: i32,
+        message: String,
+        data: Option<serde_json::Value>,
+    },
 }
 
 impl McpError {
@@ -35,17 +45,30 @@ impl McpError {
             McpError::NotConnected => -32000,
             McpError::ConnectionClosed => -32001,
             McpError::RequestTimeout => -32002,
-            McpError::ShutdownTimeout => -32001,
-            McpError::ShutdownError(_) => -32002,
+            McpError::RequestCancelled => -32800,
+            McpError::ShutdownTimeout => -32010,
+            McpError::ShutdownError(_) => -32011,
             McpError::ResourceNotFound(_) => -32003,
             McpError::InvalidResource(_) => -32004,
             McpError::IoError => -32005,
             McpError::CapabilityNotSupported(_) => -32006,
             McpError::AccessDenied(_) => -32007,
             McpError::ToolExecutionError(_) => -32008,
+            McpError::RateLimited { .. } => -32009,
             McpError::Custom { code, .. } => *code,
         }
     }
+
+    /// Structured details sent as the `data` of the JSON-RPC error
+    pub fn data(&self) -> Option<serde_json::Value> {
+        match self {
+            McpError::RateLimited { retry_after } => Some(serde_json::json!({
+                "retryAfterMs": retry_after.as_millis() as u64
+            })),
+            McpError::Custom { data, .. } => data.clone(),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for McpError {
@@ -57,24 +80,76 @@ impl fmt::Display for McpError {
             McpError::InvalidParams => write!(f, "Invalid parameters"),
             McpError::InternalError(s) => write!(f, "Internal error: {}", s),
             McpError::NotConnected => write!(f, "Not connected"),
-            McpError::ConnectionClosed => write!(f, "NConnection closed"),
+            McpError::ConnectionClosed => write!(f, "Connection closed"),
             McpError::RequestTimeout => write!(f, "Request timeout"),
+            McpError::RequestCancelled => write!(f, "Request cancelled"),
             McpError::IoError => write!(f, "io error"),
             McpError::SerializationError => write!(f, "Serialization error"),
-            McpError::ResourceNotFound(s) => write!(f, " {} Resource not found", s),
-            McpError::InvalidResource(s) => write!(f, "{} Invalid resource", s),
+            McpError::ResourceNotFound(s) => write!(f, "Resource not found: {}", s),
+            McpError::InvalidResource(s) => write!(f, "Invalid resource: {}", s),
             McpError::AccessDenied(s) => write!(f, "Access denied: {}", s),
             McpError::ToolExecutionError(s) => write!(f, "Tool execution error: {}", s),
             McpError::CapabilityNotSupported(s) => write!(f, "Capability not supported: {}", s),
+            McpError::RateLimited { retry_after } => {
+                write!(f, "Rate limited, retry after {}ms", retry_after.as_millis())
+            }
             McpError::ShutdownTimeout => write!(f, "Shutdown timed out"),
             McpError::ShutdownError(msg) => write!(f, "Shutdown error: {}", msg),
-            McpError::Custom { code, message } => write!(f, "Error {}: {}", code, message),
+            McpError::Custom { code, message, .. } => write!(f, "Error {}: {}", code, message),
         }
     }
 }
 
 impl std::error::Error for McpError {}
 
+impl From<JsonRpcError> for McpError {
+    /// Map an error response back to the variant that produced it. Every variant
+    /// has its own code except `SerializationError`, which is reported as the
+    /// standard internal error and so comes back as `InternalError`. Unknown
+    /// codes become `Custom` and keep `data`.
+    fn from(error: JsonRpcError) -> Self {
+        let JsonRpcError {
+            code,
+            message,
+            data,
+        } = error;
+        // The message is the variant's `Display`, so drop the prefix it adds
+        let detail = |prefix: &str| message.strip_prefix(prefix).unwrap_or(&message).to_string();
+        match code {
+            -32700 => McpError::ParseError,
+            -32600 => McpError::InvalidRequest(detail("Invalid request: ")),
+            -32601 => McpError::MethodNotFound,
+            -32602 => McpError::InvalidParams,
+            -32603 => McpError::InternalError(detail("Internal error: ")),
+            -32800 => McpError::RequestCancelled,
+            -32000 => McpError::NotConnected,
+            -32001 => McpError::ConnectionClosed,
+            -32002 => McpError::RequestTimeout,
+            -32003 => McpError::ResourceNotFound(detail("Resource not found: ")),
+            -32004 => McpError::InvalidResource(detail("Invalid resource: ")),
+            -32005 => McpError::IoError,
+            -32006 => McpError::CapabilityNotSupported(detail("Capability not supported: ")),
+            -32007 => McpError::AccessDenied(detail("Access denied: ")),
+            -32008 => McpError::ToolExecutionError(detail("Tool execution error: ")),
+            -32009 => McpError::RateLimited {
+                retry_after: Duration::from_millis(
+                    data.as_ref()
+                        .and_then(|data| data.get("retryAfterMs"))
+                        .and_then(|ms| ms.as_u64())
+                        .unwrap_or_default(),
+                ),
+            },
+            -32010 => McpError::ShutdownTimeout,
+            -32011 => McpError::ShutdownError(detail("Shutdown error: ")),
+            _ => McpError::Custom {
+                code,
+                message: detail(&format!("Error {}: ", code)),
+                data,
+            },
+        }
+    }
+}
+
 impl From<serde_json::Error> for McpError {
     fn from(_error: serde_json::Error) -> Self {
         McpError::SerializationError
@@ -93,3 +168,71 @@ impl From<tokio::time::error::Elapsed> for McpError {
         McpError::RequestTimeout
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(error: &McpError) -> McpError {
+        McpError::from(JsonRpcError {
+            code: error.code(),
+            message: error.to_string(),
+            data: error.data(),
+        })
+    }
+
+    #[test]
+    fn test_error_codes_round_trip() {
+        let errors = [
+            McpError::ParseError,
+            McpError::InvalidRequest("bad".to_string()),
+            McpError::ShutdownTimeout,
+            McpError::ShutdownError("stuck".to_string()),
+            McpError::MethodNotFound,
+            McpError::InvalidParams,
+            McpError::InternalError("oops".to_string()),
+            McpError::NotConnected,
+            McpError::ConnectionClosed,
+            McpError::RequestTimeout,
+            McpError::RequestCancelled,
+            McpError::ResourceNotFound("file:///a".to_string()),
+            McpError::InvalidResource("file:///b".to_string()),
+            McpError::AccessDenied("file:///c".to_string()),
+            McpError::IoError,
+            McpError::CapabilityNotSupported("sampling".to_string()),
+            McpError::ToolExecutionError("failed".to_string()),
+            McpError::RateLimited {
+                retry_after: Duration::from_millis(1500),
+            },
+            McpError::Custom {
+                code: -31000,
+                message: "Quota exceeded".to_string(),
+                data: Some(serde_json::json!({ "retryAfter": 30 })),
+            },
+        ];
+
+        let mut codes: Vec<i32> = errors.iter().map(McpError::code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len(), "Error codes must be unique");
+
+        for error in &errors {
+            let mapped = round_trip(error);
+            assert_eq!(
+                std::mem::discriminant(&mapped),
+                std::mem::discriminant(error),
+                "{:?} came back as {:?}",
+                error,
+                mapped
+            );
+            assert_eq!(mapped.to_string(), error.to_string());
+            assert_eq!(mapped.data(), error.data());
+        }
+
+        // The one lossy mapping: serialization failures are internal errors on the wire
+        match round_trip(&McpError::SerializationError) {
+            McpError::InternalError(message) => assert_eq!(message, "Serialization error"),
+            other => panic!("Expected InternalError, got {:?}", other),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use protocol::JsonRpcNotification;
 
 pub mod error;
+pub mod auth;
 pub mod server;
 pub mod transport;
 pub mod resource;
@@ -8,7 +9,13 @@ pub mod protocol;
 pub mod tools;
 pub mod prompts;
 pub mod logging;
+pub mod sampling;
+pub mod roots;
+pub mod types;
 pub mod client;
+pub mod testing;
+
+mod glob;
 
 #[derive(Debug, Clone)]
 pub struct NotificationSender {
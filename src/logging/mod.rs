@@ -6,7 +6,8 @@ use tracing_core::{Event, Level, Subscriber};
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::Layer;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Debug,
@@ -71,6 +72,12 @@ pub struct LoggingManager {
     capabilities: LoggingCapabilities,
 }
 
+impl Default for LoggingManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LoggingManager {
     pub fn new() -> Self {
         Self {
@@ -84,6 +91,10 @@ impl LoggingManager {
         self.notification_sender = Some(sender);
     }
 
+    pub fn capabilities(&self) -> &LoggingCapabilities {
+        &self.capabilities
+    }
+
     pub async fn set_level(&mut self, level: String) -> Result<(), McpError> {
         self.current_level = match level.to_lowercase().as_str() {
             "debug" => LogLevel::Debug,
@@ -114,7 +125,7 @@ impl LoggingManager {
                 // Skip transport-related messages
                 if message.data.get("message")
                     .and_then(|m| m.as_str())
-                    .map_or(false, |m| {
+                    .is_some_and(|m| {
                         m.contains("Broadcasting SSE message") || 
                         m.contains("Failed to broadcast message") ||
                         m.contains("-> ") ||
@@ -1,12 +1,18 @@
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
-use crate::{error::McpError, protocol::JsonRpcNotification, NotificationSender};
+use crate::{
+    error::McpError, protocol::JsonRpcNotification, resource::ResourceContent, server::ClientPeer,
+    NotificationSender,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptArgument {
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(default)]
     pub required: bool,
 }
 
@@ -24,14 +30,13 @@ pub enum MessageContent {
     #[serde(rename = "text")]
     Text { text: String },
     #[serde(rename = "image")]
-    Image { data: String, mime_type: String },
-    #[serde(rename = "resource")]
-    Resource {
-        uri: String,
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
         mime_type: String,
-        text: Option<String>,
-        blob: Option<String>,
     },
+    #[serde(rename = "resource")]
+    Resource { resource: ResourceContent },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +58,7 @@ pub struct ListPromptsRequest {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListPromptsResponse {
     pub prompts: Vec<Prompt>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -65,13 +71,28 @@ pub struct GetPromptRequest {
     pub arguments: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct PromptCapabilities {
     pub list_changed: bool,
 }
 
+// Prompt Generator trait
+#[async_trait]
+pub trait PromptGenerator: Send + Sync {
+    /// Build the messages for `prompt` from validated arguments. `client` is the
+    /// client that asked for the prompt, which the generator may call back into.
+    async fn generate(
+        &self,
+        prompt: &Prompt,
+        arguments: Option<serde_json::Value>,
+        client: ClientPeer,
+    ) -> Result<PromptResult, McpError>;
+}
+
 pub struct PromptManager {
     pub prompts: Arc<RwLock<HashMap<String, Prompt>>>,
+    generators: Arc<RwLock<HashMap<String, Arc<dyn PromptGenerator>>>>,
     pub capabilities: PromptCapabilities,
     pub notification_sender: Option<NotificationSender>,
 }
@@ -80,6 +101,7 @@ impl PromptManager {
     pub fn new(capabilities: PromptCapabilities) -> Self {
         Self {
             prompts: Arc::new(RwLock::new(HashMap::new())),
+            generators: Arc::new(RwLock::new(HashMap::new())),
             capabilities,
             notification_sender: None,
         }
@@ -97,6 +119,12 @@ impl PromptManager {
         self.notify_list_changed().await.ok();
     }
 
+    /// Register a prompt whose messages `generator` builds on each request
+    pub async fn register_prompt_generator(&self, prompt: Prompt, generator: Arc<dyn PromptGenerator>) {
+        self.generators.write().await.insert(prompt.name.clone(), generator);
+        self.register_prompt(prompt).await;
+    }
+
     pub async fn list_prompts(&self, _cursor: Option<String>) -> Result<ListPromptsResponse, McpError> {
         let prompts = self.prompts.read().await;
         let prompts: Vec<_> = prompts.values().cloned().collect();
@@ -111,19 +139,7 @@ impl PromptManager {
         let prompts = self.prompts.read().await;
         let prompt = prompts.get(name)
             .ok_or_else(|| McpError::InvalidRequest(format!("Unknown prompt: {}", name)))?;
-
-        // Validate required arguments
-        if let Some(args) = &arguments {
-            for arg in prompt.arguments.iter().filter(|a| a.required) {
-                if !args.get(&arg.name).is_some() {
-                    return Err(McpError::InvalidRequest(
-                        format!("Missing required argument: {}", arg.name)
-                    ));
-                }
-            }
-        } else if prompt.arguments.iter().any(|a| a.required) {
-            return Err(McpError::InvalidRequest("Missing required arguments".to_string()));
-        }
+        Self::validate_arguments(prompt, &arguments)?;
 
         // Here you would generate the actual prompt messages based on the template
         // This is a simple example
@@ -140,6 +156,40 @@ impl PromptManager {
         })
     }
 
+    /// Get a prompt for the client that requested it, running its generator if it has one
+    pub async fn get_prompt_with_client(
+        &self,
+        name: &str,
+        arguments: Option<serde_json::Value>,
+        client: ClientPeer,
+    ) -> Result<PromptResult, McpError> {
+        let generator = self.generators.read().await.get(name).cloned();
+        let Some(generator) = generator else {
+            return self.get_prompt(name, arguments).await;
+        };
+
+        let prompt = self.prompts.read().await.get(name).cloned()
+            .ok_or_else(|| McpError::InvalidRequest(format!("Unknown prompt: {}", name)))?;
+        Self::validate_arguments(&prompt, &arguments)?;
+        generator.generate(&prompt, arguments, client).await
+    }
+
+    fn validate_arguments(prompt: &Prompt, arguments: &Option<serde_json::Value>) -> Result<(), McpError> {
+        // Validate required arguments
+        if let Some(args) = arguments {
+            for arg in prompt.arguments.iter().filter(|a| a.required) {
+                if args.get(&arg.name).is_none() {
+                    return Err(McpError::InvalidRequest(
+                        format!("Missing required argument: {}", arg.name)
+                    ));
+                }
+            }
+        } else if prompt.arguments.iter().any(|a| a.required) {
+            return Err(McpError::InvalidRequest("Missing required arguments".to_string()));
+        }
+        Ok(())
+    }
+
     async fn notify_list_changed(&self) -> Result<(), McpError> {
         if !self.capabilities.list_changed {
             return Ok(());
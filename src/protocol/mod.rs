@@ -1,25 +1,150 @@
 use crate::{
     error::McpError,
-    transport::{JsonRpcMessage, Transport, TransportChannels, TransportCommand, TransportEvent},
+    types::{ClientCapabilities, ServerCapabilities},
+    transport::{
+        JsonRpcMessage, SessionId, Transport, TransportChannels, TransportCommand, TransportEvent,
+    },
 };
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc, time::Duration};
-use tokio::sync::{mpsc, RwLock};
+use tokio::{
+    sync::{mpsc, RwLock, Semaphore},
+    task::JoinSet,
+};
 
 // Constants
 pub const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 60000;
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 64;
+
+/// MCP protocol versions this crate speaks, oldest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+pub const LATEST_PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// The version to answer an `initialize` request with: the requested one if it
+/// is supported, otherwise the newest supported one. Versions are dates, so
+/// they order as strings.
+pub fn negotiate_protocol_version<S: AsRef<str>>(requested: &str, supported: &[S]) -> Option<String> {
+    if supported.iter().any(|version| version.as_ref() == requested) {
+        return Some(requested.to_string());
+    }
+    supported
+        .iter()
+        .map(|version| version.as_ref())
+        .max()
+        .map(String::from)
+}
+
+/// How long a closing connection waits for in-flight request handlers
+const HANDLER_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
 
 // Protocol Options
 #[derive(Debug, Clone)]
 pub struct ProtocolOptions {
     /// Whether to enforce strict capability checking
     pub enforce_strict_capabilities: bool,
+    /// How many incoming requests may be handled at once; later ones wait for a slot
+    pub max_concurrent_requests: usize,
+    /// Ping the peer periodically to notice when it stops answering
+    pub heartbeat: Option<HeartbeatOptions>,
 }
 
 impl Default for ProtocolOptions {
     fn default() -> Self {
         Self {
             enforce_strict_capabilities: false,
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            heartbeat: None,
+        }
+    }
+}
+
+/// Keepalive for single-peer connections such as stdio or SSE. After `max_failures`
+/// pings in a row go unanswered the connection is treated as closed.
+#[derive(Debug, Clone)]
+pub struct HeartbeatOptions {
+    /// Time between pings
+    pub interval: Duration,
+    /// How long to wait for each pong
+    pub timeout: Duration,
+    pub max_failures: u32,
+}
+
+impl Default for HeartbeatOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+            max_failures: 3,
+        }
+    }
+}
+
+/// Capabilities one side of a connection declared during `initialize`
+#[derive(Debug, Clone)]
+pub enum PeerCapabilities {
+    Client(ClientCapabilities),
+    Server(ServerCapabilities),
+}
+
+impl PeerCapabilities {
+    /// The capability a peer must have declared to answer a `method` request
+    fn check_request(&self, method: &str) -> Result<(), McpError> {
+        let missing = match self {
+            Self::Server(server) => match method {
+                "logging/setLevel" => server.logging.is_none().then_some("logging"),
+                "prompts/list" | "prompts/get" => server.prompts.is_none().then_some("prompts"),
+                "resources/list" | "resources/templates/list" | "resources/read" => {
+                    server.resources.is_none().then_some("resources")
+                }
+                "resources/subscribe" | "resources/unsubscribe" => {
+                    let subscribe = server.resources.as_ref().is_some_and(|r| r.subscribe);
+                    (!subscribe).then_some("resources.subscribe")
+                }
+                "tools/list" | "tools/call" => server.tools.is_none().then_some("tools"),
+                _ => None,
+            },
+            Self::Client(client) => match method {
+                "sampling/createMessage" => client.sampling.is_none().then_some("sampling"),
+                "roots/list" => client.roots.is_none().then_some("roots"),
+                _ => None,
+            },
+        };
+        Self::require(missing)
+    }
+
+    /// The capability a peer must have declared to send a `method` notification
+    fn check_notification(&self, method: &str) -> Result<(), McpError> {
+        let missing = match self {
+            Self::Server(server) => match method {
+                "notifications/message" => server.logging.is_none().then_some("logging"),
+                "notifications/resources/updated" | "notifications/resources/list_changed" => {
+                    server.resources.is_none().then_some("resources")
+                }
+                "notifications/prompts/list_changed" => server.prompts.is_none().then_some("prompts"),
+                "notifications/tools/list_changed" => server.tools.is_none().then_some("tools"),
+                _ => None,
+            },
+            Self::Client(client) => match method {
+                "notifications/roots/list_changed" => {
+                    let list_changed = client.roots.as_ref().is_some_and(|r| r.list_changed);
+                    (!list_changed).then_some("roots.listChanged")
+                }
+                _ => None,
+            },
+        };
+        Self::require(missing)
+    }
+
+    /// The capability a peer must have declared to handle `method` requests.
+    /// Handling a request takes the same capability as being sent it.
+    fn check_handler(&self, method: &str) -> Result<(), McpError> {
+        self.check_request(method)
+    }
+
+    fn require(missing: Option<&str>) -> Result<(), McpError> {
+        match missing {
+            Some(capability) => Err(McpError::CapabilityNotSupported(capability.to_string())),
+            None => Ok(()),
         }
     }
 }
@@ -27,16 +152,59 @@ impl Default for ProtocolOptions {
 // Progress types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Progress {
-    pub progress: u64,
-    pub total: Option<u64>,
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// A JSON-RPC request id, which peers may send as a number or a string
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+impl From<i64> for RequestId {
+    fn from(id: i64) -> Self {
+        RequestId::Number(id)
+    }
+}
+
+impl From<String> for RequestId {
+    fn from(id: String) -> Self {
+        RequestId::String(id)
+    }
+}
+
+impl From<&str> for RequestId {
+    fn from(id: &str) -> Self {
+        RequestId::String(id.to_string())
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RequestId::Number(id) => write!(f, "{}", id),
+            RequestId::String(id) => write!(f, "{}", id),
+        }
+    }
 }
 
+/// Token a requester attaches as `_meta.progressToken` to receive progress updates
+pub type ProgressToken = RequestId;
+
 pub type ProgressCallback = Box<dyn Fn(Progress) + Send + Sync>;
 
 pub struct RequestOptions {
     pub on_progress: Option<ProgressCallback>,
     pub signal: Option<tokio::sync::watch::Receiver<bool>>,
     pub timeout: Option<Duration>,
+    /// Restart the timeout whenever a progress notification arrives
+    pub reset_timeout_on_progress: bool,
 }
 
 impl Default for RequestOptions {
@@ -45,6 +213,7 @@ impl Default for RequestOptions {
             on_progress: None,
             signal: None,
             timeout: Some(Duration::from_millis(DEFAULT_REQUEST_TIMEOUT_MS)),
+            reset_timeout_on_progress: false,
         }
     }
 }
@@ -52,6 +221,64 @@ impl Default for RequestOptions {
 // Request handler extra data
 pub struct RequestHandlerExtra {
     pub signal: tokio::sync::watch::Receiver<bool>,
+    /// Session the request arrived on, for transports that serve several clients
+    pub session_id: Option<SessionId>,
+    /// Reports progress to the requester, if it asked for updates
+    pub progress: ProgressReporter,
+    /// The connection the request arrived on, for sending requests back to the
+    /// requester with `request_to(session_id, ..)`
+    pub peer: Protocol,
+}
+
+/// Sends `notifications/progress` for one incoming request
+#[derive(Clone)]
+pub struct ProgressReporter {
+    token: Option<ProgressToken>,
+    session_id: Option<SessionId>,
+    cmd_tx: mpsc::Sender<TransportCommand>,
+}
+
+impl ProgressReporter {
+    /// The requester's progress token, if it sent one
+    pub fn token(&self) -> Option<&ProgressToken> {
+        self.token.as_ref()
+    }
+
+    /// Report progress to the requester. Does nothing if it did not ask for updates.
+    pub async fn report(
+        &self,
+        progress: f64,
+        total: Option<f64>,
+        message: Option<String>,
+    ) -> Result<(), McpError> {
+        let Some(token) = &self.token else {
+            return Ok(());
+        };
+        let notification = ProgressNotification {
+            progress_token: token.clone(),
+            progress,
+            total,
+            message,
+        };
+        let msg = JsonRpcMessage::Notification(JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/progress".to_string(),
+            params: Some(serde_json::to_value(notification)?),
+        });
+        self.cmd_tx
+            .send(Protocol::address(self.session_id.clone(), msg))
+            .await
+            .map_err(|_| McpError::ConnectionClosed)
+    }
+}
+
+// Notification handler extra data
+pub struct NotificationHandlerExtra {
+    /// Session the notification arrived on, for transports that serve several clients
+    pub session_id: Option<SessionId>,
+    /// The connection the notification arrived on. Notifications are handled in
+    /// order on the message loop, so spawn a task before awaiting a request on it.
+    pub peer: Protocol,
 }
 
 // Protocol implementation
@@ -59,12 +286,17 @@ pub struct Protocol {
     pub cmd_tx: Option<mpsc::Sender<TransportCommand>>,
     pub event_rx: Option<Arc<tokio::sync::Mutex<mpsc::Receiver<TransportEvent>>>>,
     pub options: ProtocolOptions,
-    pub request_message_id: Arc<RwLock<u64>>,
+    pub request_message_id: Arc<RwLock<i64>>,
     pub request_handlers: Arc<RwLock<HashMap<String, RequestHandler>>>,
     pub notification_handlers: Arc<RwLock<HashMap<String, NotificationHandler>>>,
-    pub response_handlers: Arc<RwLock<HashMap<u64, ResponseHandler>>>,
-    pub progress_handlers: Arc<RwLock<HashMap<u64, ProgressCallback>>>,
-    //request_abort_controllers: Arc<RwLock<HashMap<String, tokio::sync::watch::Sender<bool>>>>,
+    pub response_handlers: ResponseHandlers,
+    pub progress_handlers: ProgressHandlers,
+    pub session_closed_handler: Option<Arc<SessionClosedHandler>>,
+    pub heartbeat_failed_handler: Option<Arc<HeartbeatFailedHandler>>,
+    request_guard: Option<Arc<RequestGuard>>,
+    request_abort_controllers: AbortControllers,
+    local_capabilities: Arc<RwLock<Option<PeerCapabilities>>>,
+    remote_capabilities: RemoteCapabilities,
 }
 
 type RequestHandler = Box<
@@ -72,9 +304,29 @@ type RequestHandler = Box<
         + Send
         + Sync,
 >;
-type NotificationHandler =
-    Box<dyn Fn(JsonRpcNotification) -> BoxFuture<Result<(), McpError>> + Send + Sync>;
+type NotificationHandler = Box<
+    dyn Fn(JsonRpcNotification, NotificationHandlerExtra) -> BoxFuture<Result<(), McpError>>
+        + Send
+        + Sync,
+>;
+type SessionClosedHandler = Box<dyn Fn(SessionId) -> BoxFuture<()> + Send + Sync>;
+type HeartbeatFailedHandler = Box<dyn Fn() -> BoxFuture<()> + Send + Sync>;
+/// Decides whether an incoming request from a session may be handled at all
+type RequestGuard =
+    Box<dyn Fn(&JsonRpcRequest, Option<&SessionId>) -> Result<(), McpError> + Send + Sync>;
 type ResponseHandler = Box<dyn FnOnce(Result<JsonRpcResponse, McpError>) + Send + Sync>;
+/// Handlers of outgoing requests awaiting a response, keyed by the session the
+/// request went to and its id, so no other session can answer it
+pub type ResponseHandlers =
+    Arc<RwLock<HashMap<(Option<SessionId>, RequestId), ResponseHandler>>>;
+/// Progress callbacks of outgoing requests, keyed like `ResponseHandlers`
+pub type ProgressHandlers =
+    Arc<RwLock<HashMap<(Option<SessionId>, ProgressToken), ProgressCallback>>>;
+/// Cancellation signals of in-flight incoming requests, keyed by session and request id
+type AbortControllers =
+    Arc<RwLock<HashMap<(Option<SessionId>, RequestId), tokio::sync::watch::Sender<bool>>>>;
+/// Capabilities each peer declared, keyed by session
+type RemoteCapabilities = Arc<RwLock<HashMap<Option<SessionId>, PeerCapabilities>>>;
 type BoxFuture<T> = std::pin::Pin<Box<dyn std::future::Future<Output = T> + Send>>;
 
 // Add new builder struct
@@ -82,6 +334,12 @@ pub struct ProtocolBuilder {
     options: ProtocolOptions,
     request_handlers: HashMap<String, RequestHandler>,
     notification_handlers: HashMap<String, NotificationHandler>,
+    session_closed_handler: Option<SessionClosedHandler>,
+    heartbeat_failed_handler: Option<HeartbeatFailedHandler>,
+    request_guard: Option<RequestGuard>,
+    request_abort_controllers: AbortControllers,
+    progress_handlers: ProgressHandlers,
+    capabilities: Option<PeerCapabilities>,
 }
 
 impl ProtocolBuilder {
@@ -90,9 +348,21 @@ impl ProtocolBuilder {
             options: options.unwrap_or_default(),
             request_handlers: HashMap::new(),
             notification_handlers: HashMap::new(),
+            session_closed_handler: None,
+            heartbeat_failed_handler: None,
+            request_guard: None,
+            request_abort_controllers: Arc::new(RwLock::new(HashMap::new())),
+            progress_handlers: Arc::new(RwLock::new(HashMap::new())),
+            capabilities: None,
         }
     }
 
+    /// Declare the capabilities this side supports, known up front on servers
+    pub fn with_capabilities(mut self, capabilities: PeerCapabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
     pub fn with_request_handler(mut self, method: &str, handler: RequestHandler) -> Self {
         self.request_handlers.insert(method.to_string(), handler);
         self
@@ -104,11 +374,34 @@ impl ProtocolBuilder {
         self
     }
 
+    /// Called when a client of a multi-session transport disconnects
+    pub fn with_session_closed_handler(mut self, handler: SessionClosedHandler) -> Self {
+        self.session_closed_handler = Some(handler);
+        self
+    }
+
+    /// Called when the heartbeat gives up on the peer, just before the connection closes
+    pub fn with_heartbeat_failed_handler(mut self, handler: HeartbeatFailedHandler) -> Self {
+        self.heartbeat_failed_handler = Some(handler);
+        self
+    }
+
+    /// Checked before every incoming request; requests it refuses get its error
+    /// as their response and never reach a handler
+    pub fn with_request_guard(mut self, guard: RequestGuard) -> Self {
+        self.request_guard = Some(guard);
+        self
+    }
+
     fn register_default_handlers(mut self) -> Self {
         // Add default handlers
+        // Raise the signal of the cancelled request so its handler stops
+        // and no response is sent
+        let controllers = Arc::clone(&self.request_abort_controllers);
         self = self.with_notification_handler(
-            "cancelled",
-            Box::new(|notification| {
+            "notifications/cancelled",
+            Box::new(move |notification, extra| {
+                let controllers = Arc::clone(&controllers);
                 Box::pin(async move {
                     let params = notification.params.ok_or(McpError::InvalidParams)?;
 
@@ -118,20 +411,66 @@ impl ProtocolBuilder {
                     tracing::debug!(
                         "Request {} cancelled: {}",
                         cancelled.request_id,
-                        cancelled.reason
+                        cancelled.reason.as_deref().unwrap_or("no reason given")
                     );
 
+                    let key = (extra.session_id, cancelled.request_id);
+                    if let Some(signal) = controllers.read().await.get(&key) {
+                        let _ = signal.send(true);
+                    }
+                    Ok(())
+                })
+            }),
+        );
+
+        // Route progress updates to the callback of the request they belong to
+        let progress_handlers = Arc::clone(&self.progress_handlers);
+        self = self.with_notification_handler(
+            "notifications/progress",
+            Box::new(move |notification, extra| {
+                let progress_handlers = Arc::clone(&progress_handlers);
+                Box::pin(async move {
+                    let params = notification.params.ok_or(McpError::InvalidParams)?;
+                    let update: ProgressNotification =
+                        serde_json::from_value(params).map_err(|_| McpError::InvalidParams)?;
+
+                    let key = (extra.session_id, update.progress_token);
+                    if let Some(callback) = progress_handlers.read().await.get(&key) {
+                        callback(Progress {
+                            progress: update.progress,
+                            total: update.total,
+                            message: update.message,
+                        });
+                    }
                     Ok(())
                 })
             }),
         );
 
-        // Add other default handlers similarly...
+        // Answer pings so the peer can tell we are alive
+        self = self.with_request_handler(
+            "ping",
+            Box::new(|_request, _extra| Box::pin(async { Ok(serde_json::json!({})) })),
+        );
+
         self
     }
 
-    pub fn build(self) -> Protocol {
-        let protocol = Protocol {
+    /// Build the protocol. Request handlers for methods the declared
+    /// capabilities don't cover are dropped, so they are never answered.
+    pub fn build(mut self) -> Protocol {
+        if let Some(capabilities) = &self.capabilities {
+            self.request_handlers
+                .retain(|method, _| match capabilities.check_handler(method) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::warn!("Not handling {}: {}", method, e);
+                        false
+                    }
+                });
+        }
+
+        Protocol {
             cmd_tx: None,
             event_rx: None,
             options: self.options,
@@ -139,11 +478,14 @@ impl ProtocolBuilder {
             request_handlers: Arc::new(RwLock::new(self.request_handlers)),
             notification_handlers: Arc::new(RwLock::new(self.notification_handlers)),
             response_handlers: Arc::new(RwLock::new(HashMap::new())),
-            progress_handlers: Arc::new(RwLock::new(HashMap::new())),
-            //request_abort_controllers: Arc::new(RwLock::new(HashMap::new())),
-        };
-
-        protocol
+            progress_handlers: self.progress_handlers,
+            session_closed_handler: self.session_closed_handler.map(Arc::new),
+            heartbeat_failed_handler: self.heartbeat_failed_handler.map(Arc::new),
+            request_guard: self.request_guard.map(Arc::new),
+            request_abort_controllers: self.request_abort_controllers,
+            local_capabilities: Arc::new(RwLock::new(self.capabilities)),
+            remote_capabilities: Arc::new(RwLock::new(HashMap::new())),
+        }
     }
 }
 
@@ -156,7 +498,7 @@ pub struct ProtocolHandle {
 impl ProtocolHandle {
     pub async fn close(&self) -> Result<(), McpError> {
         // Send close signal
-        if let Err(_) = self.close_tx.send(()).await {
+        if self.close_tx.send(()).await.is_err() {
             tracing::warn!("Protocol already closed");
         }
         
@@ -172,6 +514,145 @@ impl ProtocolHandle {
     }
 }
 
+impl Clone for Protocol {
+    fn clone(&self) -> Self {
+        Protocol {
+            cmd_tx: self.cmd_tx.clone(),
+            event_rx: self.event_rx.clone(),
+            options: self.options.clone(),
+            request_message_id: Arc::clone(&self.request_message_id),
+            request_handlers: Arc::clone(&self.request_handlers),
+            notification_handlers: Arc::clone(&self.notification_handlers),
+            response_handlers: Arc::clone(&self.response_handlers),
+            progress_handlers: Arc::clone(&self.progress_handlers),
+            session_closed_handler: self.session_closed_handler.clone(),
+            heartbeat_failed_handler: self.heartbeat_failed_handler.clone(),
+            request_guard: self.request_guard.clone(),
+            request_abort_controllers: Arc::clone(&self.request_abort_controllers),
+            local_capabilities: Arc::clone(&self.local_capabilities),
+            remote_capabilities: Arc::clone(&self.remote_capabilities),
+        }
+    }
+}
+
+/// Handles the messages the connection loop receives
+struct Dispatcher {
+    request_handlers: Arc<RwLock<HashMap<String, RequestHandler>>>,
+    notification_handlers: Arc<RwLock<HashMap<String, NotificationHandler>>>,
+    response_handlers: ResponseHandlers,
+    abort_controllers: AbortControllers,
+    permits: Arc<Semaphore>,
+    cmd_tx: mpsc::Sender<TransportCommand>,
+    peer: Protocol,
+}
+
+impl Dispatcher {
+    /// Start handling an incoming request. The returned future resolves to its
+    /// response, or `None` if the request was cancelled.
+    async fn request(
+        &self,
+        req: JsonRpcRequest,
+        session_id: Option<SessionId>,
+    ) -> BoxFuture<Option<JsonRpcMessage>> {
+        if req.jsonrpc != "2.0" {
+            let error = McpError::InvalidRequest(format!("Unsupported JSON-RPC version {}", req.jsonrpc));
+            return Box::pin(futures::future::ready(Some(Protocol::error(Some(req.id), error))));
+        }
+        if let Some(guard) = &self.peer.request_guard {
+            if let Err(e) = guard(&req, session_id.as_ref()) {
+                tracing::debug!("Refused request {}: {}", req.method, e);
+                return Box::pin(futures::future::ready(Some(Protocol::error(Some(req.id), e))));
+            }
+        }
+        let handlers = self.request_handlers.read().await;
+        let Some(handler) = handlers.get(&req.method) else {
+            tracing::debug!("No handler for method {}", req.method);
+            return Box::pin(futures::future::ready(Some(Protocol::error(
+                Some(req.id),
+                McpError::MethodNotFound,
+            ))));
+        };
+
+        let (signal_tx, mut signal) = tokio::sync::watch::channel(false);
+        let key = (session_id.clone(), req.id.clone());
+        self.abort_controllers.write().await.insert(key.clone(), signal_tx);
+
+        let extra = RequestHandlerExtra {
+            signal: signal.clone(),
+            session_id: session_id.clone(),
+            progress: ProgressReporter {
+                token: Protocol::progress_token(&req),
+                session_id,
+                cmd_tx: self.cmd_tx.clone(),
+            },
+            peer: self.peer.clone(),
+        };
+        let response = handler(req.clone(), extra);
+        let permits = Arc::clone(&self.permits);
+        let abort_controllers = Arc::clone(&self.abort_controllers);
+
+        Box::pin(async move {
+            let result = tokio::select! {
+                result = async {
+                    let _permit = permits.acquire_owned().await;
+                    response.await
+                } => Some(result),
+                _ = signal.wait_for(|cancelled| *cancelled) => None,
+            };
+            abort_controllers.write().await.remove(&key);
+
+            // A cancelled request gets no response
+            if result.is_none() {
+                tracing::debug!("Request {} cancelled", req.id);
+            }
+            result.map(|result| Protocol::response(req.id, result))
+        })
+    }
+
+    /// Deliver a response to the request waiting for it, if `session_id` is the
+    /// session the request went to. Returns an error reply for a message that
+    /// only parsed as a response because it has no method.
+    async fn response(
+        &self,
+        resp: JsonRpcResponse,
+        session_id: Option<SessionId>,
+    ) -> Option<JsonRpcMessage> {
+        let Some(id) = &resp.id else {
+            if resp.error.is_none() {
+                return Some(Protocol::error(None, McpError::InvalidRequest("Missing method".to_string())));
+            }
+            tracing::error!("Peer could not parse a message: {:?}", resp.error);
+            return None;
+        };
+        let mut handlers = self.response_handlers.write().await;
+        if let Some(handler) = handlers.remove(&(session_id, id.clone())) {
+            handler(Ok(resp));
+            return None;
+        }
+        if resp.result.is_none() && resp.error.is_none() {
+            return Some(Protocol::error(
+                Some(id.clone()),
+                McpError::InvalidRequest("Missing method".to_string()),
+            ));
+        }
+        tracing::debug!("Ignoring response to unknown request {}", id);
+        None
+    }
+
+    async fn notification(&self, notif: JsonRpcNotification, session_id: Option<SessionId>) {
+        let handlers = self.notification_handlers.read().await;
+        if let Some(handler) = handlers.get(&notif.method) {
+            let extra = NotificationHandlerExtra {
+                session_id,
+                peer: self.peer.clone(),
+            };
+            if let Err(e) = handler(notif.clone(), extra).await {
+                tracing::error!("Notification handler error: {:?}", e);
+            }
+        }
+    }
+}
+
 impl Protocol {
     pub fn builder(options: Option<ProtocolOptions>) -> ProtocolBuilder {
         ProtocolBuilder::new(options).register_default_handlers()
@@ -189,92 +670,166 @@ impl Protocol {
         let (close_tx, mut close_rx) = mpsc::channel(1);
 
         let event_rx = Arc::clone(&event_rx);
-        let request_handlers = Arc::clone(&self.request_handlers);
-        let notification_handlers = Arc::clone(&self.notification_handlers);
-        let response_handlers = Arc::clone(&self.response_handlers);
-        let cmd_tx = cmd_tx.clone();
+        let session_closed_handler = self.session_closed_handler.clone();
+        let dispatcher = Dispatcher {
+            request_handlers: Arc::clone(&self.request_handlers),
+            notification_handlers: Arc::clone(&self.notification_handlers),
+            response_handlers: Arc::clone(&self.response_handlers),
+            abort_controllers: Arc::clone(&self.request_abort_controllers),
+            permits: Arc::new(Semaphore::new(self.options.max_concurrent_requests.max(1))),
+            cmd_tx: cmd_tx.clone(),
+            peer: self.clone(),
+        };
+
+        if let Some(heartbeat) = self.options.heartbeat.clone() {
+            tokio::spawn(Self::heartbeat(self.clone(), heartbeat, close_tx.clone()));
+        }
 
         // Spawn message handling loop
         tokio::spawn({
             let cmd_tx = cmd_tx.clone();
             async move {
+                let mut tasks = JoinSet::new();
                 loop {
                     tokio::select! {
                         _ = close_rx.recv() => {
                             tracing::debug!("Received close signal");
                             break;
                         }
+                        Some(result) = tasks.join_next(), if !tasks.is_empty() => {
+                            if let Err(e) = result {
+                                tracing::error!("Request handler failed: {:?}", e);
+                            }
+                        }
                         event = async {
                             let mut rx = event_rx.lock().await;
                             rx.recv().await
                         } => {
-                            match event {
-                                Some(TransportEvent::Message(msg)) => {
-                                    // ... existing message handling code ...
-                                    match msg {
-                                        JsonRpcMessage::Request(req) => {
-                                            let handlers = request_handlers.read().await;
-                                            if let Some(handler) = handlers.get(&req.method) {
-                                                let (tx, rx) = tokio::sync::watch::channel(false);
-                                                let extra = RequestHandlerExtra { signal: rx };
-
-                                                match handler(req.clone(), extra).await {
-                                                    Ok(result) => {
-                                                        let response = JsonRpcMessage::Response(JsonRpcResponse {
-                                                            jsonrpc: "2.0".to_string(),
-                                                            id: req.id,
-                                                            result: Some(result),
-                                                            error: None,
-                                                        });
-                                                        if let Err(e) = cmd_tx.send(TransportCommand::SendMessage(response)).await {
-                                                            tracing::error!("Failed to send response: {:?}", e);
-                                                        }
-                                                    }
-                                                    Err(e) => {
-                                                        let response = JsonRpcMessage::Response(JsonRpcResponse {
-                                                            jsonrpc: "2.0".to_string(),
-                                                            id: req.id,
-                                                            result: None,
-                                                            error: Some(JsonRpcError {
-                                                                code: e.code(),
-                                                                message: e.to_string(),
-                                                                data: None,
-                                                            }),
-                                                        });
-                                                        if let Err(e) = cmd_tx.send(TransportCommand::SendMessage(response)).await {
-                                                            tracing::error!("Failed to send error response: {:?}", e);
-                                                        }
-                                                    }
-                                                }
-                                            }
+                            let (session_id, msg) = match event {
+                                Some(TransportEvent::Message(msg)) => (None, msg),
+                                Some(TransportEvent::SessionMessage(session_id, msg)) => {
+                                    (Some(session_id), msg)
+                                }
+                                Some(TransportEvent::SessionClosed(session_id)) => {
+                                    tracing::debug!("Session {} closed", session_id);
+                                    dispatcher
+                                        .peer
+                                        .remote_capabilities
+                                        .write()
+                                        .await
+                                        .remove(&Some(session_id.clone()));
+                                    if let Some(handler) = &session_closed_handler {
+                                        handler(session_id).await;
+                                    }
+                                    continue;
+                                }
+                                Some(TransportEvent::Malformed(session_id, e)) => {
+                                    // The request's id is unknown, so the error goes back with a null id
+                                    let reply = Self::address(session_id, Self::error(None, e));
+                                    if let Err(e) = cmd_tx.send(reply).await {
+                                        tracing::error!("Failed to send error response: {:?}", e);
+                                    }
+                                    continue;
+                                }
+                                Some(TransportEvent::Error(e)) => {
+                                    tracing::error!("Transport error: {:?}", e);
+                                    continue;
+                                }
+                                Some(TransportEvent::Closed) | None => {
+                                    break;
+                                }
+                            };
+
+                            match msg {
+                                JsonRpcMessage::Request(req) => {
+                                    // Handlers run as their own tasks so a slow one, or one
+                                    // waiting on the peer, never stalls this loop
+                                    let response = dispatcher.request(req, session_id.clone()).await;
+                                    let cmd_tx = cmd_tx.clone();
+                                    tasks.spawn(async move {
+                                        let Some(response) = response.await else {
+                                            return;
+                                        };
+                                        // Replies are queued on the transport's command
+                                        // channel, which writes one whole message at a time
+                                        if let Err(e) = cmd_tx.send(Self::address(session_id, response)).await {
+                                            tracing::error!("Failed to send response: {:?}", e);
                                         }
-                                        JsonRpcMessage::Response(resp) => {
-                                            let mut handlers = response_handlers.write().await;
-                                            if let Some(handler) = handlers.remove(&resp.id) {
-                                                handler(Ok(resp));
+                                    });
+                                }
+                                JsonRpcMessage::Batch(messages) if messages.is_empty() => {
+                                    let error = Self::error(None, McpError::InvalidRequest("Empty batch".to_string()));
+                                    if let Err(e) = cmd_tx.send(Self::address(session_id, error)).await {
+                                        tracing::error!("Failed to send error response: {:?}", e);
+                                    }
+                                }
+                                JsonRpcMessage::Batch(messages) => {
+                                    // Requests in a batch run concurrently and are answered
+                                    // together in one batch
+                                    let mut responses = Vec::new();
+                                    for msg in messages {
+                                        match msg {
+                                            JsonRpcMessage::Request(req) => {
+                                                responses.push(dispatcher.request(req, session_id.clone()).await);
                                             }
-                                        }
-                                        JsonRpcMessage::Notification(notif) => {
-                                            let handlers = notification_handlers.read().await;
-                                            if let Some(handler) = handlers.get(&notif.method) {
-                                                if let Err(e) = handler(notif.clone()).await {
-                                                    tracing::error!("Notification handler error: {:?}", e);
+                                            JsonRpcMessage::Response(resp) => {
+                                                if let Some(error) = dispatcher.response(resp, session_id.clone()).await {
+                                                    responses.push(Box::pin(futures::future::ready(Some(error))));
                                                 }
                                             }
+                                            JsonRpcMessage::Notification(notif) => {
+                                                dispatcher.notification(notif, session_id.clone()).await
+                                            }
+                                            JsonRpcMessage::Batch(_) => {
+                                                let error = McpError::InvalidRequest("Nested batch".to_string());
+                                                responses.push(Box::pin(futures::future::ready(Some(Self::error(None, error)))));
+                                            }
                                         }
                                     }
+
+                                    if !responses.is_empty() {
+                                        let cmd_tx = cmd_tx.clone();
+                                        tasks.spawn(async move {
+                                            let responses: Vec<JsonRpcMessage> = futures::future::join_all(responses)
+                                                .await
+                                                .into_iter()
+                                                .flatten()
+                                                .collect();
+                                            if responses.is_empty() {
+                                                return;
+                                            }
+                                            let batch = JsonRpcMessage::Batch(responses);
+                                            if let Err(e) = cmd_tx.send(Self::address(session_id, batch)).await {
+                                                tracing::error!("Failed to send batch response: {:?}", e);
+                                            }
+                                        });
+                                    }
                                 }
-                                Some(TransportEvent::Error(e)) => {
-                                    tracing::error!("Transport error: {:?}", e);
+                                JsonRpcMessage::Response(resp) => {
+                                    if let Some(error) = dispatcher.response(resp, session_id.clone()).await {
+                                        if let Err(e) = cmd_tx.send(Self::address(session_id, error)).await {
+                                            tracing::error!("Failed to send error response: {:?}", e);
+                                        }
+                                    }
                                 }
-                                Some(TransportEvent::Closed) | None => {
-                                    break;
+                                JsonRpcMessage::Notification(notif) => {
+                                    dispatcher.notification(notif, session_id).await
                                 }
                             }
                         }
                     }
                 }
 
+                // Let in-flight handlers reply before closing, then abort stragglers
+                let drained = tokio::time::timeout(HANDLER_DRAIN_TIMEOUT, async {
+                    while tasks.join_next().await.is_some() {}
+                })
+                .await;
+                if drained.is_err() {
+                    tracing::warn!("Aborting {} unfinished request handlers", tasks.len());
+                    tasks.abort_all();
+                }
+
                 // Cleanup on exit
                 let _ = cmd_tx.send(TransportCommand::Close).await;
                 tracing::debug!("Protocol message loop terminated");
@@ -288,17 +843,94 @@ impl Protocol {
         })
     }
 
-    // Add Clone implementation for Protocol
-    pub fn clone(&self) -> Self {
-        Protocol {
-            cmd_tx: self.cmd_tx.clone(),
-            event_rx: self.event_rx.clone(),
-            options: self.options.clone(),
-            request_message_id: Arc::clone(&self.request_message_id),
-            request_handlers: Arc::clone(&self.request_handlers),
-            notification_handlers: Arc::clone(&self.notification_handlers),
-            response_handlers: Arc::clone(&self.response_handlers),
-            progress_handlers: Arc::clone(&self.progress_handlers),
+    /// Ping the peer every interval until the connection closes. Once too many pings
+    /// in a row fail, close the connection as if the transport had reported it closed.
+    async fn heartbeat(protocol: Protocol, heartbeat: HeartbeatOptions, close_tx: mpsc::Sender<()>) {
+        let mut ticks = tokio::time::interval(heartbeat.interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick completes at once; the peer was just connected
+        ticks.tick().await;
+
+        let mut failures = 0;
+        loop {
+            ticks.tick().await;
+            if close_tx.is_closed() {
+                return;
+            }
+
+            match protocol.ping(Some(heartbeat.timeout)).await {
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    tracing::warn!(
+                        "Heartbeat ping failed ({} of {}): {:?}",
+                        failures,
+                        heartbeat.max_failures,
+                        e
+                    );
+                }
+            }
+
+            if failures >= heartbeat.max_failures {
+                tracing::error!("Peer missed {} pings, closing connection", failures);
+                if let Some(handler) = &protocol.heartbeat_failed_handler {
+                    handler().await;
+                }
+                let _ = close_tx.send(()).await;
+                return;
+            }
+        }
+    }
+
+    /// Check that the peer is still responsive with a `ping` request
+    pub async fn ping(&self, timeout: Option<Duration>) -> Result<(), McpError> {
+        let options = RequestOptions {
+            timeout: timeout.or(Some(Duration::from_millis(DEFAULT_REQUEST_TIMEOUT_MS))),
+            ..Default::default()
+        };
+        self.request::<_, serde_json::Value>("ping", Option::<()>::None, Some(options))
+            .await
+            .map(|_| ())
+    }
+
+    /// The `_meta.progressToken` of an incoming request
+    fn progress_token(request: &JsonRpcRequest) -> Option<ProgressToken> {
+        let token = request.params.as_ref()?.get("_meta")?.get("progressToken")?;
+        serde_json::from_value(token.clone()).ok()
+    }
+
+    /// Turn a request handler's result into its JSON-RPC response
+    fn response(id: RequestId, result: Result<serde_json::Value, McpError>) -> JsonRpcMessage {
+        match result {
+            Ok(result) => JsonRpcMessage::Response(JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: Some(id),
+                result: Some(result),
+                error: None,
+            }),
+            Err(e) => Self::error(Some(id), e),
+        }
+    }
+
+    /// An error response, with a null id when the request's id is unknown
+    pub(crate) fn error(id: Option<RequestId>, e: McpError) -> JsonRpcMessage {
+        JsonRpcMessage::Response(JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code: e.code(),
+                message: e.to_string(),
+                data: e.data(),
+            }),
+        })
+    }
+
+    /// Address a message to one session, if any, rather than every peer
+    fn address(session_id: Option<SessionId>, msg: JsonRpcMessage) -> TransportCommand {
+        match session_id {
+            Some(session_id) => TransportCommand::SendTo(session_id, msg),
+            None => TransportCommand::SendMessage(msg),
         }
     }
 
@@ -308,6 +940,22 @@ impl Protocol {
         params: Option<Req>,
         options: Option<RequestOptions>,
     ) -> Result<Resp, McpError>
+    where
+        Req: Serialize,
+        Resp: for<'de> Deserialize<'de>,
+    {
+        self.request_to(None, method, params, options).await
+    }
+
+    /// Send a request to a single session of a multi-session transport, or to
+    /// the only peer when `session_id` is `None`
+    pub async fn request_to<Req, Resp>(
+        &self,
+        session_id: Option<SessionId>,
+        method: &str,
+        params: Option<Req>,
+        options: Option<RequestOptions>,
+    ) -> Result<Resp, McpError>
     where
         Req: Serialize,
         Resp: for<'de> Deserialize<'de>,
@@ -317,61 +965,68 @@ impl Protocol {
         let has_progress = options.on_progress.is_some();
 
         if self.options.enforce_strict_capabilities {
-            self.assert_capability_for_method(method)?;
+            self.assert_capability_for_method(&session_id, method).await?;
         }
 
-        let message_id = {
-            let mut id = self.request_message_id.write().await;
-            *id += 1;
-            *id
-        };
+        let message_id = self.next_message_id().await;
 
         // Only serialize params if Some
-        let params_value = if let Some(params) = params {
-            let mut value = serde_json::to_value(params).map_err(|_| McpError::InvalidParams)?;
-            
-            // Add progress token if needed
-            if let Some(progress_callback) = options.on_progress {
-                self.progress_handlers
-                    .write()
-                    .await
-                    .insert(message_id, progress_callback);
-
-                if let serde_json::Value::Object(ref mut map) = value {
-                    map.insert(
-                        "_meta".to_string(),
-                        serde_json::json!({ "progressToken": message_id }),
-                    );
-                }
+        let mut params_value = match params {
+            Some(params) => {
+                Some(serde_json::to_value(params).map_err(|_| McpError::InvalidParams)?)
             }
-            Some(value)
-        } else {
-            None
+            None => None,
         };
 
+        // Add progress token if needed
+        let progress_seen = Arc::new(tokio::sync::Notify::new());
+        if let Some(progress_callback) = options.on_progress {
+            let progress_seen = Arc::clone(&progress_seen);
+            self.progress_handlers.write().await.insert(
+                (session_id.clone(), message_id.clone()),
+                Box::new(move |progress| {
+                    progress_seen.notify_one();
+                    progress_callback(progress);
+                }),
+            );
+
+            let value = params_value.get_or_insert_with(|| serde_json::json!({}));
+            if let serde_json::Value::Object(map) = value {
+                map.insert(
+                    "_meta".to_string(),
+                    serde_json::json!({ "progressToken": message_id }),
+                );
+            }
+        }
+
         let request = JsonRpcMessage::Request(JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
-            id: message_id,
+            id: message_id.clone(),
             method: method.to_string(),
             params: params_value, // Now properly optional
         });
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
 
+        let key = (session_id.clone(), message_id.clone());
         self.response_handlers.write().await.insert(
-            message_id,
+            key.clone(),
             Box::new(move |result| {
                 let _ = tx.send(result);
             }),
         );
 
-        if let Some(cmd_tx) = &self.cmd_tx {
-            cmd_tx
-                .send(TransportCommand::SendMessage(request))
+        let sent = match &self.cmd_tx {
+            Some(cmd_tx) => cmd_tx
+                .send(Self::address(session_id.clone(), request))
                 .await
-                .map_err(|_| McpError::ConnectionClosed)?;
-        } else {
-            return Err(McpError::NotConnected);
+                .map_err(|_| McpError::ConnectionClosed),
+            None => Err(McpError::NotConnected),
+        };
+        if let Err(e) = sent {
+            self.response_handlers.write().await.remove(&key);
+            self.progress_handlers.write().await.remove(&key);
+            return Err(e);
         }
 
         // Setup timeout
@@ -379,41 +1034,155 @@ impl Protocol {
         let timeout_fut = tokio::time::sleep(timeout);
         tokio::pin!(timeout_fut);
 
-        let result = tokio::select! {
-            response = rx => {
-                match response {
-                    Ok(Ok(response)) => {
-                        match response.result {
-                            Some(result) => serde_json::from_value(result).map_err(|_| McpError::InvalidParams),
-                            None => Err(McpError::InternalError("No result in response".to_string())),
-                        }
-                    }
-                    Ok(Err(e)) => Err(e),
-                    Err(e) => {
-                        tracing::error!("Request failed: {:?}", e);
-                        Err(McpError::InternalError(e.to_string()))
+        // A caller without a signal never cancels
+        let cancelled = async {
+            match options.signal {
+                Some(mut signal) => {
+                    if signal.wait_for(|cancelled| *cancelled).await.is_err() {
+                        std::future::pending::<()>().await;
                     }
                 }
-            }
-            _ = timeout_fut => {
-                Err(McpError::RequestTimeout)
+                None => std::future::pending().await,
             }
         };
 
+        tokio::pin!(cancelled);
+
+        let result = loop {
+            break tokio::select! {
+                response = &mut rx => Self::parse_response(response),
+                _ = &mut cancelled => {
+                    self.cancel_request(session_id, &message_id, "Cancelled by caller").await;
+                    Err(McpError::RequestCancelled)
+                }
+                _ = &mut timeout_fut => {
+                    self.cancel_request(session_id, &message_id, "Request timed out").await;
+                    Err(McpError::RequestTimeout)
+                }
+                _ = progress_seen.notified(), if options.reset_timeout_on_progress => {
+                    timeout_fut.as_mut().reset(tokio::time::Instant::now() + timeout);
+                    continue;
+                }
+            };
+        };
+
         // Cleanup progress handler
         if has_progress {
-            self.progress_handlers.write().await.remove(&message_id);
+            self.progress_handlers.write().await.remove(&key);
         }
 
         result
     }
 
+    /// Send several requests as one JSON-RPC batch. Each returned `PendingResponse`
+    /// resolves on its own as the peer answers.
+    pub async fn request_batch(
+        &self,
+        requests: Vec<(&str, Option<serde_json::Value>)>,
+    ) -> Result<Vec<PendingResponse>, McpError> {
+        let cmd_tx = self.cmd_tx.as_ref().ok_or(McpError::NotConnected)?;
+
+        let mut messages = Vec::with_capacity(requests.len());
+        let mut pending = Vec::with_capacity(requests.len());
+        for (method, params) in requests {
+            if self.options.enforce_strict_capabilities {
+                self.assert_capability_for_method(&None, method).await?;
+            }
+
+            let id = self.next_message_id().await;
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            self.response_handlers.write().await.insert(
+                (None, id.clone()),
+                Box::new(move |result| {
+                    let _ = tx.send(result);
+                }),
+            );
+
+            messages.push(JsonRpcMessage::Request(JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                id: id.clone(),
+                method: method.to_string(),
+                params,
+            }));
+            pending.push(PendingResponse {
+                id,
+                rx,
+                protocol: self.clone(),
+            });
+        }
+
+        if let Err(e) = cmd_tx
+            .send(TransportCommand::SendMessage(JsonRpcMessage::Batch(messages)))
+            .await
+        {
+            let mut handlers = self.response_handlers.write().await;
+            for request in &pending {
+                handlers.remove(&(None, request.id.clone()));
+            }
+            tracing::error!("Failed to send batch: {:?}", e);
+            return Err(McpError::ConnectionClosed);
+        }
+        Ok(pending)
+    }
+
+    async fn next_message_id(&self) -> RequestId {
+        let mut id = self.request_message_id.write().await;
+        *id += 1;
+        RequestId::Number(*id)
+    }
+
+    fn parse_response<Resp>(
+        response: Result<Result<JsonRpcResponse, McpError>, tokio::sync::oneshot::error::RecvError>,
+    ) -> Result<Resp, McpError>
+    where
+        Resp: for<'de> Deserialize<'de>,
+    {
+        match response {
+            Ok(Ok(response)) => match response.error {
+                Some(error) => Err(error.into()),
+                // A `null` result deserializes as `None`
+                None => serde_json::from_value(response.result.unwrap_or_default())
+                    .map_err(|e| McpError::InternalError(format!("Invalid result: {}", e))),
+            },
+            Ok(Err(e)) => Err(e),
+            Err(e) => {
+                tracing::error!("Request failed: {:?}", e);
+                Err(McpError::InternalError(e.to_string()))
+            }
+        }
+    }
+
+    /// Stop waiting for an outgoing request and tell the peer it was sent to,
+    /// `session_id` on multi-session transports, to abandon it
+    async fn cancel_request(&self, session_id: Option<SessionId>, id: &RequestId, reason: &str) {
+        self.response_handlers
+            .write()
+            .await
+            .remove(&(session_id.clone(), id.clone()));
+
+        let cancelled = CancelledNotification {
+            request_id: id.clone(),
+            reason: Some(reason.to_string()),
+        };
+        let notification = JsonRpcMessage::Notification(JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/cancelled".to_string(),
+            params: Some(serde_json::to_value(cancelled).unwrap()),
+        });
+        let Some(cmd_tx) = &self.cmd_tx else {
+            return;
+        };
+        if let Err(e) = cmd_tx.send(Self::address(session_id, notification)).await {
+            tracing::debug!("Failed to send cancellation for request {}: {:?}", id, e);
+        }
+    }
+
     pub async fn notification<N: Serialize>(
         &self,
         method: &str,
         params: Option<N>,
     ) -> Result<(), McpError> {
-        self.assert_notification_capability(method)?;
+        self.assert_notification_capability(method).await?;
 
         let notification = JsonRpcMessage::Notification(JsonRpcNotification {
             jsonrpc: "2.0".to_string(),
@@ -441,14 +1210,20 @@ impl Protocol {
         Ok(())
     }
 
-    pub async fn set_request_handler(&mut self, method: &str, handler: RequestHandler) {
-        self.assert_request_handler_capability(method)
-            .expect("Invalid request handler capability");
+    /// Handle `method` requests with `handler`. Fails with `CapabilityNotSupported`
+    /// if this side declared capabilities that do not cover `method`.
+    pub async fn set_request_handler(
+        &mut self,
+        method: &str,
+        handler: RequestHandler,
+    ) -> Result<(), McpError> {
+        self.assert_request_handler_capability(method).await?;
 
         self.request_handlers
             .write()
             .await
             .insert(method.to_string(), handler);
+        Ok(())
     }
 
     pub async fn set_notification_handler(&mut self, method: &str, handler: NotificationHandler) {
@@ -458,23 +1233,52 @@ impl Protocol {
             .insert(method.to_string(), handler);
     }
 
-    // Protected methods that should be implemented by subclasses
-    fn assert_capability_for_method(&self, method: &str) -> Result<(), McpError> {
-        // Subclasses should implement this
-        Ok(())
+    /// Declare the capabilities this side supports. Clients learn theirs at `initialize`.
+    pub async fn set_local_capabilities(&self, capabilities: PeerCapabilities) {
+        *self.local_capabilities.write().await = Some(capabilities);
     }
 
-    fn assert_notification_capability(&self, method: &str) -> Result<(), McpError> {
-        // Subclasses should implement this
-        Ok(())
+    /// Record the capabilities the peer on `session_id` declared during `initialize`
+    pub async fn set_remote_capabilities(
+        &self,
+        session_id: Option<SessionId>,
+        capabilities: PeerCapabilities,
+    ) {
+        self.remote_capabilities
+            .write()
+            .await
+            .insert(session_id, capabilities);
     }
 
-    fn assert_request_handler_capability(&self, method: &str) -> Result<(), McpError> {
-        // Subclasses should implement this
-        Ok(())
+    // Peers that have not declared capabilities yet are not checked, so the
+    // initialize handshake itself always goes through
+    async fn assert_capability_for_method(
+        &self,
+        session_id: &Option<SessionId>,
+        method: &str,
+    ) -> Result<(), McpError> {
+        match self.remote_capabilities.read().await.get(session_id) {
+            Some(capabilities) => capabilities.check_request(method),
+            None => Ok(()),
+        }
+    }
+
+    async fn assert_notification_capability(&self, method: &str) -> Result<(), McpError> {
+        match &*self.local_capabilities.read().await {
+            Some(capabilities) => capabilities.check_notification(method),
+            None => Ok(()),
+        }
+    }
+
+    async fn assert_request_handler_capability(&self, method: &str) -> Result<(), McpError> {
+        match &*self.local_capabilities.read().await {
+            Some(capabilities) => capabilities.check_handler(method),
+            None => Ok(()),
+        }
     }
 
     pub async fn send_notification(&self, notification: JsonRpcNotification) -> Result<(), McpError> {
+        self.assert_notification_capability(&notification.method).await?;
         if let Some(cmd_tx) = &self.cmd_tx {
             cmd_tx.send(TransportCommand::SendMessage(JsonRpcMessage::Notification(notification)))
                 .await
@@ -484,35 +1288,112 @@ impl Protocol {
             Err(McpError::NotConnected)
         }
     }
+
+    /// Send a notification to a single session instead of every connected client
+    pub async fn send_notification_to(
+        &self,
+        session_id: SessionId,
+        notification: JsonRpcNotification,
+    ) -> Result<(), McpError> {
+        self.assert_notification_capability(&notification.method).await?;
+        if let Some(cmd_tx) = &self.cmd_tx {
+            cmd_tx
+                .send(TransportCommand::SendTo(
+                    session_id,
+                    JsonRpcMessage::Notification(notification),
+                ))
+                .await
+                .map_err(|_| McpError::ConnectionClosed)?;
+            Ok(())
+        } else {
+            Err(McpError::NotConnected)
+        }
+    }
+}
+
+/// One request of a batch sent with `Protocol::request_batch`
+pub struct PendingResponse {
+    id: RequestId,
+    rx: tokio::sync::oneshot::Receiver<Result<JsonRpcResponse, McpError>>,
+    protocol: Protocol,
+}
+
+impl PendingResponse {
+    pub fn id(&self) -> &RequestId {
+        &self.id
+    }
+
+    /// Wait for this request's response, up to the default request timeout
+    pub async fn result<Resp>(self) -> Result<Resp, McpError>
+    where
+        Resp: for<'de> Deserialize<'de>,
+    {
+        let timeout = Duration::from_millis(DEFAULT_REQUEST_TIMEOUT_MS);
+        match tokio::time::timeout(timeout, self.rx).await {
+            Ok(response) => Protocol::parse_response(response),
+            Err(_) => {
+                self.protocol.cancel_request(None, &self.id, "Request timed out").await;
+                Err(McpError::RequestTimeout)
+            }
+        }
+    }
 }
 
 // Helper types for JSON-RPC
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CancelledNotification {
-    pub request_id: String,
-    pub reason: String,
+    pub request_id: RequestId,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProgressNotification {
-    pub progress: u64,
-    pub total: Option<u64>,
-    pub progress_token: u64,
+    pub progress_token: ProgressToken,
+    pub progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
-    pub id: u64,
+    pub id: RequestId,
     pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub params: Option<serde_json::Value>,
 }
 
+impl JsonRpcRequest {
+    /// Deserialize the params, treating missing params as an empty object.
+    /// Params that don't match `T` are `McpError::InvalidParams`.
+    pub fn parse_params<T>(&self) -> Result<T, McpError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let params = self
+            .params
+            .clone()
+            .unwrap_or_else(|| serde_json::Value::Object(Default::default()));
+        serde_json::from_value(params).map_err(|e| {
+            tracing::debug!("Invalid params for {}: {}", self.method, e);
+            McpError::InvalidParams
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JsonRpcResponse {
     pub jsonrpc: String,
-    pub id: u64,
+    /// `None` (serialized as `null`) when the request's id could not be read
+    pub id: Option<RequestId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<JsonRpcError>,
 }
 
@@ -520,6 +1401,7 @@ pub struct JsonRpcResponse {
 pub struct JsonRpcNotification {
     pub jsonrpc: String,
     pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub params: Option<serde_json::Value>,
 }
 
@@ -527,6 +1409,722 @@ pub struct JsonRpcNotification {
 pub struct JsonRpcError {
     pub code: i32,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<serde_json::Value>,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::InMemoryTransport;
+    use tokio::sync::Notify;
+
+    /// A server whose `slow` request waits for `release`, whose `fast` request answers
+    /// at once and whose `progress` request reports three steps 50ms apart
+    async fn connect_pair(
+        options: ProtocolOptions,
+        release: Arc<Notify>,
+    ) -> (Protocol, ProtocolHandle, ProtocolHandle) {
+        let (server_transport, client_transport) = InMemoryTransport::pair();
+
+        let mut server = Protocol::builder(Some(options))
+            .with_request_handler(
+                "slow",
+                Box::new(move |_request, _extra| {
+                    let release = Arc::clone(&release);
+                    Box::pin(async move {
+                        release.notified().await;
+                        Ok(serde_json::json!("slow"))
+                    })
+                }),
+            )
+            .with_request_handler(
+                "fast",
+                Box::new(|_request, _extra| Box::pin(async { Ok(serde_json::json!("fast")) })),
+            )
+            .with_request_handler(
+                "progress",
+                Box::new(|_request, extra| {
+                    Box::pin(async move {
+                        for step in 1..=3 {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            extra
+                                .progress
+                                .report(step as f64, Some(3.0), Some(format!("step {}", step)))
+                                .await?;
+                        }
+                        Ok(serde_json::json!("done"))
+                    })
+                }),
+            )
+            .build();
+        let server_handle = server.connect(server_transport).await.unwrap();
+
+        let mut client = Protocol::builder(None).build();
+        let client_handle = client.connect(client_transport).await.unwrap();
+        (client, client_handle, server_handle)
+    }
+
+    async fn call(client: &Protocol, method: &str) -> Result<String, McpError> {
+        client.request(method, Option::<()>::None, None).await
+    }
+
+    #[tokio::test]
+    async fn test_slow_handler_does_not_block_others() {
+        let release = Arc::new(Notify::new());
+        let (client, _client_handle, _server_handle) =
+            connect_pair(ProtocolOptions::default(), Arc::clone(&release)).await;
+
+        let slow = tokio::spawn({
+            let client = client.clone();
+            async move { call(&client, "slow").await }
+        });
+
+        let fast = tokio::time::timeout(Duration::from_secs(1), call(&client, "fast")).await;
+        assert_eq!(fast.unwrap().unwrap(), "fast");
+        assert!(!slow.is_finished());
+
+        release.notify_one();
+        assert_eq!(slow.await.unwrap().unwrap(), "slow");
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_requests() {
+        let release = Arc::new(Notify::new());
+        let options = ProtocolOptions {
+            max_concurrent_requests: 1,
+            ..Default::default()
+        };
+        let (client, _client_handle, _server_handle) =
+            connect_pair(options, Arc::clone(&release)).await;
+
+        let slow = tokio::spawn({
+            let client = client.clone();
+            async move { call(&client, "slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The only slot is taken until the slow request finishes
+        let fast = tokio::spawn({
+            let client = client.clone();
+            async move { call(&client, "fast").await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!fast.is_finished());
+
+        release.notify_one();
+        assert_eq!(slow.await.unwrap().unwrap(), "slow");
+        assert_eq!(fast.await.unwrap().unwrap(), "fast");
+    }
+
+    /// Wait until the server has dropped every in-flight request
+    async fn assert_no_requests_in_flight(server: &ProtocolHandle) {
+        for _ in 0..50 {
+            if server.get_ref().request_abort_controllers.read().await.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Server still has requests in flight");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request() {
+        let release = Arc::new(Notify::new());
+        let (client, _client_handle, server_handle) =
+            connect_pair(ProtocolOptions::default(), Arc::clone(&release)).await;
+
+        let (cancel_tx, cancel_rx) = tokio::sync::watch::channel(false);
+        let slow = tokio::spawn({
+            let client = client.clone();
+            async move {
+                let options = RequestOptions {
+                    signal: Some(cancel_rx),
+                    ..Default::default()
+                };
+                client
+                    .request::<_, String>("slow", Option::<()>::None, Some(options))
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server_handle.get_ref().request_abort_controllers.read().await.len(), 1);
+
+        cancel_tx.send(true).unwrap();
+        assert!(matches!(slow.await.unwrap(), Err(McpError::RequestCancelled)));
+
+        // The server aborted the handler instead of replying
+        assert_no_requests_in_flight(&server_handle).await;
+        assert!(client.response_handlers.read().await.is_empty());
+        assert_eq!(call(&client, "fast").await.unwrap(), "fast");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_cancels_request() {
+        let release = Arc::new(Notify::new());
+        let (client, _client_handle, server_handle) =
+            connect_pair(ProtocolOptions::default(), Arc::clone(&release)).await;
+
+        let options = RequestOptions {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let result = client
+            .request::<_, String>("slow", Option::<()>::None, Some(options))
+            .await;
+        assert!(matches!(result, Err(McpError::RequestTimeout)));
+        assert_no_requests_in_flight(&server_handle).await;
+    }
+
+    #[tokio::test]
+    async fn test_timeout_cancels_on_originating_session() {
+        let mut protocol = Protocol::builder(None).build();
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(8);
+        protocol.cmd_tx = Some(cmd_tx);
+
+        let options = RequestOptions {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let result = protocol
+            .request_to::<_, String>(Some("a".to_string()), "slow", Option::<()>::None, Some(options))
+            .await;
+        assert!(matches!(result, Err(McpError::RequestTimeout)));
+
+        // The cancellation follows the request to its session only
+        match cmd_rx.recv().await {
+            Some(TransportCommand::SendTo(session_id, JsonRpcMessage::Request(_))) => {
+                assert_eq!(session_id, "a")
+            }
+            other => panic!("Expected request to session a, got {:?}", other),
+        }
+        match cmd_rx.recv().await {
+            Some(TransportCommand::SendTo(session_id, JsonRpcMessage::Notification(n))) => {
+                assert_eq!(session_id, "a");
+                assert_eq!(n.method, "notifications/cancelled");
+            }
+            other => panic!("Expected cancellation to session a, got {:?}", other),
+        }
+    }
+
+    /// Hands out channels the test drives, as a multi-session transport would
+    struct ScriptedTransport(Option<TransportChannels>);
+
+    #[async_trait::async_trait]
+    impl Transport for ScriptedTransport {
+        async fn start(&mut self) -> Result<TransportChannels, McpError> {
+            self.0.take().ok_or(McpError::NotConnected)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_addressed_session_answers() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(8);
+        let (event_tx, event_rx) = mpsc::channel(8);
+        let mut protocol = Protocol::builder(None).build();
+        let _handle = protocol
+            .connect(ScriptedTransport(Some(TransportChannels {
+                cmd_tx,
+                event_rx: Arc::new(tokio::sync::Mutex::new(event_rx)),
+            })))
+            .await
+            .unwrap();
+
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = RequestOptions {
+            on_progress: Some(Box::new({
+                let updates = Arc::clone(&updates);
+                move |progress| updates.lock().unwrap().push(progress.progress)
+            })),
+            ..Default::default()
+        };
+        let request = tokio::spawn({
+            let protocol = protocol.clone();
+            async move {
+                protocol
+                    .request_to::<_, String>(Some("a".to_string()), "roots/list", Option::<()>::None, Some(options))
+                    .await
+            }
+        });
+        let id = match cmd_rx.recv().await {
+            Some(TransportCommand::SendTo(_, JsonRpcMessage::Request(request))) => request.id,
+            other => panic!("Expected request, got {:?}", other),
+        };
+
+        let message = |value: serde_json::Value| -> JsonRpcMessage { serde_json::from_value(value).unwrap() };
+        let progress = |step: u64| {
+            message(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": { "progressToken": id, "progress": step }
+            }))
+        };
+        let response = |result: &str| {
+            message(serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+        };
+
+        // Session b can neither report progress for nor answer session a's request
+        for (session_id, msg) in [
+            ("b", progress(1)),
+            ("b", response("forged")),
+            ("a", progress(2)),
+            ("a", response("genuine")),
+        ] {
+            event_tx
+                .send(TransportEvent::SessionMessage(session_id.to_string(), msg))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(request.await.unwrap().unwrap(), "genuine");
+        assert_eq!(*updates.lock().unwrap(), vec![2.0]);
+        assert!(protocol.response_handlers.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsent_request_leaves_no_handlers() {
+        let with_progress = || RequestOptions {
+            on_progress: Some(Box::new(|_| {})),
+            ..Default::default()
+        };
+
+        let mut protocol = Protocol::builder(None).build();
+        let result = protocol
+            .request::<_, String>("fast", Option::<()>::None, Some(with_progress()))
+            .await;
+        assert!(matches!(result, Err(McpError::NotConnected)));
+
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        drop(cmd_rx);
+        protocol.cmd_tx = Some(cmd_tx);
+        let result = protocol
+            .request::<_, String>("fast", Option::<()>::None, Some(with_progress()))
+            .await;
+        assert!(matches!(result, Err(McpError::ConnectionClosed)));
+
+        assert!(protocol.response_handlers.read().await.is_empty());
+        assert!(protocol.progress_handlers.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_progress_reaches_caller() {
+        let (client, _client_handle, _server_handle) =
+            connect_pair(ProtocolOptions::default(), Arc::new(Notify::new())).await;
+
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = RequestOptions {
+            on_progress: Some(Box::new({
+                let updates = Arc::clone(&updates);
+                move |progress| updates.lock().unwrap().push(progress)
+            })),
+            ..Default::default()
+        };
+        let result: String = client
+            .request("progress", Option::<()>::None, Some(options))
+            .await
+            .unwrap();
+        assert_eq!(result, "done");
+
+        let updates = std::mem::take(&mut *updates.lock().unwrap());
+        let steps: Vec<f64> = updates.iter().map(|p| p.progress).collect();
+        assert_eq!(steps, vec![1.0, 2.0, 3.0]);
+        assert_eq!(updates[2].total, Some(3.0));
+        assert_eq!(updates[2].message.as_deref(), Some("step 3"));
+        assert!(client.progress_handlers.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_progress_resets_timeout() {
+        let (client, _client_handle, _server_handle) =
+            connect_pair(ProtocolOptions::default(), Arc::new(Notify::new())).await;
+
+        let options = |reset_timeout_on_progress| RequestOptions {
+            on_progress: Some(Box::new(|_| {})),
+            timeout: Some(Duration::from_millis(100)),
+            reset_timeout_on_progress,
+            ..Default::default()
+        };
+
+        // 150ms of work with updates every 50ms outlasts the 100ms timeout only
+        // when progress keeps it alive
+        let result = client
+            .request::<_, String>("progress", Option::<()>::None, Some(options(false)))
+            .await;
+        assert!(matches!(result, Err(McpError::RequestTimeout)));
+
+        let result = client
+            .request::<_, String>("progress", Option::<()>::None, Some(options(true)))
+            .await;
+        assert_eq!(result.unwrap(), "done");
+    }
+
+    #[test]
+    fn test_progress_token_forms() {
+        let number: ProgressToken = serde_json::from_value(serde_json::json!(7)).unwrap();
+        assert_eq!(number, ProgressToken::Number(7));
+        let string: ProgressToken = serde_json::from_value(serde_json::json!("abc")).unwrap();
+        assert_eq!(string, ProgressToken::String("abc".to_string()));
+
+        let notification = ProgressNotification {
+            progress_token: number,
+            progress: 0.5,
+            total: None,
+            message: None,
+        };
+        assert_eq!(
+            serde_json::to_value(notification).unwrap(),
+            serde_json::json!({ "progressToken": 7, "progress": 0.5 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_batch() {
+        let (client, _client_handle, _server_handle) =
+            connect_pair(ProtocolOptions::default(), Arc::new(Notify::new())).await;
+
+        let pending = client
+            .request_batch(vec![("progress", None), ("fast", None)])
+            .await
+            .unwrap();
+        let mut pending = pending.into_iter();
+        let progress = pending.next().unwrap();
+        let fast = pending.next().unwrap();
+        assert_ne!(progress.id(), fast.id());
+
+        // Each request resolves on its own, in any order
+        assert_eq!(fast.result::<String>().await.unwrap(), "fast");
+        assert_eq!(progress.result::<String>().await.unwrap(), "done");
+    }
+
+    #[test]
+    fn test_batch_wire_format() {
+        let batch: JsonRpcMessage = serde_json::from_value(serde_json::json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+            { "jsonrpc": "2.0", "method": "notifications/initialized" }
+        ]))
+        .unwrap();
+        match &batch {
+            JsonRpcMessage::Batch(messages) => {
+                assert!(matches!(messages[0], JsonRpcMessage::Request(_)));
+                assert!(matches!(messages[1], JsonRpcMessage::Notification(_)));
+            }
+            other => panic!("Expected batch, got {:?}", other),
+        }
+        assert!(serde_json::to_value(&batch).unwrap().is_array());
+    }
+
+    #[tokio::test]
+    async fn test_string_request_id() {
+        let (server_transport, mut peer) = InMemoryTransport::pair();
+        let mut server = Protocol::builder(None)
+            .with_request_handler(
+                "echo",
+                Box::new(|request, _extra| {
+                    Box::pin(async move { Ok(request.params.unwrap_or_default()) })
+                }),
+            )
+            .build();
+        let _server_handle = server.connect(server_transport).await.unwrap();
+        let peer = peer.start().await.unwrap();
+
+        // Hosts may pick string ids; the reply must echo the same id back
+        let request: JsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0", "id": "req-1", "method": "echo", "params": { "a": 1 }
+        }))
+        .unwrap();
+        peer.cmd_tx
+            .send(TransportCommand::SendMessage(request))
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), async {
+            peer.event_rx.lock().await.recv().await
+        })
+        .await
+        .unwrap();
+        match event {
+            Some(TransportEvent::Message(JsonRpcMessage::Response(resp))) => {
+                assert_eq!(resp.id, Some(RequestId::from("req-1")));
+                assert_eq!(resp.result, Some(serde_json::json!({ "a": 1 })));
+            }
+            other => panic!("Expected response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat() {
+        // Pings are answered by default
+        let (client, _client_handle, _server_handle) =
+            connect_pair(ProtocolOptions::default(), Arc::new(Notify::new())).await;
+        client.ping(None).await.unwrap();
+
+        // A peer that never answers is given up on after max_failures pings
+        let (client_transport, mut peer) = InMemoryTransport::pair();
+        let failed = Arc::new(Notify::new());
+        let mut client = Protocol::builder(Some(ProtocolOptions {
+            heartbeat: Some(HeartbeatOptions {
+                interval: Duration::from_millis(20),
+                timeout: Duration::from_millis(20),
+                max_failures: 2,
+            }),
+            ..Default::default()
+        }))
+        .with_heartbeat_failed_handler({
+            let failed = Arc::clone(&failed);
+            Box::new(move || {
+                let failed = Arc::clone(&failed);
+                Box::pin(async move { failed.notify_one() })
+            })
+        })
+        .build();
+        let _client_handle = client.connect(client_transport).await.unwrap();
+        let peer = peer.start().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), failed.notified())
+            .await
+            .expect("Heartbeat never gave up");
+
+        // The connection is closed, which the peer sees once the pings stop
+        let mut pings = 0;
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match peer.event_rx.lock().await.recv().await {
+                    Some(TransportEvent::Message(JsonRpcMessage::Request(req))) => {
+                        assert_eq!(req.method, "ping");
+                        pings += 1;
+                    }
+                    Some(TransportEvent::Message(_)) => (),
+                    other => return other,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(matches!(closed, Some(TransportEvent::Closed)));
+        assert_eq!(pings, 2);
+    }
+
+    #[test]
+    fn test_request_id_wire_format() {
+        let response: JsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32700, "message": "Parse error", "data": null }
+        }))
+        .unwrap();
+        match response {
+            JsonRpcMessage::Response(resp) => assert_eq!(resp.id, None),
+            other => panic!("Expected response, got {:?}", other),
+        }
+
+        let notification: JsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0", "method": "notifications/initialized"
+        }))
+        .unwrap();
+        assert!(matches!(notification, JsonRpcMessage::Notification(_)));
+
+        assert_eq!(serde_json::to_value(RequestId::from(7)).unwrap(), serde_json::json!(7));
+        assert_eq!(
+            serde_json::to_value(RequestId::from("abc")).unwrap(),
+            serde_json::json!("abc")
+        );
+
+        // Peers may number their requests below zero
+        let negative: RequestId = serde_json::from_value(serde_json::json!(-3)).unwrap();
+        assert_eq!(negative, RequestId::Number(-3));
+        assert_eq!(serde_json::to_value(negative).unwrap(), serde_json::json!(-3));
+    }
+
+    #[test]
+    fn test_malformed_result() {
+        let response = JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: Some(RequestId::from(1)),
+            result: Some(serde_json::json!("not a number")),
+            error: None,
+        };
+        match Protocol::parse_response::<u32>(Ok(Ok(response))) {
+            Err(McpError::InternalError(message)) => assert!(message.starts_with("Invalid result")),
+            other => panic!("Expected InternalError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_responses() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let (server_stream, peer_stream) = tokio::io::duplex(4096);
+        let mut server = Protocol::builder(None)
+            .with_request_handler(
+                "sum",
+                Box::new(|request, _extra| {
+                    Box::pin(async move {
+                        let numbers: Vec<u64> = request.parse_params()?;
+                        Ok(serde_json::json!(numbers.iter().sum::<u64>()))
+                    })
+                }),
+            )
+            .build();
+        let _server_handle = server
+            .connect(crate::transport::StreamTransport::from_stream(server_stream, 32))
+            .await
+            .unwrap();
+
+        let (reader, mut writer) = tokio::io::split(peer_stream);
+        let mut lines = BufReader::new(reader).lines();
+        let cases = [
+            (r#"{"jsonrpc":"2.0","id":1,"method":"missing"}"#, serde_json::json!(1), -32601),
+            (r#"{"jsonrpc":"1.0","id":2,"method":"sum"}"#, serde_json::json!(2), -32600),
+            (r#"{"jsonrpc":"2.0","id":3}"#, serde_json::json!(3), -32600),
+            (r#"{"jsonrpc":"2.0","id":4,"method":"sum","params":"x"}"#, serde_json::json!(4), -32602),
+            (r#"{"jsonrpc":"2.0","id":5,"method":"#, serde_json::Value::Null, -32700),
+            (r#"{"id":6}"#, serde_json::Value::Null, -32600),
+            ("[]", serde_json::Value::Null, -32600),
+        ];
+        for (input, id, code) in cases {
+            writer.write_all(format!("{}\n", input).as_bytes()).await.unwrap();
+            let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(reply["id"], id, "Wrong id for {}", input);
+            assert_eq!(reply["error"]["code"], code, "Wrong code for {}", input);
+        }
+
+        // Still serving after all of that
+        writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":\"sum\",\"params\":[1,2]}\n")
+            .await
+            .unwrap();
+        let line = lines.next_line().await.unwrap().unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["result"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_error_response_maps_to_mcp_error() {
+        let (server_transport, client_transport) = InMemoryTransport::pair();
+        let mut server = Protocol::builder(None)
+            .with_request_handler(
+                "denied",
+                Box::new(|_request, _extra| {
+                    Box::pin(async { Err(McpError::AccessDenied("secret.txt".to_string())) })
+                }),
+            )
+            .with_request_handler(
+                "custom",
+                Box::new(|_request, _extra| {
+                    Box::pin(async {
+                        Err(McpError::Custom {
+                            code: -31000,
+                            message: "Quota exceeded".to_string(),
+                            data: Some(serde_json::json!({ "retryAfter": 30 })),
+                        })
+                    })
+                }),
+            )
+            .build();
+        let _server_handle = server.connect(server_transport).await.unwrap();
+        let mut client = Protocol::builder(None).build();
+        let _client_handle = client.connect(client_transport).await.unwrap();
+
+        match call(&client, "denied").await {
+            Err(McpError::AccessDenied(message)) => assert_eq!(message, "secret.txt"),
+            other => panic!("Expected AccessDenied, got {:?}", other),
+        }
+        match call(&client, "missing").await {
+            Err(McpError::MethodNotFound) => (),
+            other => panic!("Expected MethodNotFound, got {:?}", other),
+        }
+        match call(&client, "custom").await {
+            Err(e @ McpError::Custom { .. }) => {
+                assert_eq!(e.code(), -31000);
+                assert_eq!(e.to_string(), "Error -31000: Quota exceeded");
+                assert_eq!(e.data(), Some(serde_json::json!({ "retryAfter": 30 })));
+            }
+            other => panic!("Expected Custom, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capability_enforcement() {
+        use crate::{resource::ResourceCapabilities, tools::ToolCapabilities};
+
+        let server_capabilities = ServerCapabilities {
+            resources: Some(ResourceCapabilities {
+                subscribe: false,
+                list_changed: true,
+            }),
+            ..Default::default()
+        };
+        let (server_transport, client_transport) = InMemoryTransport::pair();
+        let mut server = Protocol::builder(None)
+            .with_capabilities(PeerCapabilities::Server(server_capabilities.clone()))
+            .with_request_handler(
+                "resources/list",
+                Box::new(|_request, _extra| Box::pin(async { Ok(serde_json::json!("listed")) })),
+            )
+            // Not declared, so dropped by `build`
+            .with_request_handler(
+                "tools/list",
+                Box::new(|_request, _extra| Box::pin(async { Ok(serde_json::json!([])) })),
+            )
+            .build();
+        let _server_handle = server.connect(server_transport).await.unwrap();
+        let mut client = Protocol::builder(Some(ProtocolOptions {
+            enforce_strict_capabilities: true,
+            ..Default::default()
+        }))
+        .build();
+        let _client_handle = client.connect(client_transport).await.unwrap();
+
+        // Before initialize nothing is known about the server, so nothing is refused
+        match call(&client, "tools/list").await {
+            Err(McpError::MethodNotFound) => (),
+            other => panic!("Expected MethodNotFound, got {:?}", other),
+        }
+
+        client
+            .set_remote_capabilities(None, PeerCapabilities::Server(server_capabilities))
+            .await;
+        assert_eq!(call(&client, "resources/list").await.unwrap(), "listed");
+        for (method, capability) in [("tools/list", "tools"), ("resources/subscribe", "resources.subscribe")] {
+            match call(&client, method).await {
+                Err(McpError::CapabilityNotSupported(missing)) => assert_eq!(missing, capability),
+                other => panic!("Expected CapabilityNotSupported for {}, got {:?}", method, other),
+            }
+        }
+
+        // The server only handles and announces what it declared
+        let handler: RequestHandler =
+            Box::new(|_request, _extra| Box::pin(async { Ok(serde_json::json!([])) }));
+        match server.set_request_handler("tools/list", handler).await {
+            Err(McpError::CapabilityNotSupported(missing)) => assert_eq!(missing, "tools"),
+            other => panic!("Expected CapabilityNotSupported, got {:?}", other),
+        }
+        server
+            .notification("notifications/resources/list_changed", Option::<()>::None)
+            .await
+            .unwrap();
+        match server
+            .notification("notifications/tools/list_changed", Option::<()>::None)
+            .await
+        {
+            Err(McpError::CapabilityNotSupported(missing)) => assert_eq!(missing, "tools"),
+            other => panic!("Expected CapabilityNotSupported, got {:?}", other),
+        }
+
+        server
+            .set_local_capabilities(PeerCapabilities::Server(ServerCapabilities {
+                tools: Some(ToolCapabilities { list_changed: true }),
+                ..Default::default()
+            }))
+            .await;
+        let handler: RequestHandler =
+            Box::new(|_request, _extra| Box::pin(async { Ok(serde_json::json!([])) }));
+        server.set_request_handler("tools/list", handler).await.unwrap();
+    }
+}
@@ -2,15 +2,15 @@ use async_trait::async_trait;
 use mime::Mime;
 use mime_guess::MimeGuess;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc, path::PathBuf};
+use std::{collections::HashMap, sync::Arc, path::{Component, Path, PathBuf}};
 use tokio::sync::RwLock;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-use tokio::sync::mpsc;
 
-use crate::{error::McpError, protocol::JsonRpcNotification, NotificationSender};
+use crate::{error::McpError, protocol::JsonRpcNotification, server::ClientPeer, NotificationSender};
 
 // Resource Types
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Resource {
     pub uri: String,
     pub name: String,
@@ -21,6 +21,7 @@ pub struct Resource {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ResourceTemplate {
     pub uri_template: String,
     pub name: String,
@@ -31,6 +32,7 @@ pub struct ResourceTemplate {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ResourceContent {
     pub uri: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -48,6 +50,7 @@ pub struct ListResourcesRequest {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListResourcesResponse {
     pub resources: Vec<Resource>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -59,12 +62,19 @@ pub struct ReadResourceRequest {
     pub uri: String,
 }
 
+/// Params of `resources/subscribe` and `resources/unsubscribe`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SubscribeRequest {
+    pub uri: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReadResourceResponse {
     pub contents: Vec<ResourceContent>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListTemplatesResponse {
     pub resource_templates: Vec<ResourceTemplate>,
 }
@@ -84,6 +94,12 @@ pub trait ResourceProvider: Send + Sync {
     
     /// Read resource contents
     async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContent>, McpError>;
+
+    /// Read resource contents for the client that requested them. Defaults to
+    /// `read_resource`.
+    async fn read_resource_with_client(&self, uri: &str, _client: &ClientPeer) -> Result<Vec<ResourceContent>, McpError> {
+        self.read_resource(uri).await
+    }
     
     /// List available templates
     async fn list_templates(&self) -> Result<Vec<ResourceTemplate>, McpError>;
@@ -103,7 +119,8 @@ pub struct ResourceManager {
     notification_sender: Option<NotificationSender>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct ResourceCapabilities {
     pub subscribe: bool,
     pub list_changed: bool,
@@ -164,6 +181,23 @@ impl ResourceManager {
         Ok(ReadResourceResponse { contents })
     }
 
+    /// Read a resource on behalf of the client that requested it
+    pub async fn read_resource_with_client(&self, uri: &str, client: &ClientPeer) -> Result<ReadResourceResponse, McpError> {
+        let provider = {
+            let providers = self.providers.read().await;
+            let scheme = uri.split("://").next()
+                .ok_or_else(|| McpError::InvalidRequest("Invalid URI format".to_string()))?;
+            providers.get(scheme)
+                .cloned()
+                .ok_or_else(|| McpError::ResourceNotFound(uri.to_string()))?
+        };
+
+        provider.validate_uri(uri).await?;
+
+        let contents = provider.read_resource_with_client(uri, client).await?;
+        Ok(ReadResourceResponse { contents })
+    }
+
     pub async fn list_templates(&self) -> Result<ListTemplatesResponse, McpError> {
         let providers = self.providers.read().await;
         let mut all_templates = Vec::new();
@@ -248,26 +282,43 @@ impl ResourceManager {
 // File System Resource Provider Implementation
 pub struct FileSystemProvider {
     root_path: PathBuf,
-
+    use_client_roots: bool,
 }
 
 impl FileSystemProvider {
     pub fn new<P: Into<PathBuf>>(root_path: P) -> Self {
         Self {
             root_path: root_path.into(),
-          
+            use_client_roots: false,
         }
     }
 
+    /// Only serve files that are also inside one of the requesting client's
+    /// `file://` roots, when it shared any
+    pub fn with_client_roots(mut self) -> Self {
+        self.use_client_roots = true;
+        self
+    }
+
     fn sanitize_path(&self, uri: &str) -> Result<PathBuf, McpError> {
         let path = uri.strip_prefix("file://")
             .ok_or_else(|| McpError::InvalidRequest("Invalid file URI".to_string()))?;
             
-        let full_path = self.root_path.join(path);
-        if !full_path.starts_with(&self.root_path) {
-            return Err(McpError::AccessDenied("Path traversal attempt detected".to_string()));
+        // Listed resources carry absolute paths under the root; anything else is
+        // relative to the root. Either way the path may never climb out of it.
+        let path = Path::new(path);
+        let relative = path.strip_prefix(&self.root_path).unwrap_or(path);
+        let mut full_path = self.root_path.clone();
+        for component in relative.components() {
+            match component {
+                Component::Normal(part) => full_path.push(part),
+                Component::RootDir | Component::CurDir => {}
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(McpError::AccessDenied("Path traversal attempt detected".to_string()));
+                }
+            }
         }
-        
+
         Ok(full_path)
     }
 
@@ -284,7 +335,7 @@ impl FileSystemProvider {
         String::from_utf8(content.to_vec()).is_ok()
     }
 
-    fn get_mime_type(&self, path: &PathBuf) -> Option<String> {
+    fn get_mime_type(&self, path: &Path) -> Option<String> {
         if path.is_dir() {
             // For directories, use a standard mime type
             Some("inode/directory".to_string())
@@ -320,7 +371,7 @@ impl FileSystemProvider {
         }
     }
 
-    fn validate_access(&self, path: &PathBuf) -> Result<(), McpError> {
+    fn validate_access(&self, path: &Path) -> Result<(), McpError> {
         // Check if path exists
         if !path.exists() {
             return Err(McpError::ResourceNotFound(path.to_string_lossy().to_string()));
@@ -406,35 +457,18 @@ impl ResourceProvider for FileSystemProvider {
     }
 
     async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContent>, McpError> {
-        let path = self.sanitize_path(uri)?;
-        
-        if !path.exists() {
-            return Err(McpError::ResourceNotFound(uri.to_string()));
-        }
-
-        let content = tokio::fs::read(&path).await.map_err(|_e| McpError::IoError)?;
-        let mime_type = self.get_mime_type(&path)
-            .unwrap_or_else(|| "application/octet-stream".to_string());
+        FileSystemProvider::read_resource(self, uri).await
+    }
 
-        let resource_content = if self.is_text_content(&mime_type, &content) {
-            let text = String::from_utf8(content)
-                .map_err(|_| McpError::InvalidResource("Invalid UTF-8".to_string()))?;
-            ResourceContent {
-                uri: uri.to_string(),
-                mime_type: Some(mime_type),
-                text: Some(text),
-                blob: None,
+    async fn read_resource_with_client(&self, uri: &str, client: &ClientPeer) -> Result<Vec<ResourceContent>, McpError> {
+        let roots = client.root_directories();
+        if self.use_client_roots && !roots.is_empty() {
+            let path = self.sanitize_path(uri)?;
+            if !roots.iter().any(|root| path.starts_with(root)) {
+                return Err(McpError::AccessDenied(format!("{} is outside the client's roots", uri)));
             }
-        } else {
-            ResourceContent {
-                uri: uri.to_string(),
-                mime_type: Some(mime_type),
-                text: None,
-                blob: Some(BASE64.encode(&content)),
-            }
-        };
-
-        Ok(vec![resource_content])
+        }
+        FileSystemProvider::read_resource(self, uri).await
     }
 
     async fn list_templates(&self) -> Result<Vec<ResourceTemplate>, McpError> {
@@ -526,4 +560,37 @@ mod tests {
 
         Ok(())
     }
+    #[test]
+    fn test_path_traversal() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let provider = FileSystemProvider::new(root);
+
+        // Absolute paths under the root are kept, anything else is relative to it
+        let listed = format!("file://{}", root.join("notes.txt").to_string_lossy());
+        assert_eq!(provider.sanitize_path(&listed).unwrap(), root.join("notes.txt"));
+        assert_eq!(
+            provider.sanitize_path("file:///docs/./notes.txt").unwrap(),
+            root.join("docs/notes.txt")
+        );
+        assert_eq!(
+            provider.sanitize_path("file:///etc/passwd").unwrap(),
+            root.join("etc/passwd")
+        );
+
+        for uri in [
+            "file:///../secret.txt".to_string(),
+            "file://docs/../../secret.txt".to_string(),
+            format!("file://{}", root.join("../secret.txt").to_string_lossy()),
+        ] {
+            match provider.sanitize_path(&uri) {
+                Err(McpError::AccessDenied(_)) => (),
+                other => panic!("Expected AccessDenied for {}, got {:?}", uri, other),
+            }
+        }
+        assert!(matches!(
+            provider.sanitize_path("http://example.com/notes.txt"),
+            Err(McpError::InvalidRequest(_))
+        ));
+    }
 }
\ No newline at end of file
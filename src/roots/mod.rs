@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// A directory or file the client lets servers operate on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Root {
+    /// A `file://` URI
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Root {
+    /// A root for a local directory. Returns `None` for relative paths.
+    pub fn from_path(path: &Path, name: Option<String>) -> Option<Self> {
+        let uri = Url::from_directory_path(path).ok()?;
+        Some(Self {
+            uri: uri.to_string(),
+            name,
+        })
+    }
+
+    /// Local path of the root, or `None` if it is not a `file://` URI
+    pub fn path(&self) -> Option<PathBuf> {
+        Url::parse(&self.uri).ok()?.to_file_path().ok()
+    }
+}
+
+// Request/Response types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListRootsResult {
+    pub roots: Vec<Root>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_paths() {
+        let root = Root::from_path(Path::new("/home/user/project"), Some("Project".to_string()))
+            .unwrap();
+        assert_eq!(root.uri, "file:///home/user/project/");
+        assert_eq!(root.path(), Some(PathBuf::from("/home/user/project/")));
+        assert!(Root::from_path(Path::new("relative"), None).is_none());
+
+        let remote = Root {
+            uri: "https://example.com/repo".to_string(),
+            name: None,
+        };
+        assert_eq!(remote.path(), None);
+        assert_eq!(
+            serde_json::to_value(&remote).unwrap(),
+            serde_json::json!({ "uri": "https://example.com/repo" })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error::McpError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SamplingContent {
+    Text {
+        text: String,
+    },
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: SamplingContent,
+}
+
+/// A model the server would like the client to use. Clients may map the name
+/// to an equivalent model from another provider.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelHint {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// How the server weighs cost, speed and capability, each from 0 to 1
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPreferences {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hints: Option<Vec<ModelHint>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_priority: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_priority: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intelligence_priority: Option<f64>,
+}
+
+/// Which MCP servers' context the client should add to the prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IncludeContext {
+    None,
+    ThisServer,
+    AllServers,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopReason {
+    EndTurn,
+    StopSequence,
+    MaxTokens,
+    /// A provider-specific reason
+    #[serde(untagged)]
+    Other(String),
+}
+
+// Request/Response types
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageRequest {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_preferences: Option<ModelPreferences>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_context: Option<IncludeContext>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    /// Provider-specific parameters passed through to the model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl CreateMessageRequest {
+    /// Ask for a reply to a single user message
+    pub fn user_text(text: impl Into<String>, max_tokens: u32) -> Self {
+        Self {
+            messages: vec![SamplingMessage {
+                role: Role::User,
+                content: SamplingContent::Text { text: text.into() },
+            }],
+            model_preferences: None,
+            system_prompt: None,
+            include_context: None,
+            temperature: None,
+            max_tokens,
+            stop_sequences: None,
+            metadata: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: SamplingContent,
+    /// The model that generated the message
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
+}
+
+// Sampling Handler trait
+#[async_trait]
+pub trait SamplingHandler: Send + Sync {
+    /// Generate a message for a server's `sampling/createMessage` request.
+    /// Clients typically let the user review the request and the result.
+    async fn create_message(
+        &self,
+        request: CreateMessageRequest,
+    ) -> Result<CreateMessageResult, McpError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_message_wire_format() {
+        let request: CreateMessageRequest = serde_json::from_value(serde_json::json!({
+            "messages": [
+                { "role": "user", "content": { "type": "text", "text": "Hello" } },
+                { "role": "assistant", "content": { "type": "image", "data": "aGk=", "mimeType": "image/png" } }
+            ],
+            "modelPreferences": { "hints": [{ "name": "claude" }], "speedPriority": 0.5 },
+            "includeContext": "thisServer",
+            "maxTokens": 100
+        }))
+        .unwrap();
+        assert_eq!(request.messages[1].role, Role::Assistant);
+        assert_eq!(request.include_context, Some(IncludeContext::ThisServer));
+        assert_eq!(
+            request.model_preferences.unwrap().speed_priority,
+            Some(0.5)
+        );
+
+        let result = CreateMessageResult {
+            role: Role::Assistant,
+            content: SamplingContent::Text {
+                text: "Hi".to_string(),
+            },
+            model: "test-model".to_string(),
+            stop_reason: Some(StopReason::EndTurn),
+        };
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            serde_json::json!({
+                "role": "assistant",
+                "content": { "type": "text", "text": "Hi" },
+                "model": "test-model",
+                "stopReason": "endTurn"
+            })
+        );
+
+        let other: StopReason = serde_json::from_value(serde_json::json!("toolUse")).unwrap();
+        assert_eq!(other, StopReason::Other("toolUse".to_string()));
+    }
+}
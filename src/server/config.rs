@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
-use crate::{prompts::Prompt, tools::{Tool, ToolType}};
+use crate::{prompts::Prompt, tools::ToolType, transport::LOOPBACK_ORIGINS};
 
 // Server Configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -38,9 +38,24 @@ pub struct ResourceSettings {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SecuritySettings {
+    /// Require an `Authorization: Bearer` token on network transports
     pub enable_auth: bool,
+    /// A static token that grants every required scope
     pub token_secret: Option<String>,
+    /// The key signed tokens are checked with. Must differ from `token_secret`.
+    #[serde(default)]
+    pub signing_secret: Option<String>,
+    /// Scopes a signed token must carry
+    #[serde(default)]
+    pub required_scopes: Vec<String>,
+    /// Requests each session may make
     pub rate_limit: RateLimitSettings,
+    /// Requests all sessions together may make
+    #[serde(default)]
+    pub global_rate_limit: Option<RateLimitSettings>,
+    /// Browser origins network transports accept, with `*` wildcards. Defaults
+    /// to loopback origins. With `*` alone every origin is accepted, no page may
+    /// read the responses and the server only listens on loopback.
     pub allowed_origins: Vec<String>,
 }
 
@@ -49,16 +64,21 @@ impl Default for SecuritySettings {
         SecuritySettings {
             enable_auth: false,
             token_secret: None,
+            signing_secret: None,
+            required_scopes: vec![],
             rate_limit: RateLimitSettings {
                 requests_per_minute: 60,
                 burst_size: 10,
             },
-            allowed_origins: vec!["*".to_string()],
+            global_rate_limit: None,
+            allowed_origins: LOOPBACK_ORIGINS.iter().map(|origin| origin.to_string()).collect(),
         }
     }
 }
 
 
+/// A token bucket: `burst_size` requests at once, refilled at
+/// `requests_per_minute`. A rate of 0 turns the limit off.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RateLimitSettings {
     pub requests_per_minute: u32,
@@ -86,11 +106,19 @@ impl Default for LoggingSettings {
 // Add new tool settings struct
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ToolSettings {
+    /// Offer tools at all; when off the tools capability is not declared
     pub enabled: bool,
+    /// Ask the tool manager's `ToolApprover` before every call
     pub require_confirmation: bool,
+    /// Names of the tools clients may see and call, with `*` wildcards
     pub allowed_tools: Vec<String>,
+    /// Calls running longer fail with an error result; 0 means no limit
     pub max_execution_time_ms: u64,
+    /// `tools/call` requests each session may make, on top of the request limit
     pub rate_limit: RateLimitSettings,
+    /// Give every tool its own `rate_limit` bucket instead of sharing one
+    #[serde(default)]
+    pub rate_limit_per_tool: bool,
 }
 
 impl Default for ToolSettings {
@@ -104,6 +132,7 @@ impl Default for ToolSettings {
                 requests_per_minute: 30,
                 burst_size: 5,
             },
+            rate_limit_per_tool: false,
         }
     }
 }
@@ -114,6 +143,7 @@ pub enum TransportType {
     Stdio,
     Sse,
     WebSocket,
+    StreamableHttp,
 }
 
 impl From<&str> for TransportType {
@@ -122,6 +152,7 @@ impl From<&str> for TransportType {
             "stdio" => TransportType::Stdio,
             "sse" => TransportType::Sse,
             "ws" => TransportType::WebSocket,
+            "http" => TransportType::StreamableHttp,
             _ => TransportType::Stdio,
         }
     }
@@ -153,15 +184,7 @@ impl Default for ServerConfig {
                 max_file_size: 10 * 1024 * 1024, // 10MB
                 enable_templates: true,
             },
-            security: SecuritySettings {
-                enable_auth: false,
-                token_secret: None,
-                rate_limit: RateLimitSettings {
-                    requests_per_minute: 60,
-                    burst_size: 10,
-                },
-                allowed_origins: vec!["*".to_string()],
-            },
+            security: SecuritySettings::default(),
             logging: LoggingSettings {
                 level: "info".to_string(),
                 file: None,
@@ -176,6 +199,7 @@ impl Default for ServerConfig {
                     requests_per_minute: 30,
                     burst_size: 5,
                 },
+                rate_limit_per_tool: false,
             },
             tools: vec![],
             prompts: vec![],
@@ -1,84 +1,47 @@
 use config::ServerConfig;
-use serde::{Deserialize, Serialize};
-use std::{
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
-    },
-    time::Duration,
+use session::{session_key, SessionManager};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
 };
-use tokio::sync::RwLock;
-use tracing::info;
 
 use crate::prompts::{GetPromptRequest, ListPromptsRequest, PromptCapabilities, PromptManager};
 use crate::tools::{ToolCapabilities, ToolManager};
 use crate::{
-    client::ServerCapabilities,
+    auth::BearerAuth,
     error::McpError,
     logging::LoggingCapabilities,
-    protocol::{JsonRpcNotification, Protocol, ProtocolBuilder, ProtocolOptions},
-    resource::{ListResourcesRequest, ReadResourceRequest, ResourceCapabilities, ResourceManager},
+    protocol::{
+        JsonRpcNotification, NotificationHandlerExtra, PeerCapabilities, Protocol, ProtocolBuilder,
+        ProtocolOptions, SUPPORTED_PROTOCOL_VERSIONS,
+    },
+    resource::{
+        ListResourcesRequest, ReadResourceRequest, ResourceCapabilities, ResourceManager,
+        SubscribeRequest,
+    },
+    roots::Root,
     tools::{CallToolRequest, ListToolsRequest},
-    transport::{SseTransport, StdioTransport},
+    types::ServerCapabilities,
+    transport::{
+        AllowedOrigins, SseTransport, StdioTransport, StreamableHttpTransport, Transport,
+        WebSocketTransport,
+    },
     NotificationSender,
 };
-use crate::{
-    logging::{LoggingManager, SetLevelRequest},
-    transport::{JsonRpcMessage, TransportCommand},
-};
+use crate::logging::{LogLevel, LoggingManager, SetLevelRequest};
 use tokio::sync::mpsc;
 
 pub mod config;
-
-// Add initialization types
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InitializeParams {
-    pub protocol_version: String,
-    pub capabilities: ClientCapabilities,
-    pub client_info: ClientInfo,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InitializeResult {
-    pub protocol_version: String,
-    pub capabilities: ServerCapabilities,
-    pub server_info: ServerInfo,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ClientCapabilities {
-    pub roots: Option<RootsCapabilities>,
-    pub sampling: Option<SamplingCapabilities>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RootsCapabilities {
-    pub list_changed: bool,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SamplingCapabilities {}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ClientInfo {
-    pub name: String,
-    pub version: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ServerInfo {
-    pub name: String,
-    pub version: String,
-}
-
-// Add server state enum
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum ServerState {
-    Created,
-    Initializing,
-    Running,
-    ShuttingDown,
-}
+mod peer;
+mod rate_limit;
+mod session;
+
+pub use peer::ClientPeer;
+pub use rate_limit::RateLimiter;
+pub use crate::types::{
+    ClientCapabilities, ClientInfo, InitializeParams, InitializeResult, RootsCapabilities,
+    SamplingCapabilities, ServerInfo,
+};
 
 pub struct McpServer {
     pub config: ServerConfig,
@@ -88,9 +51,9 @@ pub struct McpServer {
     pub logging_manager: Arc<tokio::sync::Mutex<LoggingManager>>,
     notification_tx: mpsc::Sender<JsonRpcNotification>,
     notification_rx: Option<mpsc::Receiver<JsonRpcNotification>>, // Make this Option
-    state: Arc<RwLock<ServerState>>,
+    sessions: SessionManager,
+    rate_limiter: RateLimiter,
     supported_versions: Vec<String>,
-    client_capabilities: Arc<RwLock<Option<ClientCapabilities>>>,
 }
 
 impl McpServer {
@@ -112,7 +75,9 @@ impl McpServer {
                 tx: notification_tx.clone(),
             });
 
-        let tool_manager = Arc::new(ToolManager::new(tool_capabilities));
+        let tool_manager = Arc::new(
+            ToolManager::new(tool_capabilities).with_settings(config.tool_settings.clone()),
+        );
 
         for tool in config.tools.iter() {
             tool_manager.register_tool(tool.to_tool_provider()).await;
@@ -139,83 +104,128 @@ impl McpServer {
         let logging_manager = Arc::new(tokio::sync::Mutex::new(logging_manager));
 
         Self {
-            config,
             resource_manager,
             tool_manager,
             prompt_manager,
             logging_manager,
             notification_tx,
             notification_rx: Some(notification_rx), // Wrap in Some
-            state: Arc::new(RwLock::new(ServerState::Created)),
-            supported_versions: vec!["2024-11-05".to_string()],
-            client_capabilities: Arc::new(RwLock::new(None)),
+            sessions: SessionManager::new(config.server.max_connections),
+            rate_limiter: RateLimiter::new(&config.security, &config.tool_settings),
+            supported_versions: SUPPORTED_PROTOCOL_VERSIONS
+                .iter()
+                .map(|version| version.to_string())
+                .collect(),
+            config,
         }
     }
 
+    /// Start the initialize handshake for `session_id`. Single-peer transports use the empty id.
     pub async fn handle_initialize(
         &self,
+        session_id: &str,
         params: InitializeParams,
     ) -> Result<InitializeResult, McpError> {
-        // Verify state
-        let mut state = self.state.write().await;
-        if *state != ServerState::Created {
-            return Err(McpError::InvalidRequest(
-                "Server already initialized".to_string(),
-            ));
-        }
-        *state = ServerState::Initializing;
-
-        // Validate protocol version
-        if !self.supported_versions.contains(&params.protocol_version) {
-            return Err(McpError::InvalidRequest(format!(
-                "Unsupported protocol version: {}. Supported versions: {:?}",
-                params.protocol_version, self.supported_versions
-            )));
-        }
+        let protocol_version = self
+            .sessions
+            .initialize(session_id, &params, &self.supported_versions)
+            .await?;
 
-        // Store client capabilities
-        *self.client_capabilities.write().await = Some(params.capabilities);
-
-        // Return server capabilities
-        let result = InitializeResult {
-            protocol_version: "2024-11-05".to_string(),
-            capabilities: ServerCapabilities {
-                logging: Some(LoggingCapabilities {}),
-                prompts: Some(PromptCapabilities { list_changed: true }),
-                resources: Some(ResourceCapabilities {
-                    subscribe: true,
-                    list_changed: true,
-                }),
-                tools: Some(ToolCapabilities { list_changed: true }),
-            },
-            server_info: ServerInfo {
+        Ok(Self::initialize_result(
+            ServerInfo {
                 name: self.config.server.name.clone(),
                 version: self.config.server.version.clone(),
             },
-        };
+            self.capabilities(),
+            protocol_version,
+        ))
+    }
+
+    /// Limit the protocol versions offered to clients, e.g. to pin an older one
+    pub fn set_supported_versions(&mut self, versions: Vec<String>) {
+        self.supported_versions = versions;
+    }
+
+    pub async fn handle_initialized(&self, session_id: &str) -> Result<(), McpError> {
+        self.sessions.initialized(session_id).await
+    }
+
+    pub async fn assert_initialized(&self, session_id: &str) -> Result<(), McpError> {
+        self.sessions.assert_initialized(session_id).await
+    }
+
+    /// Capabilities the client on `session_id` declared during initialization
+    pub async fn client_capabilities(&self, session_id: &str) -> Option<ClientCapabilities> {
+        self.sessions
+            .get(session_id)
+            .await
+            .map(|session| session.client_capabilities)
+    }
 
-        Ok(result)
+    /// Roots the client on `session_id` last reported
+    pub async fn roots(&self, session_id: &str) -> Vec<Root> {
+        self.sessions
+            .get(session_id)
+            .await
+            .map(|session| session.roots)
+            .unwrap_or_default()
     }
 
-    pub async fn handle_initialized(&self) -> Result<(), McpError> {
-        let mut state = self.state.write().await;
-        if *state != ServerState::Initializing {
-            return Err(McpError::InvalidRequest(
-                "Invalid server state for initialized notification".to_string(),
-            ));
+    /// Fetch the client's roots in the background, if it has any. Runs as its own
+    /// task because the response arrives on the loop that handles notifications.
+    fn refresh_roots(sessions: SessionManager, extra: NotificationHandlerExtra) {
+        tokio::spawn(async move {
+            let client = ClientPeer::for_session(&sessions, extra.session_id, extra.peer).await;
+            if client.capabilities().and_then(|c| c.roots.as_ref()).is_none() {
+                return;
+            }
+            let session_id = session_key(client.session_id().cloned());
+            match client.list_roots().await {
+                Ok(roots) => {
+                    if let Err(e) = sessions.set_roots(&session_id, roots).await {
+                        tracing::debug!("Session closed before roots arrived: {:?}", e);
+                    }
+                }
+                Err(e) => tracing::error!("Failed to list roots: {:?}", e),
+            }
+        });
+    }
+
+    /// Protocol version negotiated with the client on `session_id`
+    pub async fn protocol_version(&self, session_id: &str) -> Option<String> {
+        self.sessions
+            .get(session_id)
+            .await
+            .map(|session| session.protocol_version)
+    }
+
+    /// Capabilities the server declares to every client
+    pub fn capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            logging: Some(LoggingCapabilities {}),
+            prompts: Some(PromptCapabilities { list_changed: true }),
+            resources: Some(ResourceCapabilities {
+                subscribe: true,
+                list_changed: true,
+            }),
+            tools: self
+                .config
+                .tool_settings
+                .enabled
+                .then_some(ToolCapabilities { list_changed: true }),
         }
-        *state = ServerState::Running;
-        Ok(())
     }
 
-    pub async fn assert_initialized(&self) -> Result<(), McpError> {
-        let state = self.state.read().await;
-        if *state != ServerState::Running {
-            return Err(McpError::InvalidRequest(
-                "Server not initialized".to_string(),
-            ));
+    fn initialize_result(
+        server_info: ServerInfo,
+        capabilities: ServerCapabilities,
+        protocol_version: String,
+    ) -> InitializeResult {
+        InitializeResult {
+            protocol_version,
+            capabilities,
+            server_info,
         }
-        Ok(())
     }
 
     pub async fn handle_notifications(
@@ -234,88 +244,59 @@ impl McpServer {
 
     pub async fn run_stdio_transport(&mut self) -> Result<(), McpError> {
         let transport = StdioTransport::new(None);
-        let protocol = Protocol::builder(Some(ProtocolOptions {
-            enforce_strict_capabilities: true,
-        }));
-
-        // Build and connect protocol
-        let mut protocol = self.register_protocol_handlers(protocol).build();
-        let protocol_handle = protocol.connect(transport).await?;
-
-        // Create shutdown flag
-        let shutdown_requested = Arc::new(AtomicBool::new(false));
-
-        let mut notification_rx = self
-            .notification_rx
-            .take()
-            .ok_or_else(|| McpError::InternalError("Missing notification receiver".to_string()))?;
-
-        // Spawn notification handler
-        let notification_task = {
-            let shutdown_requested = Arc::clone(&shutdown_requested);
-            tokio::spawn({
-                let protocol_handle = protocol_handle.clone();
-                async move {
-                    while let Some(notification) = notification_rx.recv().await {
-                        if shutdown_requested.load(Ordering::SeqCst) {
-                            break;
-                        }
-                        if let Err(e) = protocol_handle
-                            .get_ref()
-                            .send_notification(notification)
-                            .await
-                        {
-                            tracing::error!("Failed to send notification: {:?}", e);
-                        }
-                    }
-                }
-            })
-        };
-
-        // Create shutdown signal handler
-        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
-        tokio::spawn(async move {
-            tokio::signal::ctrl_c().await.ok();
-            let _ = shutdown_tx.send(());
-        });
-
-        // Run main loop
-        let shutdown_result = tokio::select! {
-            _ = shutdown_rx => {
-                tracing::info!("Received shutdown signal");
-                Ok(())
-            }
-            result = notification_task => {
-                match result {
-                    Ok(_) => Ok(()),
-                    Err(e) => Err(McpError::InternalError(format!("Notification task failed: {:?}", e)))
-                }
-            }
-        };
-
-        // Perform graceful shutdown
-        shutdown_requested.store(true, Ordering::SeqCst);
-        *self.state.write().await = ServerState::ShuttingDown;
-
-        // Close protocol through handle
-        protocol_handle.close().await?;
+        self.run_transport(transport).await
+    }
 
-        // Cleanup
-        self.notification_rx = None;
+    pub async fn run_sse_transport(&mut self) -> Result<(), McpError> {
+        let mut transport = SseTransport::new_server(
+            self.config.server.host.clone(),
+            self.config.server.port,
+            4096,
+        )
+        .with_allowed_origins(self.allowed_origins());
+        if let Some(auth) = BearerAuth::from_settings(&self.config.security)? {
+            transport = transport.with_auth(auth);
+        }
+        self.run_transport(transport).await
+    }
 
-        tracing::info!("Server shutdown completed");
-        shutdown_result
+    pub async fn run_websocket_transport(&mut self) -> Result<(), McpError> {
+        let mut transport = WebSocketTransport::new_server(
+            self.config.server.host.clone(),
+            self.config.server.port,
+            4096,
+        )
+        .with_allowed_origins(self.allowed_origins());
+        if let Some(auth) = BearerAuth::from_settings(&self.config.security)? {
+            transport = transport.with_auth(auth);
+        }
+        self.run_transport(transport).await
     }
 
-    pub async fn run_sse_transport(&mut self) -> Result<(), McpError> {
-        let transport = SseTransport::new_server(
+    pub async fn run_streamable_http_transport(&mut self) -> Result<(), McpError> {
+        let mut transport = StreamableHttpTransport::new_server(
             self.config.server.host.clone(),
             self.config.server.port,
             4096,
-        );
+        )
+        .with_allowed_origins(self.allowed_origins());
+        if let Some(auth) = BearerAuth::from_settings(&self.config.security)? {
+            transport = transport.with_auth(auth);
+        }
+        self.run_transport(transport).await
+    }
+
+    fn allowed_origins(&self) -> AllowedOrigins {
+        AllowedOrigins::new(self.config.security.allowed_origins.clone())
+    }
+
+    /// Serve the protocol over `transport` until a shutdown signal is received
+    pub async fn run_transport<T: Transport>(&mut self, transport: T) -> Result<(), McpError> {
         let protocol = Protocol::builder(Some(ProtocolOptions {
             enforce_strict_capabilities: true,
-        }));
+            ..Default::default()
+        }))
+        .with_capabilities(PeerCapabilities::Server(self.capabilities()));
 
         // Build and connect protocol
         let mut protocol = self.register_protocol_handlers(protocol).build();
@@ -332,6 +313,7 @@ impl McpServer {
         // Spawn notification handler
         let notification_task = {
             let shutdown_requested = Arc::clone(&shutdown_requested);
+            let sessions = self.sessions.clone();
             tokio::spawn({
                 let protocol_handle = protocol_handle.clone();
                 async move {
@@ -339,12 +321,14 @@ impl McpServer {
                         if shutdown_requested.load(Ordering::SeqCst) {
                             break;
                         }
-                        if let Err(e) = protocol_handle
-                            .get_ref()
-                            .send_notification(notification)
-                            .await
-                        {
-                            tracing::error!("Failed to send notification: {:?}", e);
+                        for session_id in sessions.recipients(&notification).await {
+                            if let Err(e) = protocol_handle
+                                .get_ref()
+                                .send_notification_to(session_id, notification.clone())
+                                .await
+                            {
+                                tracing::error!("Failed to send notification: {:?}", e);
+                            }
                         }
                     }
                 }
@@ -374,7 +358,7 @@ impl McpServer {
 
         // Perform graceful shutdown
         shutdown_requested.store(true, Ordering::SeqCst);
-        *self.state.write().await = ServerState::ShuttingDown;
+        self.sessions.shutdown().await;
 
         // Close protocol through handle
         protocol_handle.close().await?;
@@ -389,7 +373,6 @@ impl McpServer {
     pub fn register_resource_handlers(&self, builder: ProtocolBuilder) -> ProtocolBuilder {
         // Clone Arc references once at the beginning
         let resource_manager = Arc::clone(&self.resource_manager);
-        let tool_manager = Arc::clone(&self.tool_manager);
 
         // Chain all handlers in a single builder flow
         let builder = builder.with_request_handler(
@@ -397,33 +380,29 @@ impl McpServer {
             Box::new(move |request, _extra| {
                 let rm = Arc::clone(&resource_manager);
                 Box::pin(async move {
-                    let params: ListResourcesRequest = if let Some(params) = request.params {
-                        serde_json::from_value(params).unwrap()
-                    } else {
-                        ListResourcesRequest { cursor: None }
-                    };
+                    let params: ListResourcesRequest = request.parse_params()?;
 
                     rm.list_resources(params.cursor)
                         .await
                         .map(|response| serde_json::to_value(response).unwrap())
-                        .map_err(|e| e.into())
                 })
             }),
         );
 
         // Clone for next handler
         let resource_manager = Arc::clone(&self.resource_manager);
+        let sessions = self.sessions.clone();
         let builder = builder.with_request_handler(
             "resources/read",
-            Box::new(move |request, _extra| {
+            Box::new(move |request, extra| {
                 let rm = Arc::clone(&resource_manager);
+                let sessions = sessions.clone();
                 Box::pin(async move {
-                    let params: ReadResourceRequest =
-                        serde_json::from_value(request.params.unwrap()).unwrap();
-                    rm.read_resource(&params.uri)
+                    let params: ReadResourceRequest = request.parse_params()?;
+                    let client = ClientPeer::for_request(&sessions, extra).await;
+                    rm.read_resource_with_client(&params.uri, &client)
                         .await
                         .map(|response| serde_json::to_value(response).unwrap())
-                        .map_err(|e| e.into())
                 })
             }),
         );
@@ -438,21 +417,42 @@ impl McpServer {
                     rm.list_templates()
                         .await
                         .map(|response| serde_json::to_value(response).unwrap())
-                        .map_err(|e| e.into())
                 })
             }),
         );
 
-        // Clone for conditional handler
+        // Clone for conditional handlers
         let builder = if self.resource_manager.capabilities.subscribe {
             let resource_manager = Arc::clone(&self.resource_manager);
-            builder.with_request_handler(
+            let sessions = self.sessions.clone();
+            let builder = builder.with_request_handler(
                 "resources/subscribe",
-                Box::new(move |request, _extra| {
+                Box::new(move |request, extra| {
                     let rm = Arc::clone(&resource_manager);
+                    let sessions = sessions.clone();
                     Box::pin(async move {
-                        let params = serde_json::from_value(request.params.unwrap()).unwrap();
-                        rm.subscribe(request.id.to_string(), params)
+                        let session_id = session_key(extra.session_id);
+                        let SubscribeRequest { uri } = request.parse_params()?;
+                        sessions.subscribe(&session_id, uri.clone()).await?;
+                        rm.subscribe(session_id, uri)
+                            .await
+                            .map(|_| serde_json::json!({}))
+                    })
+                }),
+            );
+
+            let resource_manager = Arc::clone(&self.resource_manager);
+            let sessions = self.sessions.clone();
+            builder.with_request_handler(
+                "resources/unsubscribe",
+                Box::new(move |request, extra| {
+                    let rm = Arc::clone(&resource_manager);
+                    let sessions = sessions.clone();
+                    Box::pin(async move {
+                        let session_id = session_key(extra.session_id);
+                        let SubscribeRequest { uri } = request.parse_params()?;
+                        sessions.unsubscribe(&session_id, &uri).await?;
+                        rm.unsubscribe(&session_id, &uri)
                             .await
                             .map(|_| serde_json::json!({}))
                     })
@@ -462,50 +462,47 @@ impl McpServer {
             builder
         };
 
-        // Add tool handlers
-        let builder = builder.with_request_handler(
-            "tools/list",
-            Box::new(move |request, _extra| {
-                let tm = Arc::clone(&tool_manager);
-                Box::pin(async move {
-                    let params: ListToolsRequest = if let Some(params) = request.params {
-                        serde_json::from_value(params).map_err(|e| {
-                            tracing::error!("Error parsing list tools request: {:?}", e);
-
-                            McpError::ParseError
-                        })?
-                    } else {
-                        ListToolsRequest { cursor: None }
-                    };
+        // Add tool handlers. Disabled tools are not handled at all, so clients
+        // get method not found.
+        let builder = if self.config.tool_settings.enabled {
+            let tool_manager = Arc::clone(&self.tool_manager);
+            let builder = builder.with_request_handler(
+                "tools/list",
+                Box::new(move |request, _extra| {
+                    let tm = Arc::clone(&tool_manager);
+                    Box::pin(async move {
+                        let params: ListToolsRequest = request.parse_params()?;
 
-                    tm.list_tools(params.cursor)
-                        .await
-                        .map(|response| serde_json::to_value(response).unwrap())
-                        .map_err(|e| e.into())
-                })
-            }),
-        );
+                        tm.list_tools(params.cursor)
+                            .await
+                            .map(|response| serde_json::to_value(response).unwrap())
+                    })
+                }),
+            );
 
-        // Clone for final handler
-        let tool_manager = Arc::clone(&self.tool_manager);
-        let builder = builder.with_request_handler(
-            "tools/call",
-            Box::new(move |request, _extra| {
-                let tm = Arc::clone(&tool_manager);
-                println!("Request: {:?}", request);
-                Box::pin(async move {
-                    let params: CallToolRequest =
-                        serde_json::from_value(request.params.unwrap()).unwrap();
-                    tm.call_tool(&params.name, params.arguments)
-                        .await
-                        .map(|response| {
-                            println!("Response: {:?}", response);
-                            serde_json::to_value(response).unwrap()
-                        })
-                        .map_err(|e| e.into())
-                })
-            }),
-        );
+            let tool_manager = Arc::clone(&self.tool_manager);
+            let sessions = self.sessions.clone();
+            builder.with_request_handler(
+                "tools/call",
+                Box::new(move |request, extra| {
+                    let tm = Arc::clone(&tool_manager);
+                    let sessions = sessions.clone();
+                    println!("Request: {:?}", request);
+                    Box::pin(async move {
+                        let params: CallToolRequest = request.parse_params()?;
+                        let client = ClientPeer::for_request(&sessions, extra).await;
+                        tm.call_tool_with_client(&params.name, params.arguments, client)
+                            .await
+                            .map(|response| {
+                                println!("Response: {:?}", response);
+                                serde_json::to_value(response).unwrap()
+                            })
+                    })
+                }),
+            )
+        } else {
+            builder
+        };
 
         // Add prompt handlers
         let prompt_manager = Arc::clone(&self.prompt_manager);
@@ -514,45 +511,44 @@ impl McpServer {
             Box::new(move |request, _extra| {
                 let pm = Arc::clone(&prompt_manager);
                 Box::pin(async move {
-                    let params: ListPromptsRequest = if let Some(params) = request.params {
-                        serde_json::from_value(params).unwrap()
-                    } else {
-                        ListPromptsRequest { cursor: None }
-                    };
+                    let params: ListPromptsRequest = request.parse_params()?;
 
                     pm.list_prompts(params.cursor)
                         .await
                         .map(|response| serde_json::to_value(response).unwrap())
-                        .map_err(|e| e.into())
                 })
             }),
         );
 
         let prompt_manager = Arc::clone(&self.prompt_manager);
+        let sessions = self.sessions.clone();
         let builder = builder.with_request_handler(
             "prompts/get",
-            Box::new(move |request, _extra| {
+            Box::new(move |request, extra| {
                 let pm = Arc::clone(&prompt_manager);
+                let sessions = sessions.clone();
                 Box::pin(async move {
-                    let params: GetPromptRequest =
-                        serde_json::from_value(request.params.unwrap()).unwrap();
-                    pm.get_prompt(&params.name, params.arguments)
+                    let params: GetPromptRequest = request.parse_params()?;
+                    let client = ClientPeer::for_request(&sessions, extra).await;
+                    pm.get_prompt_with_client(&params.name, params.arguments, client)
                         .await
                         .map(|response| serde_json::to_value(response).unwrap())
-                        .map_err(|e| e.into())
                 })
             }),
         );
 
         // Add logging handlers
-        let logging_manager = Arc::clone(&self.logging_manager);
+        let sessions = self.sessions.clone();
         let builder = builder.with_request_handler(
             "logging/setLevel",
-            Box::new(move |request, _extra| {
-                let lm = Arc::clone(&logging_manager);
+            Box::new(move |request, extra| {
+                let sessions = sessions.clone();
                 Box::pin(async move {
-                    let params: SetLevelRequest = serde_json::from_value(request.params.unwrap())?;
-                    lm.lock().await.set_level(params.level).await?;
+                    let params: SetLevelRequest = request.parse_params()?;
+                    let level: LogLevel = params.level.parse()?;
+                    sessions
+                        .set_log_level(&session_key(extra.session_id), level)
+                        .await?;
                     Ok(serde_json::json!({}))
                 })
             }),
@@ -563,7 +559,7 @@ impl McpServer {
 
     // Add this method to handle shutdown requests
     fn register_shutdown_handlers(&self, builder: ProtocolBuilder) -> ProtocolBuilder {
-        let builder = builder.with_request_handler(
+        builder.with_request_handler(
             "shutdown",
             Box::new(move |_request, _extra| {
                 Box::pin(async move {
@@ -571,16 +567,14 @@ impl McpServer {
                     Ok(serde_json::json!({}))
                 })
             }),
-        );
-
-        builder
+        )
     }
 
     pub fn register_protocol_handlers(&self, builder: ProtocolBuilder) -> ProtocolBuilder {
         // Clone required components for initialize handler
-        let state = Arc::clone(&self.state);
+        let sessions = self.sessions.clone();
         let supported_versions = self.supported_versions.clone();
-        let client_capabilities = Arc::clone(&self.client_capabilities);
+        let capabilities = self.capabilities();
         let server_info = ServerInfo {
             name: self.config.server.name.clone(),
             version: self.config.server.version.clone(),
@@ -588,75 +582,98 @@ impl McpServer {
 
         let builder = builder.with_request_handler(
             "initialize",
-            Box::new(move |request, _extra| {
-                let state = Arc::clone(&state);
+            Box::new(move |request, extra| {
+                let sessions = sessions.clone();
                 let supported_versions = supported_versions.clone();
-                let client_capabilities = Arc::clone(&client_capabilities);
                 let server_info = server_info.clone();
+                let capabilities = capabilities.clone();
 
                 Box::pin(async move {
                     tracing::debug!("Handling initialize request");
-                    let params: InitializeParams = serde_json::from_value(request.params.unwrap())?;
-
-                    // Verify state
-                    let mut state = state.write().await;
-                    if *state != ServerState::Created {
-                        return Err(McpError::InvalidRequest(
-                            "Server already initialized".to_string(),
-                        ));
-                    }
-                    *state = ServerState::Initializing;
-
-                    // Validate protocol version
-                    if !supported_versions.contains(&params.protocol_version) {
-                        return Err(McpError::InvalidRequest(format!(
-                            "Unsupported protocol version: {}. Supported versions: {:?}",
-                            params.protocol_version, supported_versions
-                        )));
-                    }
-
-                    // Store client capabilities
-                    *client_capabilities.write().await = Some(params.capabilities);
-
-                    // Return server capabilities
-                    let result = InitializeResult {
-                        protocol_version: "2024-11-05".to_string(),
-                        capabilities: ServerCapabilities {
-                            logging: Some(LoggingCapabilities {}),
-                            prompts: Some(PromptCapabilities { list_changed: true }),
-                            resources: Some(ResourceCapabilities {
-                                subscribe: true,
-                                list_changed: true,
-                            }),
-                            tools: Some(ToolCapabilities { list_changed: true }),
-                        },
+                    let params: InitializeParams = request.parse_params()?;
+
+                    // Each connection negotiates its own session and protocol version
+                    let protocol_version = sessions
+                        .initialize(&session_key(extra.session_id.clone()), &params, &supported_versions)
+                        .await?;
+                    extra
+                        .peer
+                        .set_remote_capabilities(
+                            extra.session_id,
+                            PeerCapabilities::Client(params.capabilities),
+                        )
+                        .await;
+
+                    Ok(serde_json::to_value(Self::initialize_result(
                         server_info,
-                    };
-
-                    Ok(serde_json::to_value(result).unwrap())
+                        capabilities,
+                        protocol_version,
+                    ))
+                    .unwrap())
                 })
             }),
         );
 
-        // Add initialized notification handler
-        let state = Arc::clone(&self.state);
+        // Add initialized notification handler. The bare "initialized" is still
+        // accepted from clients that predate the spec name.
+        let mut builder = builder;
+        for method in ["notifications/initialized", "initialized"] {
+            let sessions = self.sessions.clone();
+            builder = builder.with_notification_handler(
+                method,
+                Box::new(move |_notification, extra| {
+                    let sessions = sessions.clone();
+                    Box::pin(async move {
+                        sessions.initialized(&session_key(extra.session_id.clone())).await?;
+                        Self::refresh_roots(sessions, extra);
+                        Ok(())
+                    })
+                }),
+            );
+        }
+
+        let sessions = self.sessions.clone();
         let builder = builder.with_notification_handler(
-            "initialized",
-            Box::new(move |_| {
-                let state = Arc::clone(&state);
+            "notifications/roots/list_changed",
+            Box::new(move |_notification, extra| {
+                let sessions = sessions.clone();
                 Box::pin(async move {
-                    let mut state = state.write().await;
-                    if *state != ServerState::Initializing {
-                        return Err(McpError::InvalidRequest(
-                            "Invalid server state for initialized notification".to_string(),
-                        ));
-                    }
-                    *state = ServerState::Running;
+                    Self::refresh_roots(sessions, extra);
                     Ok(())
                 })
             }),
         );
 
+        // Refuse requests beyond the configured rates before they reach a handler
+        let rate_limiter = self.rate_limiter.clone();
+        let tools = Arc::clone(&self.tool_manager.tools);
+        let builder = builder.with_request_guard(Box::new(move |request, session_id| {
+            // While a tool is being registered its name counts as unknown
+            let is_registered = |name: &str| {
+                tools
+                    .try_read()
+                    .is_ok_and(|tools| tools.contains_key(name))
+            };
+            rate_limiter.check(request, &session_key(session_id.cloned()), is_registered)
+        }));
+
+        // Drop session state, including resource subscriptions, when a client disconnects
+        let sessions = self.sessions.clone();
+        let resource_manager = Arc::clone(&self.resource_manager);
+        let rate_limiter = self.rate_limiter.clone();
+        let builder = builder.with_session_closed_handler(Box::new(move |session_id| {
+            let sessions = sessions.clone();
+            let rm = Arc::clone(&resource_manager);
+            rate_limiter.forget_session(&session_id);
+            Box::pin(async move {
+                if let Some(session) = sessions.close(&session_id).await {
+                    for uri in session.subscriptions {
+                        let _ = rm.unsubscribe(&session_id, &uri).await;
+                    }
+                }
+            })
+        }));
+
         // Chain with existing handlers
         let builder = self.register_resource_handlers(builder);
         let builder = self.register_shutdown_handlers(builder);
@@ -665,7 +682,7 @@ impl McpServer {
         let notification_tx = self.notification_tx.clone();
         let builder = builder.with_notification_handler(
             "shutdown",
-            Box::new(move |_notification| {
+            Box::new(move |_notification, _extra| {
                 let notification_tx = notification_tx.clone();
                 Box::pin(async move {
                     let ack = JsonRpcNotification {
@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+use super::{
+    session::{session_key, SessionManager},
+    ClientCapabilities, ClientInfo,
+};
+use crate::{
+    error::McpError,
+    protocol::{Protocol, RequestHandlerExtra},
+    roots::{ListRootsResult, Root},
+    sampling::{CreateMessageRequest, CreateMessageResult},
+    transport::SessionId,
+};
+
+/// The client whose request is being handled. Tools and prompts use it to send
+/// requests back to that client while they work.
+#[derive(Clone)]
+pub struct ClientPeer {
+    session_id: Option<SessionId>,
+    capabilities: Option<ClientCapabilities>,
+    client_info: Option<ClientInfo>,
+    roots: Vec<Root>,
+    protocol_version: Option<String>,
+    protocol: Protocol,
+}
+
+impl ClientPeer {
+    /// The client that sent the request `extra` belongs to
+    pub(crate) async fn for_request(sessions: &SessionManager, extra: RequestHandlerExtra) -> Self {
+        Self::for_session(sessions, extra.session_id, extra.peer).await
+    }
+
+    pub(crate) async fn for_session(
+        sessions: &SessionManager,
+        session_id: Option<SessionId>,
+        protocol: Protocol,
+    ) -> Self {
+        let session = sessions.get(&session_key(session_id.clone())).await;
+        let (capabilities, client_info, roots, protocol_version) = match session {
+            Some(session) => (
+                Some(session.client_capabilities),
+                Some(session.client_info),
+                session.roots,
+                Some(session.protocol_version),
+            ),
+            None => (None, None, Vec::new(), None),
+        };
+        Self {
+            session_id,
+            capabilities,
+            client_info,
+            roots,
+            protocol_version,
+            protocol,
+        }
+    }
+
+    /// Session of the client on multi-session transports
+    pub fn session_id(&self) -> Option<&SessionId> {
+        self.session_id.as_ref()
+    }
+
+    /// Capabilities the client declared during initialization
+    pub fn capabilities(&self) -> Option<&ClientCapabilities> {
+        self.capabilities.as_ref()
+    }
+
+    /// Name and version the client gave during initialization
+    pub fn client_info(&self) -> Option<&ClientInfo> {
+        self.client_info.as_ref()
+    }
+
+    /// Protocol version negotiated with the client, for features that depend on it
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
+    /// Roots the client shared, as of the last `roots/list`. The server fetches
+    /// them after initialization and again whenever the client reports a change.
+    pub fn roots(&self) -> &[Root] {
+        &self.roots
+    }
+
+    /// Local directories of the client's `file://` roots
+    pub fn root_directories(&self) -> Vec<PathBuf> {
+        self.roots.iter().filter_map(Root::path).collect()
+    }
+
+    /// Ask the client for its current roots with `roots/list`
+    pub async fn list_roots(&self) -> Result<Vec<Root>, McpError> {
+        if self
+            .capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.roots.as_ref())
+            .is_none()
+        {
+            return Err(McpError::CapabilityNotSupported("roots".to_string()));
+        }
+
+        let result: ListRootsResult = self
+            .protocol
+            .request_to(self.session_id.clone(), "roots/list", Option::<()>::None, None)
+            .await?;
+        Ok(result.roots)
+    }
+
+    /// Ask the client to sample from its language model with `sampling/createMessage`
+    pub async fn create_message(
+        &self,
+        request: CreateMessageRequest,
+    ) -> Result<CreateMessageResult, McpError> {
+        if self
+            .capabilities
+            .as_ref()
+            .and_then(|capabilities| capabilities.sampling.as_ref())
+            .is_none()
+        {
+            return Err(McpError::CapabilityNotSupported("sampling".to_string()));
+        }
+
+        self.protocol
+            .request_to(
+                self.session_id.clone(),
+                "sampling/createMessage",
+                Some(request),
+                None,
+            )
+            .await
+    }
+}
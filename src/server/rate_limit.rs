@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::config::{RateLimitSettings, SecuritySettings, ToolSettings};
+use crate::{error::McpError, protocol::JsonRpcRequest, transport::SessionId};
+
+/// Methods that are never limited. Pings are cheap and keep heartbeats honest.
+const UNLIMITED_METHODS: &[&str] = &["ping"];
+
+/// Refills continuously at `rate` tokens per second, up to `capacity`
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// `None` when `settings` turn the limit off
+    fn new(settings: &RateLimitSettings, now: Instant) -> Option<Self> {
+        if settings.requests_per_minute == 0 {
+            return None;
+        }
+        let capacity = settings.burst_size.max(1) as f64;
+        Some(Self {
+            capacity,
+            rate: settings.requests_per_minute as f64 / 60.0,
+            tokens: capacity,
+            updated: now,
+        })
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// How long until a token is available, or zero if one is now
+    fn wait(&self) -> Duration {
+        if self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum BucketKey {
+    Global,
+    Session(SessionId),
+    /// `tools/call` of one session, per registered tool if so configured.
+    /// Calls to all other names share the `None` bucket.
+    Tools(SessionId, Option<String>),
+}
+
+impl BucketKey {
+    fn session(&self) -> Option<&SessionId> {
+        match self {
+            BucketKey::Global => None,
+            BucketKey::Session(session_id) | BucketKey::Tools(session_id, _) => Some(session_id),
+        }
+    }
+}
+
+// Request Rate Limiting
+//
+// Every request takes a token from its session's bucket and, if configured,
+// from a bucket shared by all sessions. `tools/call` also takes one from a
+// separate tool bucket. A request that finds any of its buckets empty takes
+// nothing and is refused with `McpError::RateLimited`, so one busy session
+// cannot use up the budget of the others.
+#[derive(Clone)]
+pub struct RateLimiter {
+    requests: RateLimitSettings,
+    global: Option<RateLimitSettings>,
+    tools: RateLimitSettings,
+    per_tool: bool,
+    buckets: Arc<Mutex<HashMap<BucketKey, TokenBucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(security: &SecuritySettings, tools: &ToolSettings) -> Self {
+        Self {
+            requests: security.rate_limit.clone(),
+            global: security.global_rate_limit.clone(),
+            tools: tools.rate_limit.clone(),
+            per_tool: tools.rate_limit_per_tool,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Take a token for `request` from `session_id`, or tell it when to retry.
+    /// Only tools for which `is_registered` holds get buckets of their own.
+    pub fn check(
+        &self,
+        request: &JsonRpcRequest,
+        session_id: &str,
+        is_registered: impl Fn(&str) -> bool,
+    ) -> Result<(), McpError> {
+        self.check_at(request, session_id, &is_registered, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        request: &JsonRpcRequest,
+        session_id: &str,
+        is_registered: &dyn Fn(&str) -> bool,
+        now: Instant,
+    ) -> Result<(), McpError> {
+        if UNLIMITED_METHODS.contains(&request.method.as_str()) {
+            return Ok(());
+        }
+
+        let mut limits = vec![(BucketKey::Session(session_id.to_string()), &self.requests)];
+        if let Some(global) = &self.global {
+            limits.push((BucketKey::Global, global));
+        }
+        if request.method == "tools/call" {
+            let tool = self
+                .per_tool
+                .then(|| request.params.as_ref()?.get("name")?.as_str())
+                .flatten()
+                .filter(|name| is_registered(name))
+                .map(str::to_string);
+            limits.push((BucketKey::Tools(session_id.to_string(), tool), &self.tools));
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut keys = Vec::new();
+        let mut retry_after = Duration::ZERO;
+        for (key, settings) in limits {
+            if !buckets.contains_key(&key) {
+                match TokenBucket::new(settings, now) {
+                    Some(bucket) => buckets.insert(key.clone(), bucket),
+                    None => continue,
+                };
+            }
+            let bucket = buckets.get_mut(&key).unwrap();
+            bucket.refill(now);
+            retry_after = retry_after.max(bucket.wait());
+            keys.push(key);
+        }
+
+        if !retry_after.is_zero() {
+            return Err(McpError::RateLimited { retry_after });
+        }
+        for key in keys {
+            if let Some(bucket) = buckets.get_mut(&key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the buckets of a session that has gone away
+    pub fn forget_session(&self, session_id: &str) {
+        self.buckets
+            .lock()
+            .unwrap()
+            .retain(|key, _| key.session().map(String::as_str) != Some(session_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::RequestId;
+
+    fn request(method: &str, params: Option<serde_json::Value>) -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: RequestId::Number(1),
+            method: method.to_string(),
+            params,
+        }
+    }
+
+    fn call(tool: &str) -> JsonRpcRequest {
+        request("tools/call", Some(serde_json::json!({ "name": tool })))
+    }
+
+    fn limit(requests_per_minute: u32, burst_size: u32) -> RateLimitSettings {
+        RateLimitSettings {
+            requests_per_minute,
+            burst_size,
+        }
+    }
+
+    fn limiter(
+        requests: RateLimitSettings,
+        global: Option<RateLimitSettings>,
+        tools: RateLimitSettings,
+        per_tool: bool,
+    ) -> RateLimiter {
+        let security = SecuritySettings {
+            rate_limit: requests,
+            global_rate_limit: global,
+            ..Default::default()
+        };
+        let tool_settings = ToolSettings {
+            rate_limit: tools,
+            rate_limit_per_tool: per_tool,
+            ..Default::default()
+        };
+        RateLimiter::new(&security, &tool_settings)
+    }
+
+    fn registered(name: &str) -> bool {
+        ["echo", "other"].contains(&name)
+    }
+
+    fn retry_after(result: Result<(), McpError>) -> Duration {
+        match result {
+            Err(McpError::RateLimited { retry_after }) => retry_after,
+            other => panic!("Expected RateLimited, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_session_bucket_refills() {
+        let limiter = limiter(limit(60, 2), None, limit(0, 0), false);
+        let now = Instant::now();
+        let list = request("tools/list", None);
+
+        assert!(limiter.check_at(&list, "a", &registered, now).is_ok());
+        assert!(limiter.check_at(&list, "a", &registered, now).is_ok());
+        assert_eq!(
+            retry_after(limiter.check_at(&list, "a", &registered, now)),
+            Duration::from_secs(1)
+        );
+
+        // Other sessions have their own bucket, and pings are never limited
+        assert!(limiter.check_at(&list, "b", &registered, now).is_ok());
+        assert!(limiter.check_at(&request("ping", None), "a", &registered, now).is_ok());
+
+        let later = now + Duration::from_millis(1500);
+        assert!(limiter.check_at(&list, "a", &registered, later).is_ok());
+        assert!(limiter.check_at(&list, "a", &registered, later).is_err());
+
+        // A forgotten session starts over with a full bucket
+        limiter.forget_session("a");
+        assert!(limiter.check_at(&list, "a", &registered, later).is_ok());
+        assert!(limiter.check_at(&list, "a", &registered, later).is_ok());
+    }
+
+    #[test]
+    fn test_global_bucket_is_shared() {
+        let limiter = limiter(limit(60, 5), Some(limit(60, 3)), limit(0, 0), false);
+        let now = Instant::now();
+        let list = request("tools/list", None);
+
+        assert!(limiter.check_at(&list, "a", &registered, now).is_ok());
+        assert!(limiter.check_at(&list, "b", &registered, now).is_ok());
+        assert!(limiter.check_at(&list, "c", &registered, now).is_ok());
+        assert!(limiter.check_at(&list, "d", &registered, now).is_err());
+    }
+
+    #[test]
+    fn test_tool_buckets() {
+        let now = Instant::now();
+
+        let shared = limiter(limit(60, 10), None, limit(6, 1), false);
+        assert!(shared.check_at(&call("echo"), "a", &registered, now).is_ok());
+        assert_eq!(
+            retry_after(shared.check_at(&call("other"), "a", &registered, now)),
+            Duration::from_secs(10)
+        );
+        // Refused requests take no tokens from the other buckets
+        for _ in 0..9 {
+            assert!(shared.check_at(&request("tools/list", None), "a", &registered, now).is_ok());
+        }
+        assert!(shared.check_at(&request("tools/list", None), "a", &registered, now).is_err());
+
+        let per_tool = limiter(limit(60, 10), None, limit(6, 1), true);
+        assert!(per_tool.check_at(&call("echo"), "a", &registered, now).is_ok());
+        assert!(per_tool.check_at(&call("other"), "a", &registered, now).is_ok());
+        assert!(per_tool.check_at(&call("echo"), "a", &registered, now).is_err());
+
+        // Names of no registered tool share a single bucket
+        assert!(per_tool.check_at(&call("missing-1"), "a", &registered, now).is_ok());
+        assert!(per_tool.check_at(&call("missing-2"), "a", &registered, now).is_err());
+        assert_eq!(per_tool.buckets.lock().unwrap().len(), 4);
+    }
+}
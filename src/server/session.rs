@@ -0,0 +1,299 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::RwLock;
+
+use super::{ClientCapabilities, ClientInfo, InitializeParams};
+use crate::{
+    error::McpError,
+    logging::LogLevel,
+    protocol::{negotiate_protocol_version, JsonRpcNotification},
+    roots::Root,
+    transport::SessionId,
+};
+
+// Per-connection server state enum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ServerState {
+    Initializing,
+    Running,
+    ShuttingDown,
+}
+
+/// Lifecycle state of one connected client
+#[derive(Debug, Clone)]
+pub(crate) struct ServerSession {
+    pub(crate) state: ServerState,
+    /// The version negotiated during initialization
+    pub(crate) protocol_version: String,
+    pub(crate) client_capabilities: ClientCapabilities,
+    pub(crate) client_info: ClientInfo,
+    pub(crate) subscriptions: HashSet<String>,
+    pub(crate) log_level: LogLevel,
+    /// Roots last fetched from the client, if it declared the capability
+    pub(crate) roots: Vec<Root>,
+}
+
+/// Single-peer transports such as stdio carry no session id and share one
+/// session under the empty id.
+pub(crate) fn session_key(session_id: Option<SessionId>) -> SessionId {
+    session_id.unwrap_or_default()
+}
+
+/// Every client that has started the initialize handshake, keyed by session
+#[derive(Clone)]
+pub(crate) struct SessionManager {
+    sessions: Arc<RwLock<HashMap<SessionId, ServerSession>>>,
+    max_connections: usize,
+}
+
+impl SessionManager {
+    pub(crate) fn new(max_connections: usize) -> Self {
+        Self {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            max_connections,
+        }
+    }
+
+    pub(crate) async fn initialize(
+        &self,
+        session_id: &str,
+        params: &InitializeParams,
+        supported_versions: &[String],
+    ) -> Result<String, McpError> {
+        let mut sessions = self.sessions.write().await;
+        if sessions.contains_key(session_id) {
+            return Err(McpError::InvalidRequest(
+                "Server already initialized".to_string(),
+            ));
+        }
+        if sessions.len() >= self.max_connections {
+            return Err(McpError::InvalidRequest(format!(
+                "Too many connections (max {})",
+                self.max_connections
+            )));
+        }
+
+        // Answer with the client's version if we speak it, else our newest; the
+        // client decides whether it can work with that
+        let protocol_version = negotiate_protocol_version(&params.protocol_version, supported_versions)
+            .ok_or_else(|| McpError::InternalError("No supported protocol versions".to_string()))?;
+
+        sessions.insert(
+            session_id.to_string(),
+            ServerSession {
+                state: ServerState::Initializing,
+                protocol_version: protocol_version.clone(),
+                client_capabilities: params.capabilities.clone(),
+                client_info: params.client_info.clone(),
+                subscriptions: HashSet::new(),
+                log_level: LogLevel::Info,
+                roots: Vec::new(),
+            },
+        );
+        Ok(protocol_version)
+    }
+
+    pub(crate) async fn initialized(&self, session_id: &str) -> Result<(), McpError> {
+        let mut sessions = self.sessions.write().await;
+        match sessions.get_mut(session_id) {
+            Some(session) if session.state == ServerState::Initializing => {
+                session.state = ServerState::Running;
+                Ok(())
+            }
+            _ => Err(McpError::InvalidRequest(
+                "Invalid server state for initialized notification".to_string(),
+            )),
+        }
+    }
+
+    pub(crate) async fn assert_initialized(&self, session_id: &str) -> Result<(), McpError> {
+        match self.get(session_id).await {
+            Some(session) if session.state == ServerState::Running => Ok(()),
+            _ => Err(McpError::InvalidRequest(
+                "Server not initialized".to_string(),
+            )),
+        }
+    }
+
+    pub(crate) async fn get(&self, session_id: &str) -> Option<ServerSession> {
+        self.sessions.read().await.get(session_id).cloned()
+    }
+
+    pub(crate) async fn set_roots(&self, session_id: &str, roots: Vec<Root>) -> Result<(), McpError> {
+        self.update(session_id, |session| session.roots = roots).await
+    }
+
+    pub(crate) async fn set_log_level(&self, session_id: &str, level: LogLevel) -> Result<(), McpError> {
+        self.update(session_id, |session| session.log_level = level).await
+    }
+
+    pub(crate) async fn subscribe(&self, session_id: &str, uri: String) -> Result<(), McpError> {
+        self.update(session_id, |session| {
+            session.subscriptions.insert(uri);
+        })
+        .await
+    }
+
+    pub(crate) async fn unsubscribe(&self, session_id: &str, uri: &str) -> Result<(), McpError> {
+        self.update(session_id, |session| {
+            session.subscriptions.remove(uri);
+        })
+        .await
+    }
+
+    async fn update(
+        &self,
+        session_id: &str,
+        f: impl FnOnce(&mut ServerSession),
+    ) -> Result<(), McpError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| McpError::InvalidRequest("Server not initialized".to_string()))?;
+        f(session);
+        Ok(())
+    }
+
+    /// Forget a disconnected session and return its final state
+    pub(crate) async fn close(&self, session_id: &str) -> Option<ServerSession> {
+        self.sessions.write().await.remove(session_id)
+    }
+
+    pub(crate) async fn shutdown(&self) {
+        for session in self.sessions.write().await.values_mut() {
+            session.state = ServerState::ShuttingDown;
+        }
+    }
+
+    /// Running sessions that should receive `notification`. Log messages are
+    /// filtered by each session's level and resource updates go to subscribers only.
+    pub(crate) async fn recipients(&self, notification: &JsonRpcNotification) -> Vec<SessionId> {
+        let param = |name: &str| {
+            notification
+                .params
+                .as_ref()
+                .and_then(|p| p.get(name))
+                .and_then(|v| v.as_str())
+        };
+
+        self.sessions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, session)| session.state == ServerState::Running)
+            .filter(|(_, session)| match notification.method.as_str() {
+                "notifications/message" => param("level")
+                    .and_then(|level| level.parse::<LogLevel>().ok())
+                    .is_none_or(|level| level >= session.log_level),
+                "notifications/resources/updated" => {
+                    param("uri").is_some_and(|uri| session.subscriptions.contains(uri))
+                }
+                _ => true,
+            })
+            .map(|(session_id, _)| session_id.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ClientInfo;
+
+    fn params(protocol_version: &str) -> InitializeParams {
+        InitializeParams {
+            protocol_version: protocol_version.to_string(),
+            capabilities: ClientCapabilities {
+                roots: None,
+                sampling: None,
+            },
+            client_info: ClientInfo {
+                name: "test-client".to_string(),
+                version: "1.0.0".to_string(),
+            },
+        }
+    }
+
+    fn notification(method: &str, params: serde_json::Value) -> JsonRpcNotification {
+        JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params: Some(params),
+        }
+    }
+
+    async fn running(sessions: &SessionManager, session_id: &str) {
+        let versions = vec!["2024-11-05".to_string()];
+        sessions
+            .initialize(session_id, &params("2024-11-05"), &versions)
+            .await
+            .unwrap();
+        sessions.initialized(session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sessions_initialize_independently() {
+        let sessions = SessionManager::new(2);
+        let versions = vec!["2024-11-05".to_string()];
+
+        running(&sessions, "a").await;
+        assert!(sessions.assert_initialized("a").await.is_ok());
+        assert!(sessions.assert_initialized("b").await.is_err());
+
+        // A second client is not blocked by the first one's handshake
+        running(&sessions, "b").await;
+        assert!(sessions
+            .initialize("a", &params("2024-11-05"), &versions)
+            .await
+            .is_err());
+
+        // Limited by max_connections until a session closes
+        assert!(sessions
+            .initialize("c", &params("2024-11-05"), &versions)
+            .await
+            .is_err());
+        assert!(sessions.close("a").await.is_some());
+        running(&sessions, "c").await;
+    }
+
+    #[tokio::test]
+    async fn test_version_negotiation() {
+        let sessions = SessionManager::new(10);
+        let versions = vec!["2024-11-05".to_string(), "2025-03-26".to_string()];
+
+        // A supported version is kept, anything else gets our newest
+        let agreed = sessions.initialize("a", &params("2024-11-05"), &versions).await;
+        assert_eq!(agreed.unwrap(), "2024-11-05");
+        let agreed = sessions.initialize("b", &params("2099-01-01"), &versions).await;
+        assert_eq!(agreed.unwrap(), "2025-03-26");
+
+        assert_eq!(sessions.get("a").await.unwrap().protocol_version, "2024-11-05");
+        assert_eq!(sessions.get("b").await.unwrap().protocol_version, "2025-03-26");
+    }
+
+    #[tokio::test]
+    async fn test_recipients() {
+        let sessions = SessionManager::new(10);
+        running(&sessions, "a").await;
+        running(&sessions, "b").await;
+        sessions.set_log_level("b", LogLevel::Error).await.unwrap();
+        sessions.subscribe("a", "file:///a.txt".to_string()).await.unwrap();
+
+        let mut all = sessions
+            .recipients(&notification("notifications/tools/list_changed", serde_json::json!({})))
+            .await;
+        all.sort();
+        assert_eq!(all, vec!["a", "b"]);
+
+        let info = notification("notifications/message", serde_json::json!({ "level": "info" }));
+        assert_eq!(sessions.recipients(&info).await, vec!["a"]);
+
+        let updated = notification(
+            "notifications/resources/updated",
+            serde_json::json!({ "uri": "file:///a.txt" }),
+        );
+        assert_eq!(sessions.recipients(&updated).await, vec!["a"]);
+    }
+}
@@ -0,0 +1,279 @@
+//! Helpers for end-to-end tests that run an `McpServer` and a `Client` in one
+//! process, linked by an `InMemoryTransport` pair, and for tests that drive a
+//! transport's channels directly.
+
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{
+    client::{Client, ClientInfo},
+    error::McpError,
+    prompts::{MessageContent, PromptManager, PromptResult},
+    protocol::{JsonRpcNotification, ProtocolHandle},
+    resource::{ReadResourceResponse, ResourceManager},
+    server::McpServer,
+    tools::{ToolContent, ToolManager, ToolResult},
+    transport::{
+        InMemoryTransport, JsonRpcMessage, TransportChannels, TransportCommand, TransportEvent,
+    },
+};
+
+/// How long helpers wait for a notification or transport event before giving up
+pub const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A running server with an initialized client connected to it
+pub struct TestHarness {
+    pub client: Client,
+    pub resource_manager: Arc<ResourceManager>,
+    pub tool_manager: Arc<ToolManager>,
+    pub prompt_manager: Arc<PromptManager>,
+    // The client's message loop stops once its handle is dropped
+    _client_handle: ProtocolHandle,
+    server_task: JoinHandle<Result<(), McpError>>,
+}
+
+impl TestHarness {
+    /// Serve `server` in the background and initialize a client against it
+    pub async fn start(server: McpServer) -> Result<Self, McpError> {
+        Self::start_with_client_info(
+            server,
+            ClientInfo {
+                name: "test-client".to_string(),
+                version: "1.0.0".to_string(),
+            },
+        )
+        .await
+    }
+
+    pub async fn start_with_client_info(
+        server: McpServer,
+        client_info: ClientInfo,
+    ) -> Result<Self, McpError> {
+        Self::start_with_client(server, Client::new(), client_info).await
+    }
+
+    /// Like `start`, but with a client already configured, for example with a
+    /// sampling handler
+    pub async fn start_with_client(
+        mut server: McpServer,
+        mut client: Client,
+        client_info: ClientInfo,
+    ) -> Result<Self, McpError> {
+        let (server_transport, client_transport) = InMemoryTransport::pair();
+
+        let resource_manager = Arc::clone(&server.resource_manager);
+        let tool_manager = Arc::clone(&server.tool_manager);
+        let prompt_manager = Arc::clone(&server.prompt_manager);
+        let server_task = tokio::spawn(async move { server.run_transport(server_transport).await });
+
+        let client_handle = client.connect(client_transport).await?;
+        client.initialize(client_info).await?;
+
+        // The server handles messages in order, so once this round trip completes
+        // it has seen `initialized` and will deliver notifications to the client
+        client.list_tools(None).await?;
+
+        Ok(Self {
+            client,
+            resource_manager,
+            tool_manager,
+            prompt_manager,
+            _client_handle: client_handle,
+            server_task,
+        })
+    }
+
+    /// Record every `method` notification the client receives from now on
+    pub async fn capture_notifications(&self, method: &str) -> NotificationCapture {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.client
+            .on_notification(method, move |notification| {
+                let _ = tx.send(notification);
+            })
+            .await;
+        NotificationCapture {
+            method: method.to_string(),
+            rx,
+        }
+    }
+
+    /// Call a tool and assert it succeeds with `expected` as its text content
+    pub async fn assert_tool_text(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+        expected: &str,
+    ) -> ToolResult {
+        let result = self
+            .client
+            .call_tool(name.to_string(), arguments)
+            .await
+            .unwrap_or_else(|e| panic!("Tool {} failed: {:?}", name, e));
+        assert!(
+            !result.is_error,
+            "Tool {} reported an error: {:?}",
+            name, result
+        );
+        assert_eq!(
+            tool_text(&result),
+            expected,
+            "Unexpected output from tool {}",
+            name
+        );
+        result
+    }
+
+    /// Call a tool and assert it fails, either as a protocol error or an error result
+    pub async fn assert_tool_error(&self, name: &str, arguments: serde_json::Value) {
+        if let Ok(result) = self.client.call_tool(name.to_string(), arguments).await {
+            assert!(
+                result.is_error,
+                "Tool {} unexpectedly succeeded: {:?}",
+                name, result
+            );
+        }
+    }
+
+    /// Read a resource and assert its text content is `expected`
+    pub async fn assert_resource_text(&self, uri: &str, expected: &str) -> ReadResourceResponse {
+        let response = self
+            .client
+            .read_resource(uri.to_string())
+            .await
+            .unwrap_or_else(|e| panic!("Reading {} failed: {:?}", uri, e));
+        let text: String = response
+            .contents
+            .iter()
+            .filter_map(|content| content.text.as_deref())
+            .collect();
+        assert_eq!(text, expected, "Unexpected content for {}", uri);
+        response
+    }
+
+    /// Get a prompt and assert its messages' text is `expected`
+    pub async fn assert_prompt_text(
+        &self,
+        name: &str,
+        arguments: Option<serde_json::Value>,
+        expected: &str,
+    ) -> PromptResult {
+        let result = self
+            .client
+            .get_prompt(name.to_string(), arguments)
+            .await
+            .unwrap_or_else(|e| panic!("Prompt {} failed: {:?}", name, e));
+        let text: String = result
+            .messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContent::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, expected, "Unexpected messages from prompt {}", name);
+        result
+    }
+
+    /// Shut the client down and stop the server
+    pub async fn shutdown(mut self) -> Result<(), McpError> {
+        let result = self.client.shutdown().await;
+        self.server_task.abort();
+        result
+    }
+}
+
+impl Drop for TestHarness {
+    fn drop(&mut self) {
+        self.server_task.abort();
+    }
+}
+
+/// Concatenated text content of a tool result
+pub fn tool_text(result: &ToolResult) -> String {
+    result
+        .content
+        .iter()
+        .filter_map(|content| match content {
+            ToolContent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Notifications of one method received by a `TestHarness` client
+pub struct NotificationCapture {
+    method: String,
+    rx: mpsc::UnboundedReceiver<JsonRpcNotification>,
+}
+
+impl NotificationCapture {
+    /// The next captured notification, or `None` after `NOTIFICATION_TIMEOUT`
+    pub async fn next(&mut self) -> Option<JsonRpcNotification> {
+        tokio::time::timeout(NOTIFICATION_TIMEOUT, self.rx.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// The next captured notification, panicking if none arrives
+    pub async fn expect(&mut self) -> JsonRpcNotification {
+        self.next()
+            .await
+            .unwrap_or_else(|| panic!("Expected {} notification", self.method))
+    }
+
+    /// Assert nothing arrives within `wait`
+    pub async fn assert_none(&mut self, wait: Duration) {
+        if let Ok(Some(notification)) = tokio::time::timeout(wait, self.rx.recv()).await {
+            panic!(
+                "Unexpected {} notification: {:?}",
+                self.method, notification
+            );
+        }
+    }
+}
+
+/// A local TCP port that was free when asked for
+pub fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// A notification for `method` without params
+pub fn notification(method: &str) -> JsonRpcMessage {
+    JsonRpcMessage::Notification(JsonRpcNotification {
+        jsonrpc: "2.0".to_string(),
+        method: method.to_string(),
+        params: None,
+    })
+}
+
+/// The next event from a started transport, or `None` after `NOTIFICATION_TIMEOUT`
+pub async fn next_event(channels: &TransportChannels) -> Option<TransportEvent> {
+    let mut rx = channels.event_rx.lock().await;
+    tokio::time::timeout(NOTIFICATION_TIMEOUT, rx.recv())
+        .await
+        .ok()
+        .flatten()
+}
+
+/// The next message from a started transport, panicking on any other event
+pub async fn next_message(channels: &TransportChannels) -> JsonRpcMessage {
+    match next_event(channels).await {
+        Some(TransportEvent::Message(message)) => message,
+        other => panic!("Expected a message, got {:?}", other),
+    }
+}
+
+/// Send `message` as raw JSON-RPC, bypassing `Protocol`
+pub async fn send_raw(channels: &TransportChannels, message: serde_json::Value) {
+    let message: JsonRpcMessage = serde_json::from_value(message).unwrap();
+    channels
+        .cmd_tx
+        .send(TransportCommand::SendMessage(message))
+        .await
+        .unwrap();
+}
@@ -0,0 +1,252 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, Lines},
+    sync::Mutex,
+};
+
+use crate::{error::McpError, glob::wildcard_match, server::ClientInfo, transport::SessionId};
+
+/// A tool call waiting for approval
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: Value,
+    /// Session of the calling client on multi-session transports
+    pub session_id: Option<SessionId>,
+    /// The calling client, when the call came in over the protocol
+    pub client_info: Option<ClientInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    Approve,
+    /// Run the tool, but with these arguments instead
+    ApproveWith(Value),
+    /// Do not run the tool; the reason is reported back to the client
+    Deny(String),
+}
+
+/// Decides whether a tool call may run. Consulted before every call while
+/// `ToolSettings::require_confirmation` is set.
+#[async_trait]
+pub trait ToolApprover: Send + Sync {
+    async fn review(&self, call: &ToolCall) -> Approval;
+}
+
+/// Approves every call
+pub struct AutoApprove;
+
+#[async_trait]
+impl ToolApprover for AutoApprove {
+    async fn review(&self, _call: &ToolCall) -> Approval {
+        Approval::Approve
+    }
+}
+
+/// Denies every call. Stands in until an approver is set, so that calls
+/// needing confirmation never run unconfirmed.
+pub struct NoApprover;
+
+#[async_trait]
+impl ToolApprover for NoApprover {
+    async fn review(&self, _call: &ToolCall) -> Approval {
+        Approval::Deny("No approver configured".to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRule {
+    /// Tool names the rule applies to, with `*` wildcards
+    pub tool: String,
+    pub action: PolicyAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+// Policy File Approval
+//
+// Decides by the first rule whose pattern matches the tool name, falling back
+// to `default`. Policy files are JSON:
+//
+//   { "rules": [{ "tool": "write_*", "action": "deny", "reason": "Read only" }],
+//     "default": "allow" }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyApprover {
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+    pub default: PolicyAction,
+}
+
+impl PolicyApprover {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, McpError> {
+        let policy = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&policy)?)
+    }
+}
+
+#[async_trait]
+impl ToolApprover for PolicyApprover {
+    async fn review(&self, call: &ToolCall) -> Approval {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| wildcard_match(&rule.tool, &call.name));
+        match rule.map(|rule| (rule.action, rule.reason.as_deref())) {
+            Some((PolicyAction::Allow, _)) => Approval::Approve,
+            None if self.default == PolicyAction::Allow => Approval::Approve,
+            Some((PolicyAction::Deny, Some(reason))) => Approval::Deny(reason.to_string()),
+            _ => Approval::Deny(format!("Tool {} is not allowed by policy", call.name)),
+        }
+    }
+}
+
+type Input = Lines<Box<dyn AsyncBufRead + Unpin + Send>>;
+type Output = Box<dyn AsyncWrite + Unpin + Send>;
+
+// Interactive Approval
+//
+// Asks an operator about each call on a terminal. Only usable when stdin is
+// not the transport, e.g. with the SSE transport. Calls are asked about one
+// at a time; a closed input denies everything.
+pub struct TerminalApprover {
+    io: Mutex<(Input, Output)>,
+}
+
+impl TerminalApprover {
+    /// Ask on stderr and read answers from stdin
+    pub fn stdio() -> Self {
+        Self::new(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stderr(),
+        )
+    }
+
+    pub fn new(
+        input: impl AsyncBufRead + Unpin + Send + 'static,
+        output: impl AsyncWrite + Unpin + Send + 'static,
+    ) -> Self {
+        let input: Box<dyn AsyncBufRead + Unpin + Send> = Box::new(input);
+        Self {
+            io: Mutex::new((input.lines(), Box::new(output))),
+        }
+    }
+
+    async fn ask(input: &mut Input, output: &mut Output, question: &str) -> Option<String> {
+        output.write_all(question.as_bytes()).await.ok()?;
+        output.flush().await.ok()?;
+        input.next_line().await.ok().flatten()
+    }
+}
+
+#[async_trait]
+impl ToolApprover for TerminalApprover {
+    async fn review(&self, call: &ToolCall) -> Approval {
+        let mut io = self.io.lock().await;
+        let (input, output) = &mut *io;
+
+        let client = match &call.client_info {
+            Some(info) => format!("{} {}", info.name, info.version),
+            None => "A local caller".to_string(),
+        };
+        let session = call
+            .session_id
+            .as_ref()
+            .map(|session_id| format!(" (session {})", session_id))
+            .unwrap_or_default();
+        let question = format!(
+            "\n{}{} wants to call tool {} with arguments:\n{}\nAllow? [y]es, [n]o, [e]dit arguments: ",
+            client,
+            session,
+            call.name,
+            serde_json::to_string_pretty(&call.arguments).unwrap_or_default()
+        );
+
+        loop {
+            let Some(answer) = Self::ask(input, output, &question).await else {
+                return Approval::Deny("No operator available to approve the call".to_string());
+            };
+            match answer.trim().to_ascii_lowercase().as_str() {
+                "y" | "yes" => return Approval::Approve,
+                "n" | "no" => return Approval::Deny("Denied by the operator".to_string()),
+                "e" | "edit" => {
+                    let Some(arguments) = Self::ask(input, output, "New arguments as JSON: ").await
+                    else {
+                        return Approval::Deny("No operator available to approve the call".to_string());
+                    };
+                    match serde_json::from_str(&arguments) {
+                        Ok(arguments) => return Approval::ApproveWith(arguments),
+                        Err(e) => {
+                            let _ = output.write_all(format!("Invalid JSON: {}\n", e).as_bytes()).await;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str) -> ToolCall {
+        ToolCall {
+            name: name.to_string(),
+            arguments: json!({ "path": "notes.txt" }),
+            session_id: Some("abc".to_string()),
+            client_info: Some(ClientInfo {
+                name: "test-client".to_string(),
+                version: "1.0.0".to_string(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_policy_approver() {
+        let policy: PolicyApprover = serde_json::from_value(json!({
+            "rules": [
+                { "tool": "write_*", "action": "deny", "reason": "Read only" },
+                { "tool": "read_file", "action": "allow" }
+            ],
+            "default": "deny"
+        }))
+        .unwrap();
+
+        assert_eq!(policy.review(&call("read_file")).await, Approval::Approve);
+        assert_eq!(
+            policy.review(&call("write_file")).await,
+            Approval::Deny("Read only".to_string())
+        );
+        assert!(matches!(policy.review(&call("delete")).await, Approval::Deny(_)));
+    }
+
+    #[tokio::test]
+    async fn test_terminal_approver() {
+        async fn answer(input: &'static str) -> Approval {
+            TerminalApprover::new(input.as_bytes(), tokio::io::sink())
+                .review(&call("read_file"))
+                .await
+        }
+
+        assert_eq!(answer("y\n").await, Approval::Approve);
+        assert!(matches!(answer("n\n").await, Approval::Deny(_)));
+        // Unknown answers and invalid JSON ask again
+        assert_eq!(
+            answer("maybe\ne\nnot json\ne\n{\"path\": \"other.txt\"}\n").await,
+            Approval::ApproveWith(json!({ "path": "other.txt" }))
+        );
+        assert!(matches!(answer("").await, Approval::Deny(_)));
+    }
+}
@@ -4,10 +4,10 @@ mod directory;
 mod search;
 
 use std::sync::Arc;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use async_trait::async_trait;
 use serde_json::Value;
-use crate::{error::McpError, tools::{Tool, ToolProvider, ToolResult, ToolContent}};
+use crate::{error::McpError, server::ClientPeer, tools::{Tool, ToolProvider, ToolResult, ToolContent}};
 
 #[derive(Clone)]
 pub struct FileSystemTools {
@@ -16,6 +16,13 @@ pub struct FileSystemTools {
     directory_tool: Arc<directory::DirectoryTool>,
     search_tool: Arc<search::SearchTool>,
     allowed_directories: Arc<Vec<PathBuf>>,
+    use_client_roots: bool,
+}
+
+impl Default for FileSystemTools {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FileSystemTools {
@@ -26,6 +33,7 @@ impl FileSystemTools {
             directory_tool: Arc::new(directory::DirectoryTool::new()),
             search_tool: Arc::new(search::SearchTool::new()),
             allowed_directories: Arc::new(vec![std::env::current_dir().unwrap()]),
+            use_client_roots: false,
         }
     }
 
@@ -36,10 +44,23 @@ impl FileSystemTools {
             directory_tool: Arc::new(directory::DirectoryTool::new()),
             search_tool: Arc::new(search::SearchTool::new()),
             allowed_directories: Arc::new(allowed_dirs),
+            use_client_roots: false,
         }
     }
 
+    /// Confine each call to the `file://` roots of the client making it that lie
+    /// inside the allowed directories, when it shared any. Roots can narrow the
+    /// allowed directories but never widen them.
+    pub fn with_client_roots(mut self) -> Self {
+        self.use_client_roots = true;
+        self
+    }
+
     pub async fn validate_path(&self, requested_path: &str) -> Result<PathBuf, McpError> {
+        Self::validate_path_in(requested_path, &self.allowed_directories)
+    }
+
+    fn validate_path_in(requested_path: &str, allowed_directories: &[PathBuf]) -> Result<PathBuf, McpError> {
         let requested_path = PathBuf::from(requested_path);
         let absolute = if requested_path.is_absolute() {
             requested_path.clone()
@@ -47,41 +68,69 @@ impl FileSystemTools {
             std::env::current_dir().unwrap().join(requested_path.clone())
         };
 
-        let normalized = absolute.canonicalize()
-            .map_err(|e| {
-                tracing::error!("Path validation error for {}: {}", requested_path.display(), e);
-                McpError::IoError
-            })?;
-        
-        for allowed_dir in self.allowed_directories.iter() {
-            if (normalized.starts_with(allowed_dir)) {
+        let normalized = Self::resolve(&absolute).ok_or_else(|| {
+            tracing::error!("Path validation error for {}", requested_path.display());
+            McpError::IoError
+        })?;
+
+        for allowed_dir in allowed_directories {
+            let allowed_dir = allowed_dir.canonicalize().unwrap_or_else(|_| allowed_dir.clone());
+            if normalized.starts_with(&allowed_dir) {
                 return Ok(normalized);
             }
         }
 
-        Err(McpError::InvalidParams)
+        Err(McpError::AccessDenied(format!(
+            "{} is outside the allowed directories",
+            requested_path.display()
+        )))
     }
-}
 
-#[async_trait]
-impl ToolProvider for FileSystemTools {
-    async fn get_tool(&self) -> Tool {
-        // Return composite tool definition containing all file system operations
-        let mut tools = vec![
-            self.read_tool.get_tool().await,
-            self.write_tool.get_tool().await,
-            self.directory_tool.get_tool().await,
-            self.search_tool.get_tool().await,
-        ];
-        
-        // Return the first tool as the main tool definition
-        tools.remove(0)
+    /// Canonicalize `absolute`, which may not exist yet, such as a file about to be
+    /// written or a directory tree about to be created. The nearest existing
+    /// ancestor is canonicalized and the missing components are appended to it.
+    fn resolve(absolute: &Path) -> Option<PathBuf> {
+        let (mut resolved, existing) = absolute
+            .ancestors()
+            .find_map(|ancestor| Some((ancestor.canonicalize().ok()?, ancestor)))?;
+        // The missing part has no symlinks, so it can be normalized lexically
+        for component in absolute.strip_prefix(existing).ok()?.components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                _ => {}
+            }
+        }
+        Some(resolved)
     }
 
-    async fn execute(&self, arguments: Value) -> Result<ToolResult, McpError> {
+    /// The parts of `roots` inside the allowed directories
+    fn roots_within_allowed(&self, roots: Vec<PathBuf>) -> Vec<PathBuf> {
+        let allowed: Vec<PathBuf> = self.allowed_directories.iter()
+            .map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.clone()))
+            .collect();
+        roots.iter()
+            .filter_map(|root| Self::resolve(root))
+            .filter(|root| allowed.iter().any(|dir| root.starts_with(dir)))
+            .collect()
+    }
+
+    /// Replace a string path argument with its validated form
+    fn validate_argument(path: &mut Value, allowed_directories: &[PathBuf]) -> Result<(), McpError> {
+        if let Some(requested) = path.as_str() {
+            let validated = Self::validate_path_in(requested, allowed_directories)?;
+            *path = Value::String(validated.to_str().ok_or(McpError::InvalidParams)?.to_string());
+        }
+        Ok(())
+    }
+
+    /// Run an operation after checking every path it touches against `allowed_directories`
+    async fn execute_in(&self, arguments: Value, allowed_directories: &[PathBuf]) -> Result<ToolResult, McpError> {
         // Add operation to list allowed directories
         if arguments["operation"].as_str() == Some("list_allowed_directories") {
-            let dirs = self.allowed_directories.iter()
+            let dirs = allowed_directories.iter()
                 .map(|p| p.to_string_lossy().to_string())
                 .collect::<Vec<_>>()
                 .join("\n");
@@ -92,6 +141,19 @@ impl ToolProvider for FileSystemTools {
             });
         }
 
+        // Sub-tools get the validated paths, never the ones the client sent
+        let mut arguments = arguments;
+        for key in ["path", "source", "destination"] {
+            if let Some(path) = arguments.get_mut(key) {
+                Self::validate_argument(path, allowed_directories)?;
+            }
+        }
+        if let Some(paths) = arguments.get_mut("paths").and_then(Value::as_array_mut) {
+            for path in paths {
+                Self::validate_argument(path, allowed_directories)?;
+            }
+        }
+
         // Route to appropriate sub-tool based on operation type
         let operation = arguments["operation"].as_str().ok_or(McpError::InvalidParams)?;
         
@@ -105,6 +167,40 @@ impl ToolProvider for FileSystemTools {
     }
 }
 
+
+#[async_trait]
+impl ToolProvider for FileSystemTools {
+    async fn get_tool(&self) -> Tool {
+        // Return composite tool definition containing all file system operations
+        let mut tools = vec![
+            self.read_tool.get_tool().await,
+            self.write_tool.get_tool().await,
+            self.directory_tool.get_tool().await,
+            self.search_tool.get_tool().await,
+        ];
+        
+        // Return the first tool as the main tool definition
+        tools.remove(0)
+    }
+
+    async fn execute(&self, arguments: Value) -> Result<ToolResult, McpError> {
+        self.execute_in(arguments, &self.allowed_directories).await
+    }
+
+    async fn execute_with_client(&self, arguments: Value, client: ClientPeer) -> Result<ToolResult, McpError> {
+        let roots = if self.use_client_roots {
+            self.roots_within_allowed(client.root_directories())
+        } else {
+            Vec::new()
+        };
+        if roots.is_empty() {
+            self.execute(arguments).await
+        } else {
+            self.execute_in(arguments, &roots).await
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -228,7 +324,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_path_validation() {
-        let (fs_tools, temp_dir) = setup_test_env().await;
+        let (fs_tools, _temp_dir) = setup_test_env().await;
         let invalid_path = "/tmp/invalid/path";
 
         // Test invalid path
@@ -241,12 +337,81 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_paths_that_do_not_exist_yet() {
+        let (fs_tools, temp_dir) = setup_test_env().await;
+        let root = temp_dir.path().canonicalize().unwrap();
+
+        // Missing directories are checked through the nearest existing ancestor
+        let nested = temp_dir.path().join("a/b/c");
+        assert_eq!(
+            fs_tools.validate_path(nested.to_str().unwrap()).await.unwrap(),
+            root.join("a/b/c")
+        );
+        let result = fs_tools.execute(json!({
+            "operation": "create_directory",
+            "path": nested.to_str().unwrap(),
+        })).await.unwrap();
+        assert!(!result.is_error);
+        assert!(nested.is_dir());
+
+        // Sub-tools get the normalized path, so `missing` is never created
+        let detour = temp_dir.path().join("missing/../d");
+        fs_tools.execute(json!({
+            "operation": "create_directory",
+            "path": detour.to_str().unwrap(),
+        })).await.unwrap();
+        assert!(root.join("d").is_dir());
+        assert!(!root.join("missing").exists());
+
+        let escape = temp_dir.path().join("missing/../../escaped");
+        assert!(matches!(
+            fs_tools.validate_path(escape.to_str().unwrap()).await,
+            Err(McpError::AccessDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_paths_outside_allowed_directories() {
+        let (fs_tools, temp_dir) = setup_test_env().await;
+        let outside_dir = TempDir::new().unwrap();
+        let inside = temp_dir.path().join("inside.txt");
+        let outside = outside_dir.path().join("outside.txt");
+        std::fs::write(&inside, "inside").unwrap();
+        std::fs::write(&outside, "outside").unwrap();
+
+        // Files that don't exist yet are checked through their directory
+        assert_eq!(
+            fs_tools.validate_path(temp_dir.path().join("new.txt").to_str().unwrap()).await.unwrap(),
+            temp_dir.path().canonicalize().unwrap().join("new.txt")
+        );
+
+        let calls = [
+            json!({ "operation": "read_file", "path": outside.to_str().unwrap() }),
+            json!({ "operation": "write_file", "path": outside_dir.path().join("new.txt").to_str().unwrap(), "content": "x" }),
+            json!({ "operation": "read_multiple_files", "paths": [inside.to_str().unwrap(), outside.to_str().unwrap()] }),
+            json!({ "operation": "move_file", "source": inside.to_str().unwrap(), "destination": outside_dir.path().join("moved.txt").to_str().unwrap() }),
+            json!({ "operation": "write_file", "path": temp_dir.path().join("../escaped.txt").to_str().unwrap(), "content": "x" }),
+        ];
+        for call in calls {
+            match fs_tools.execute(call.clone()).await {
+                Err(McpError::AccessDenied(_)) => (),
+                other => panic!("Expected AccessDenied for {}, got {:?}", call, other.map(|r| r.is_error)),
+            }
+        }
+
+        // Nothing was touched
+        assert!(inside.exists());
+        assert!(!outside_dir.path().join("new.txt").exists());
+        assert!(!outside_dir.path().join("moved.txt").exists());
+    }
+
     #[tokio::test]
     async fn test_multiple_file_operations() {
         let (fs_tools, temp_dir) = setup_test_env().await;
         
         // Create test files
-        let files = vec!["multi1.txt", "multi2.txt"];
+        let files = ["multi1.txt", "multi2.txt"];
         for (i, file) in files.iter().enumerate() {
             let path = temp_dir.path().join(file);
             fs_tools.execute(json!({
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use async_trait::async_trait;
-use futures::future::{try_join_all, Future};
+use futures::future::try_join_all;
 use serde_json::{json, Value};
 use tokio::fs;
 
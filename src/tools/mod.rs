@@ -2,14 +2,21 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use test_tool::{PingTool, TestTool};
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
 use tokio::sync::RwLock;
 
+pub mod approval;
 pub mod calculator;
 pub mod file_system;
 pub mod test_tool;
 
-use crate::error::McpError;
+use crate::{
+    error::McpError,
+    glob::wildcard_match,
+    server::{config::ToolSettings, ClientPeer},
+};
+pub use crate::resource::ResourceContent;
+pub use approval::{Approval, AutoApprove, NoApprover, ToolApprover, ToolCall};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -33,6 +40,7 @@ impl ToolType {
 
 // Tool Types
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Tool {
     pub name: String,
     pub description: String,
@@ -43,7 +51,9 @@ pub struct Tool {
 pub struct ToolInputSchema {
     #[serde(rename = "type")]
     pub schema_type: String,
+    #[serde(default)]
     pub properties: HashMap<String, Value>,
+    #[serde(default)]
     pub required: Vec<String>,
 }
 
@@ -53,7 +63,11 @@ pub enum ToolContent {
     #[serde(rename = "text")]
     Text { text: String },
     #[serde(rename = "image")]
-    Image { data: String, mime_type: String },
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
     #[serde(rename = "resource")]
     Resource {
         resource: ResourceContent,
@@ -61,18 +75,25 @@ pub enum ToolContent {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ResourceContent {
-    pub uri: String,
-    pub mime_type: Option<String>,
-    pub text: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ToolResult {
     pub content: Vec<ToolContent>,
+    #[serde(default)]
     pub is_error: bool,
 }
 
+impl ToolResult {
+    /// A failed call, reported to the client's model rather than as a protocol error
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            content: vec![ToolContent::Text {
+                text: message.into(),
+            }],
+            is_error: true,
+        }
+    }
+}
+
 // Request/Response types
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ListToolsRequest {
@@ -80,14 +101,17 @@ pub struct ListToolsRequest {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListToolsResponse {
     pub tools: Vec<Tool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CallToolRequest {
     pub name: String,
+    #[serde(default)]
     pub arguments: Value,
 }
 
@@ -99,10 +123,21 @@ pub trait ToolProvider: Send + Sync {
     
     /// Execute tool
     async fn execute(&self, arguments: Value) -> Result<ToolResult, McpError>;
+
+    /// Execute tool for `client`, which it may call back into, for example to
+    /// sample from the client's model. Defaults to `execute`.
+    async fn execute_with_client(
+        &self,
+        arguments: Value,
+        _client: ClientPeer,
+    ) -> Result<ToolResult, McpError> {
+        self.execute(arguments).await
+    }
 }
 
 // Tool Manager
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct ToolCapabilities {
     pub list_changed: bool,
 }
@@ -110,6 +145,8 @@ pub struct ToolCapabilities {
 pub struct ToolManager {
     pub tools: Arc<RwLock<HashMap<String, Arc<dyn ToolProvider>>>>,
     pub capabilities: ToolCapabilities,
+    pub settings: ToolSettings,
+    approver: RwLock<Arc<dyn ToolApprover>>,
 }
 
 impl ToolManager {
@@ -117,9 +154,33 @@ impl ToolManager {
         Self {
             tools: Arc::new(RwLock::new(HashMap::new())),
             capabilities,
+            settings: ToolSettings::default(),
+            approver: RwLock::new(Arc::new(NoApprover)),
         }
     }
 
+    /// Ask `approver` before each call while `require_confirmation` is set.
+    /// Until one is set, such calls are denied.
+    pub async fn set_approver(&self, approver: Arc<dyn ToolApprover>) {
+        *self.approver.write().await = approver;
+    }
+
+    /// Only offer the tools `settings` allow, and stop calls that run too long
+    pub fn with_settings(mut self, settings: ToolSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Whether clients may see and call the tool called `name`
+    pub fn is_allowed(&self, name: &str) -> bool {
+        self.settings.enabled
+            && self
+                .settings
+                .allowed_tools
+                .iter()
+                .any(|pattern| wildcard_match(pattern, name))
+    }
+
     pub async fn register_tool(&self, provider: Arc<dyn ToolProvider>) {
         let tool = provider.get_tool().await;
         let mut tools = self.tools.write().await;
@@ -130,8 +191,10 @@ impl ToolManager {
         let tools = self.tools.read().await;
         let mut tool_list = Vec::new();
         
-        for provider in tools.values() {
-            tool_list.push(provider.get_tool().await);
+        for (name, provider) in tools.iter() {
+            if self.is_allowed(name) {
+                tool_list.push(provider.get_tool().await);
+            }
         }
 
         Ok(ListToolsResponse {
@@ -141,10 +204,104 @@ impl ToolManager {
     }
 
     pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<ToolResult, McpError> {
-        let tools = self.tools.read().await;
-        let provider = tools.get(name)
-            .ok_or_else(|| McpError::InvalidRequest(format!("Unknown tool: {}", name)))?;
-            
-        provider.execute(arguments).await
+        let provider = self.provider(name).await?;
+        let call = ToolCall {
+            name: name.to_string(),
+            arguments,
+            session_id: None,
+            client_info: None,
+        };
+        let arguments = match self.approve(call).await {
+            Ok(arguments) => arguments,
+            Err(denied) => return Ok(denied),
+        };
+        self.run(name, provider.execute(arguments)).await
+    }
+
+    /// Call a tool on behalf of the client that requested it
+    pub async fn call_tool_with_client(
+        &self,
+        name: &str,
+        arguments: Value,
+        client: ClientPeer,
+    ) -> Result<ToolResult, McpError> {
+        let provider = self.provider(name).await?;
+        let call = ToolCall {
+            name: name.to_string(),
+            arguments,
+            session_id: client.session_id().cloned(),
+            client_info: client.client_info().cloned(),
+        };
+        let arguments = match self.approve(call).await {
+            Ok(arguments) => arguments,
+            Err(denied) => return Ok(denied),
+        };
+        self.run(name, provider.execute_with_client(arguments, client)).await
+    }
+
+    /// The arguments to run `call` with, or the error result of a denied call
+    async fn approve(&self, call: ToolCall) -> Result<Value, ToolResult> {
+        if !self.settings.require_confirmation {
+            return Ok(call.arguments);
+        }
+        let approver = Arc::clone(&*self.approver.read().await);
+        match approver.review(&call).await {
+            Approval::Approve => Ok(call.arguments),
+            Approval::ApproveWith(arguments) => {
+                tracing::info!("Tool {} approved with edited arguments", call.name);
+                Ok(arguments)
+            }
+            Approval::Deny(reason) => {
+                tracing::info!("Tool {} denied: {}", call.name, reason);
+                Err(ToolResult::error(format!("Tool call denied: {}", reason)))
+            }
+        }
+    }
+
+    async fn provider(&self, name: &str) -> Result<Arc<dyn ToolProvider>, McpError> {
+        if !self.settings.enabled {
+            return Err(McpError::CapabilityNotSupported("tools".to_string()));
+        }
+        if !self.is_allowed(name) {
+            return Err(McpError::AccessDenied(format!("Tool {} is not allowed", name)));
+        }
+        self.tools
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| McpError::InvalidRequest(format!("Unknown tool: {}", name)))
+    }
+
+    /// Wait for a tool call within the time limit. Bad arguments and failures of
+    /// the tool itself, including running too long, become error results the
+    /// model can react to; other errors, such as denied access, stay errors.
+    async fn run(
+        &self,
+        name: &str,
+        execution: impl Future<Output = Result<ToolResult, McpError>>,
+    ) -> Result<ToolResult, McpError> {
+        let limit = self.settings.max_execution_time_ms;
+        let result = if limit == 0 {
+            execution.await
+        } else {
+            match tokio::time::timeout(Duration::from_millis(limit), execution).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!("Tool {} timed out after {}ms", name, limit);
+                    return Ok(ToolResult::error(format!(
+                        "Tool {} timed out after {}ms",
+                        name, limit
+                    )));
+                }
+            }
+        };
+        match result {
+            Err(e @ (McpError::InvalidParams | McpError::ToolExecutionError(_))) => {
+                tracing::debug!("Tool {} failed: {}", name, e);
+                Ok(ToolResult::error(e.to_string()))
+            }
+            result => result,
+        }
     }
 }
@@ -1,11 +1,17 @@
 use async_trait::async_trait;
 
-use crate::{error::McpError, server};
+use crate::error::McpError;
 
 use super::{Tool, ToolContent, ToolProvider, ToolResult};
 
 pub struct TestTool;
 
+impl Default for TestTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TestTool {
     pub fn new() -> Self {
         TestTool
@@ -31,7 +37,7 @@ impl ToolProvider for TestTool {
         }
     }
 
-    async fn execute(&self, arguments: serde_json::Value) -> Result<ToolResult, McpError> {
+    async fn execute(&self, _arguments: serde_json::Value) -> Result<ToolResult, McpError> {
         Ok(ToolResult {
             content: vec![],
             is_error: false,
@@ -42,6 +48,12 @@ impl ToolProvider for TestTool {
 
 pub struct PingTool;
 
+impl Default for PingTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PingTool {
     pub fn new() -> Self {
         PingTool
@@ -68,7 +80,7 @@ impl ToolProvider for PingTool {
     }
 
     async fn execute(&self, arguments: serde_json::Value) -> Result<ToolResult, McpError> {
-        let server = arguments.get("server").map(|s| s.as_str()).flatten().unwrap_or("localhost");
+        let server = arguments.get("server").and_then(|s| s.as_str()).unwrap_or("localhost");
         let res = reqwest::get(format!("http://{}", server)).await.map_err(|e| McpError::ToolExecutionError(e.to_string()))?;
         let body = res.text().await.map_err(|e| McpError::ToolExecutionError(e.to_string()))?;
         Ok(ToolResult {
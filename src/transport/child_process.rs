@@ -0,0 +1,228 @@
+use async_trait::async_trait;
+use std::{collections::HashMap, path::PathBuf, process::Stdio, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, ChildStderr, Command},
+    sync::mpsc,
+};
+
+use super::{
+    stream::{spawn_line_reader, spawn_line_writer, untagged},
+    Transport, TransportChannels, TransportCommand, TransportEvent,
+};
+use crate::error::McpError;
+
+/// How long the server gets to exit after its stdin is closed before it is killed.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Child Process Transport Implementation
+//
+// Launches the server as a child process and exchanges newline-delimited JSON
+// over its stdin/stdout, the way MCP hosts run local servers. The child's
+// stderr is forwarded to tracing.
+pub struct ChildProcessTransport {
+    command: String,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    current_dir: Option<PathBuf>,
+    buffer_size: usize,
+}
+
+impl ChildProcessTransport {
+    pub fn new(command: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            command: command.into(),
+            args,
+            env: HashMap::new(),
+            current_dir: None,
+            buffer_size: 4092,
+        }
+    }
+
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    async fn run(
+        mut child: Child,
+        command: String,
+        mut cmd_rx: mpsc::Receiver<TransportCommand>,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) {
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            tracing::error!("Server process {} has no stdio pipes", command);
+            let _ = child.kill().await;
+            let _ = event_tx.send(TransportEvent::Closed).await;
+            return;
+        };
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(Self::forward_stderr(command.clone(), stderr));
+        }
+
+        let (write_tx, writer_handle) = spawn_line_writer(stdin);
+        let reader_handle = spawn_line_reader(stdout, usize::MAX, event_tx.clone(), untagged);
+
+        // Message sending loop, watching for the server to exit on its own
+        let exited = loop {
+            tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(TransportCommand::SendMessage(msg) | TransportCommand::SendTo(_, msg)) => {
+                        match serde_json::to_string(&msg) {
+                            Ok(s) => {
+                                if write_tx.send(s).await.is_err() {
+                                    break None;
+                                }
+                            }
+                            Err(e) => tracing::error!("Failed to serialize message: {:?}", e),
+                        }
+                    }
+                    Some(TransportCommand::Close) | None => break None,
+                },
+                status = child.wait() => break Some(status),
+            }
+        };
+
+        match exited {
+            Some(status) => {
+                let status = match status {
+                    Ok(status) => status.to_string(),
+                    Err(e) => e.to_string(),
+                };
+                tracing::warn!("Server process {} exited: {}", command, status);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::InternalError(format!(
+                        "Server process exited: {}",
+                        status
+                    ))))
+                    .await;
+            }
+            None => {
+                // Closing stdin asks the server to shut down
+                drop(write_tx);
+                match tokio::time::timeout(CLOSE_TIMEOUT, child.wait()).await {
+                    Ok(Ok(status)) => tracing::debug!("Server process {} exited: {}", command, status),
+                    Ok(Err(e)) => tracing::error!("Failed to wait for server process: {:?}", e),
+                    Err(_) => {
+                        tracing::warn!("Server process {} did not exit, killing it", command);
+                        let _ = child.kill().await;
+                    }
+                }
+            }
+        }
+
+        // Deliver anything the server wrote before exiting
+        if tokio::time::timeout(CLOSE_TIMEOUT, reader_handle).await.is_err() {
+            tracing::warn!("Server process {} left its stdout open", command);
+        }
+        writer_handle.abort();
+        let _ = event_tx.send(TransportEvent::Closed).await;
+    }
+
+    async fn forward_stderr(command: String, stderr: ChildStderr) {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            tracing::info!("[{}] {}", command, line);
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for ChildProcessTransport {
+    async fn start(&mut self) -> Result<TransportChannels, McpError> {
+        let mut command = Command::new(&self.command);
+        command
+            .args(&self.args)
+            .envs(&self.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+
+        let child = command.spawn().map_err(|e| {
+            McpError::InternalError(format!("Failed to start {}: {}", self.command, e))
+        })?;
+
+        let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
+        let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
+
+        // Spawn the transport actor
+        tokio::spawn(Self::run(child, self.command.clone(), cmd_rx, event_tx));
+
+        let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
+        Ok(TransportChannels { cmd_tx, event_rx })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{protocol::JsonRpcNotification, testing::next_event, transport::JsonRpcMessage};
+
+    #[tokio::test]
+    async fn test_child_process_round_trip() -> Result<(), McpError> {
+        // `cat` echoes every line back
+        let channels = ChildProcessTransport::new("cat", vec![]).start().await?;
+
+        channels
+            .cmd_tx
+            .send(TransportCommand::SendMessage(JsonRpcMessage::Notification(
+                JsonRpcNotification {
+                    jsonrpc: "2.0".to_string(),
+                    method: "echo".to_string(),
+                    params: None,
+                },
+            )))
+            .await
+            .unwrap();
+        match next_event(&channels).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "echo")
+            }
+            other => panic!("Expected echoed notification, got {:?}", other),
+        }
+
+        channels.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        assert!(matches!(next_event(&channels).await, Some(TransportEvent::Closed)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_child_process_unexpected_exit() -> Result<(), McpError> {
+        let channels = ChildProcessTransport::new("sh", vec!["-c".to_string(), "exit 3".to_string()])
+            .start()
+            .await?;
+
+        match next_event(&channels).await {
+            Some(TransportEvent::Error(McpError::InternalError(msg))) => {
+                assert!(msg.contains("exit status: 3"), "{}", msg)
+            }
+            other => panic!("Expected exit error, got {:?}", other),
+        }
+        assert!(matches!(next_event(&channels).await, Some(TransportEvent::Closed)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_child_process_missing_command() {
+        assert!(ChildProcessTransport::new("does-not-exist-mcp", vec![])
+            .start()
+            .await
+            .is_err());
+    }
+}
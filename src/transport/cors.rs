@@ -0,0 +1,232 @@
+use std::net::{IpAddr, Ipv4Addr};
+use warp::{
+    http::{header, HeaderValue, StatusCode},
+    reject::Reject,
+    reply::Response,
+    Filter, Rejection, Reply,
+};
+
+use super::SESSION_HEADER;
+use crate::glob::wildcard_match;
+
+/// How long browsers may cache a preflight answer, in seconds
+const PREFLIGHT_MAX_AGE: &str = "600";
+
+/// Origins allowed unless configured otherwise: pages served from this machine
+pub const LOOPBACK_ORIGINS: &[&str] = &["http://localhost:*", "http://127.0.0.1:*"];
+
+// Origin Allow-List
+//
+// Browsers send an `Origin` header with cross-site requests. Requests with an
+// Origin that is not on the list are refused before they reach the routes,
+// which keeps pages on other sites, including ones that rebind their DNS to
+// this host, from talking to the server. Requests without an Origin come from
+// non-browser clients and are let through. By default only loopback origins
+// are on the list.
+//
+// Entries are either `*`, an exact origin such as `https://app.example.com`,
+// or a pattern with a `*` wildcard, such as `https://*.example.com` or
+// `http://localhost:*`. `*` accepts requests from any page but never lets a
+// page read the responses, so pages can still send requests blindly; only use
+// it together with auth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedOrigins {
+    patterns: Vec<String>,
+}
+
+impl Default for AllowedOrigins {
+    fn default() -> Self {
+        Self::loopback()
+    }
+}
+
+impl AllowedOrigins {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|pattern| pattern.trim_end_matches('/').to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Allow pages served from this machine
+    pub fn loopback() -> Self {
+        Self::new(LOOPBACK_ORIGINS.iter().map(|origin| origin.to_string()).collect())
+    }
+
+    /// Allow every origin
+    pub fn any() -> Self {
+        Self::new(vec!["*".to_string()])
+    }
+
+    /// Whether every origin is allowed
+    pub fn is_unrestricted(&self) -> bool {
+        self.patterns.iter().any(|pattern| pattern == "*")
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| wildcard_match(pattern, &origin))
+    }
+
+    /// The address to bind instead of `ip`. Any page may talk to a server with
+    /// unrestricted origins, so such servers only listen on loopback.
+    pub fn bind_ip(&self, ip: IpAddr) -> IpAddr {
+        if self.is_unrestricted() && !ip.is_loopback() {
+            tracing::warn!(
+                "Origins are unrestricted, binding to loopback instead of {}; \
+                 set allowed_origins to listen on other interfaces",
+                ip
+            );
+            return IpAddr::V4(Ipv4Addr::LOCALHOST);
+        }
+        ip
+    }
+}
+
+#[derive(Debug)]
+struct OriginRejected(String);
+
+impl Reject for OriginRejected {}
+
+/// The request's `Origin` if pages from it may read responses, rejecting
+/// origins `origins` does not allow
+fn check_origin(
+    origins: AllowedOrigins,
+) -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>(header::ORIGIN.as_str()).and_then(
+        move |origin: Option<String>| {
+            let origins = origins.clone();
+            async move {
+                match origin {
+                    Some(origin) if !origins.allows(&origin) => {
+                        tracing::warn!("Rejected request from origin {}", origin);
+                        Err(warp::reject::custom(OriginRejected(origin)))
+                    }
+                    // Unrestricted origins are accepted, but never echoed back
+                    origin => Ok(origin.filter(|_| !origins.is_unrestricted())),
+                }
+            }
+        },
+    )
+}
+
+/// Let the allowed `origin` read the response
+fn allow_origin(mut response: Response, origin: Option<String>) -> Response {
+    let headers = response.headers_mut();
+    headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    if let Some(value) = origin.and_then(|origin| HeaderValue::from_str(&origin).ok()) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+        headers.insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            HeaderValue::from_static(SESSION_HEADER),
+        );
+    }
+    response
+}
+
+fn preflight_response(origin: Option<String>) -> Response {
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static("GET, POST, DELETE, OPTIONS"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static(
+            "Accept, Authorization, Content-Type, Last-Event-ID, Mcp-Session-Id",
+        ),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_MAX_AGE,
+        HeaderValue::from_static(PREFLIGHT_MAX_AGE),
+    );
+    allow_origin(response, origin)
+}
+
+/// Answer preflights and add CORS headers to `routes`, refusing requests from
+/// origins `origins` does not allow with 403 before `routes` see them
+pub(crate) fn with_cors<F, R>(
+    origins: AllowedOrigins,
+    routes: F,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    // Preflights never carry credentials, so they are answered ahead of auth
+    let preflight = warp::options()
+        .and(check_origin(origins.clone()))
+        .map(preflight_response);
+
+    let guarded = check_origin(origins)
+        .and(routes)
+        .map(|origin, reply: R| allow_origin(reply.into_response(), origin));
+
+    preflight.or(guarded).unify().recover(recover)
+}
+
+async fn recover(rejection: Rejection) -> Result<Response, Rejection> {
+    match rejection.find::<OriginRejected>() {
+        Some(OriginRejected(origin)) => Ok(warp::reply::with_status(
+            format!("Origin {} is not allowed", origin),
+            StatusCode::FORBIDDEN,
+        )
+        .into_response()),
+        None => Err(rejection),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_origins() {
+        let origins = AllowedOrigins::new(vec![
+            "https://app.example.com/".to_string(),
+            "https://*.example.org".to_string(),
+            "http://localhost:*".to_string(),
+        ]);
+        assert!(!origins.is_unrestricted());
+
+        assert!(origins.allows("https://app.example.com"));
+        assert!(origins.allows("HTTPS://App.Example.com"));
+        assert!(!origins.allows("https://app.example.com.evil.com"));
+        assert!(!origins.allows("http://app.example.com"));
+
+        assert!(origins.allows("https://docs.example.org"));
+        assert!(!origins.allows("https://example.org.evil.com"));
+        assert!(!origins.allows("https://evil.com"));
+
+        assert!(origins.allows("http://localhost:5173"));
+        assert!(!origins.allows("http://localhost.evil.com"));
+
+        assert!(AllowedOrigins::any().allows("https://anything.test"));
+        assert!(!AllowedOrigins::new(vec![]).allows("https://anything.test"));
+
+        let default = AllowedOrigins::default();
+        assert!(!default.is_unrestricted());
+        assert!(default.allows("http://localhost:5173"));
+        assert!(default.allows("http://127.0.0.1:8080"));
+        assert!(!default.allows("https://anything.test"));
+        assert!(!default.allows("http://localhost.evil.com"));
+    }
+
+    #[test]
+    fn test_bind_ip() {
+        let public: IpAddr = "0.0.0.0".parse().unwrap();
+        let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+
+        assert_eq!(AllowedOrigins::any().bind_ip(public), loopback);
+        assert_eq!(AllowedOrigins::any().bind_ip(loopback), loopback);
+
+        let restricted = AllowedOrigins::new(vec!["https://app.example.com".to_string()]);
+        assert_eq!(restricted.bind_ip(public), public);
+        assert_eq!(AllowedOrigins::default().bind_ip(public), public);
+    }
+}
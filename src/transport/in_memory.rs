@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::{JsonRpcMessage, Transport, TransportChannels, TransportCommand, TransportEvent};
+use crate::error::McpError;
+
+// In-Memory Transport Implementation
+//
+// Two linked transports that hand messages to each other over channels, for
+// running a client and a server in the same process without any I/O.
+// Messages sent before the peer starts are buffered until it does.
+pub struct InMemoryTransport {
+    peer_tx: Option<mpsc::Sender<JsonRpcMessage>>,
+    rx: Option<mpsc::Receiver<JsonRpcMessage>>,
+    buffer_size: usize,
+}
+
+impl InMemoryTransport {
+    /// Create two transports where everything one sends is received by the other
+    pub fn pair() -> (Self, Self) {
+        Self::pair_with_buffer_size(4092)
+    }
+
+    pub fn pair_with_buffer_size(buffer_size: usize) -> (Self, Self) {
+        let (a_tx, a_rx) = mpsc::channel(buffer_size);
+        let (b_tx, b_rx) = mpsc::channel(buffer_size);
+        (
+            Self {
+                peer_tx: Some(b_tx),
+                rx: Some(a_rx),
+                buffer_size,
+            },
+            Self {
+                peer_tx: Some(a_tx),
+                rx: Some(b_rx),
+                buffer_size,
+            },
+        )
+    }
+
+    async fn run(
+        peer_tx: mpsc::Sender<JsonRpcMessage>,
+        mut rx: mpsc::Receiver<JsonRpcMessage>,
+        mut cmd_rx: mpsc::Receiver<TransportCommand>,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) {
+        loop {
+            tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(TransportCommand::SendMessage(msg) | TransportCommand::SendTo(_, msg)) => {
+                        if peer_tx.send(msg).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(TransportCommand::Close) | None => break,
+                },
+                msg = rx.recv() => match msg {
+                    Some(msg) => {
+                        if event_tx.send(TransportEvent::Message(msg)).await.is_err() {
+                            break;
+                        }
+                    }
+                    // The peer closed its end
+                    None => break,
+                },
+            }
+        }
+
+        // Dropping our sender tells the peer we are gone
+        drop(peer_tx);
+        let _ = event_tx.send(TransportEvent::Closed).await;
+    }
+}
+
+#[async_trait]
+impl Transport for InMemoryTransport {
+    async fn start(&mut self) -> Result<TransportChannels, McpError> {
+        let (Some(peer_tx), Some(rx)) = (self.peer_tx.take(), self.rx.take()) else {
+            return Err(McpError::InternalError(
+                "In-memory transport already started".to_string(),
+            ));
+        };
+
+        let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
+        let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
+
+        // Spawn the transport actor
+        tokio::spawn(Self::run(peer_tx, rx, cmd_rx, event_tx));
+
+        let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
+        Ok(TransportChannels { cmd_tx, event_rx })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{next_event, notification};
+
+    #[tokio::test]
+    async fn test_in_memory_pair() -> Result<(), McpError> {
+        let (mut a, mut b) = InMemoryTransport::pair();
+        let a = a.start().await?;
+
+        // Buffered until the peer starts
+        a.cmd_tx
+            .send(TransportCommand::SendMessage(notification("a/hello")))
+            .await
+            .unwrap();
+        let b = b.start().await?;
+        match next_event(&b).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "a/hello")
+            }
+            other => panic!("Expected notification, got {:?}", other),
+        }
+
+        b.cmd_tx
+            .send(TransportCommand::SendMessage(notification("b/hello")))
+            .await
+            .unwrap();
+        match next_event(&a).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "b/hello")
+            }
+            other => panic!("Expected notification, got {:?}", other),
+        }
+
+        // Closing one side closes the other
+        a.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        assert!(matches!(next_event(&a).await, Some(TransportEvent::Closed)));
+        assert!(matches!(next_event(&b).await, Some(TransportEvent::Closed)));
+
+        Ok(())
+    }
+}
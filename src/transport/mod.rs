@@ -1,38 +1,64 @@
 use async_trait::async_trait;
 use futures::StreamExt;
-use jsonrpc_core::request;
-use reqwest::RequestBuilder;
 use reqwest_eventsource::{Event, EventSource};
 use serde::{Deserialize, Serialize};
-use std::{
-    net::IpAddr,
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc,
-    },
-    time::Duration,
-};
-use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt},
-    sync::{broadcast, mpsc},
-};
+use std::{net::IpAddr, sync::Arc, time::Duration};
+use tokio::sync::mpsc;
 use warp::Filter;
 
 use crate::{
+    auth::{self, BearerAuth},
     error::McpError,
     protocol::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse},
 };
 
+mod child_process;
+mod cors;
+mod in_memory;
+mod session;
+mod stream;
+mod streamable_http;
+mod tcp;
+#[cfg(unix)]
+mod unix;
+mod websocket;
+
+pub use child_process::ChildProcessTransport;
+pub use cors::{AllowedOrigins, LOOPBACK_ORIGINS};
+use cors::with_cors;
+pub use in_memory::InMemoryTransport;
+pub use stream::StreamTransport;
+pub use streamable_http::{StreamableHttpTransport, SESSION_HEADER};
+pub use tcp::TcpTransport;
+#[cfg(unix)]
+pub use unix::UnixSocketTransport;
+pub use websocket::WebSocketTransport;
+
+use session::{SessionGuard, SessionRegistry};
+
+/// Identifies one client connection on a multi-session server transport
+pub type SessionId = String;
+
 // Message types for the transport actor
 #[derive(Debug)]
 pub enum TransportCommand {
+    /// Send to the peer. Multi-session transports broadcast this to every session.
     SendMessage(JsonRpcMessage),
+    /// Send to a single session. Single-peer transports treat this like `SendMessage`.
+    SendTo(SessionId, JsonRpcMessage),
     Close,
 }
 
 #[derive(Debug)]
 pub enum TransportEvent {
     Message(JsonRpcMessage),
+    /// A message received from one session of a multi-session transport
+    SessionMessage(SessionId, JsonRpcMessage),
+    /// A session disconnected from a multi-session transport
+    SessionClosed(SessionId),
+    /// Input that was not a valid JSON-RPC message, with the session it came
+    /// from on multi-session transports. The protocol answers it with an error.
+    Malformed(Option<SessionId>, McpError),
     Error(McpError),
     Closed,
 }
@@ -42,8 +68,30 @@ pub enum TransportEvent {
 #[serde(untagged)]
 pub enum JsonRpcMessage {
     Request(JsonRpcRequest),
-    Response(JsonRpcResponse),
+    // Tried before `Response`, whose id, result and error are all optional
     Notification(JsonRpcNotification),
+    Response(JsonRpcResponse),
+    /// Several messages sent together as a JSON array
+    Batch(Vec<JsonRpcMessage>),
+}
+
+/// Parse one JSON-RPC message, telling invalid JSON (`ParseError`) apart from
+/// valid JSON that is not a message (`InvalidRequest`)
+pub(crate) fn parse_message(text: &str) -> Result<JsonRpcMessage, McpError> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| {
+        tracing::error!("Parse error: {}, input: {}", e, text);
+        McpError::ParseError
+    })?;
+    serde_json::from_value(value).map_err(|e| {
+        tracing::error!("Invalid message: {}, input: {}", e, text);
+        McpError::InvalidRequest("Not a JSON-RPC message".to_string())
+    })
+}
+
+/// Parse an HTTP body as one JSON-RPC message, treating invalid UTF-8 as invalid JSON
+pub(crate) fn parse_body(body: &[u8]) -> Result<JsonRpcMessage, McpError> {
+    let text = std::str::from_utf8(body).map_err(|_| McpError::ParseError)?;
+    parse_message(text)
 }
 
 // Transport trait
@@ -71,103 +119,6 @@ impl StdioTransport {
     pub fn new(buffer_size: Option<usize>) -> Self {
         Self { buffer_size: buffer_size.unwrap_or(4092) }
     }
-
-    async fn run(
-        reader: tokio::io::BufReader<tokio::io::Stdin>,
-        writer: tokio::io::Stdout,
-        mut cmd_rx: mpsc::Receiver<TransportCommand>,
-        event_tx: mpsc::Sender<TransportEvent>,
-    ) {
-        let (write_tx, mut write_rx) = mpsc::channel::<String>(32);
-
-        // Writer task
-        let writer_handle = {
-            let mut writer = writer;
-            tokio::spawn(async move {
-                while let Some(msg) = write_rx.recv().await {
-                    // Skip logging for certain types of messages
-                    if !msg.contains("notifications/message") && !msg.contains("list_changed") {
-                        tracing::debug!("-> {}", msg);
-                    }
-
-                    if let Err(e) = async {
-                        writer.write_all(msg.as_bytes()).await?;
-                        writer.write_all(b"\n").await?;
-                        writer.flush().await?;
-                        Ok::<_, std::io::Error>(())
-                    }.await {
-                        tracing::error!("Write error: {:?}", e);
-                        break;
-                    }
-                }
-            })
-        };
-
-        // Reader task
-        let reader_handle = tokio::spawn({
-            let mut reader = reader;
-            let event_tx = event_tx.clone();
-            async move {
-                let mut line = String::new();
-                loop {
-                    line.clear();
-                    match reader.read_line(&mut line).await {
-                        Ok(0) => break, // EOF
-                        Ok(_) => {
-                            let trimmed = line.trim();
-                            if (!trimmed.contains("notifications/message") && !trimmed.contains("list_changed")) {
-                                tracing::debug!("<- {}", trimmed);
-                            }
-
-                            if !trimmed.is_empty() {
-                                match serde_json::from_str::<JsonRpcMessage>(trimmed) {
-                                    Ok(msg) => {
-                                        if event_tx.send(TransportEvent::Message(msg)).await.is_err() {
-                                            break;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::error!("Parse error: {}, input: {}", e, trimmed);
-                                        if event_tx.send(TransportEvent::Error(McpError::ParseError)).await.is_err() {
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            tracing::error!("Read error: {:?}", e);
-                            let _ = event_tx.send(TransportEvent::Error(McpError::IoError)).await;
-                            break;
-                        }
-                    }
-                }
-            }
-        });
-
-        // Main message loop
-        while let Some(cmd) = cmd_rx.recv().await {
-            match cmd {
-                TransportCommand::SendMessage(msg) => {
-                    match serde_json::to_string(&msg) {
-                        Ok(s) => {
-                            if write_tx.send(s).await.is_err() {
-                                break;
-                            }
-                        }
-                        Err(e) => tracing::error!("Failed to serialize message: {:?}", e),
-                    }
-                }
-                TransportCommand::Close => break,
-            }
-        }
-
-        // Cleanup
-        drop(write_tx);
-        let _ = reader_handle.await;
-        let _ = writer_handle.await;
-        let _ = event_tx.send(TransportEvent::Closed).await;
-    }
 }
 
 #[async_trait]
@@ -176,13 +127,13 @@ impl Transport for StdioTransport {
         let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
         let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
 
-        // Set up buffered stdin/stdout
-        let stdin = tokio::io::stdin();
-        let stdout = tokio::io::stdout();
-        let reader = tokio::io::BufReader::with_capacity(4096, stdin);
-
         // Spawn the transport actor
-        tokio::spawn(Self::run(reader, stdout, cmd_rx, event_tx));
+        tokio::spawn(stream::run(
+            tokio::io::stdin(),
+            tokio::io::stdout(),
+            cmd_rx,
+            event_tx,
+        ));
 
         let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
         Ok(TransportChannels { cmd_tx, event_rx })
@@ -200,6 +151,9 @@ pub struct SseTransport {
     port: u16,
     client_mode: bool,
     buffer_size: usize,
+    auth: Option<BearerAuth>,
+    origins: AllowedOrigins,
+    bearer_token: Option<String>,
 }
 
 impl SseTransport {
@@ -209,6 +163,9 @@ impl SseTransport {
             port,
             client_mode: false,
             buffer_size,
+            auth: None,
+            origins: AllowedOrigins::default(),
+            bearer_token: None,
         }
     }
 
@@ -218,128 +175,217 @@ impl SseTransport {
             port,
             client_mode: true,
             buffer_size,
+            auth: None,
+            origins: AllowedOrigins::default(),
+            bearer_token: None,
         }
     }
 
+    /// Require clients to present a bearer token `auth` accepts. Others get 401 or 403.
+    pub fn with_auth(mut self, auth: BearerAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Only accept browser requests from `origins`. By default only loopback
+    /// origins are accepted; with `AllowedOrigins::any()` the server only
+    /// listens on loopback.
+    pub fn with_allowed_origins(mut self, origins: AllowedOrigins) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    /// Send `token` in an `Authorization: Bearer` header when connecting as a client
+    pub fn with_bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
     async fn run_server(
         host: String,
         port: u16,
+        buffer_size: usize,
+        auth: Option<BearerAuth>,
+        origins: AllowedOrigins,
         mut cmd_rx: mpsc::Receiver<TransportCommand>,
         event_tx: mpsc::Sender<TransportEvent>,
     ) {
-        // Create broadcast channel for SSE clients
-        let (broadcast_tx, _) = tokio::sync::broadcast::channel::<JsonRpcMessage>(100);
-        let broadcast_tx = Arc::new(broadcast_tx);
-        let broadcast_tx2 = Arc::clone(&broadcast_tx);
+        let ip = match host.parse::<IpAddr>() {
+            Ok(ip) => origins.bind_ip(ip),
+            Err(e) => {
+                tracing::error!("Invalid SSE host {}: {}", host, e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::InvalidRequest(format!(
+                        "Invalid host: {}",
+                        host
+                    ))))
+                    .await;
+                return;
+            }
+        };
 
-        // Client counter for unique IDs
-        let client_counter = Arc::new(AtomicU64::new(0));
-        let host_clone = host.clone();
+        // Every SSE stream is a session with its own outgoing queue
+        let sessions = SessionRegistry::default();
 
         // SSE endpoint route
-        let sse_route = warp::path("sse")
-            .and(warp::get())
-            .map(move || {
-                let client_id = client_counter.fetch_add(1, Ordering::SeqCst);
-                let broadcast_rx = broadcast_tx.subscribe();
-                let endpoint = format!("http://{}:{}/message/{}", host.clone(), port, client_id);
-
-                warp::sse::reply(warp::sse::keep_alive()
-                    .interval(Duration::from_secs(30))
-                    .stream(async_stream::stream! {
-                        yield Ok::<_, warp::Error>(warp::sse::Event::default()
-                            .event("endpoint")
-                            .json_data(&EndpointEvent { endpoint })
-                            .unwrap());
-
-                        let mut broadcast_rx = broadcast_rx;
-                        while let Ok(msg) = broadcast_rx.recv().await {
+        let sse_route = warp::path("sse").and(warp::get()).then({
+            let sessions = sessions.clone();
+            let event_tx = event_tx.clone();
+            move || {
+                let sessions = sessions.clone();
+                let event_tx = event_tx.clone();
+                let host = host.clone();
+                async move {
+                    let (session_id, mut session_rx) = sessions.open(buffer_size).await;
+                    let endpoint = format!("http://{}:{}/message/{}", host, port, session_id);
+                    tracing::debug!("SSE session {} connected", session_id);
+
+                    // Dropped together with the stream when the client disconnects
+                    let guard = SessionGuard {
+                        session_id,
+                        registry: sessions,
+                        event_tx,
+                    };
+
+                    warp::sse::reply(warp::sse::keep_alive()
+                        .interval(Duration::from_secs(30))
+                        .stream(async_stream::stream! {
+                            let _guard = guard;
                             yield Ok::<_, warp::Error>(warp::sse::Event::default()
-                                .event("message")
-                                .json_data(&msg)
+                                .event("endpoint")
+                                .json_data(&EndpointEvent { endpoint })
                                 .unwrap());
-                        }
-                    }))
-            });
 
-        // Message receiving route
-        let message_route = warp::path!("message" / u64)
+                            while let Some(msg) = session_rx.recv().await {
+                                yield Ok::<_, warp::Error>(warp::sse::Event::default()
+                                    .event("message")
+                                    .json_data(&msg)
+                                    .unwrap());
+                            }
+                        }))
+                }
+            }
+        });
+
+        // Message receiving route. Malformed bodies are accepted too; the error
+        // they get goes back on the session's stream like any response.
+        let message_route = warp::path!("message" / SessionId)
             .and(warp::post())
-            .and(warp::body::json())
-            .map(move |_client_id: u64, message: JsonRpcMessage| {
+            .and(warp::body::bytes())
+            .then({
+                let sessions = sessions.clone();
                 let event_tx = event_tx.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = event_tx.send(TransportEvent::Message(message)).await {
-                        tracing::error!("Failed to forward message: {:?}", e);
+                move |session_id: SessionId, body: warp::hyper::body::Bytes| {
+                    let sessions = sessions.clone();
+                    let event_tx = event_tx.clone();
+                    async move {
+                        if !sessions.contains(&session_id).await {
+                            return warp::http::StatusCode::NOT_FOUND;
+                        }
+                        let event = match parse_body(&body) {
+                            Ok(message) => TransportEvent::SessionMessage(session_id, message),
+                            Err(e) => TransportEvent::Malformed(Some(session_id), e),
+                        };
+                        if let Err(e) = event_tx.send(event).await {
+                            tracing::error!("Failed to forward message: {:?}", e);
+                            return warp::http::StatusCode::SERVICE_UNAVAILABLE;
+                        }
+                        warp::http::StatusCode::ACCEPTED
                     }
-                });
-                warp::reply()
+                }
             });
 
         // Combine routes
-        let routes = sse_route.or(message_route);
-
-        // Message forwarding task
-        tokio::spawn(async move {
-            while let Some(cmd) = cmd_rx.recv().await {
-                match cmd {
-                    TransportCommand::SendMessage(msg) => {
-                        // Skip broadcasting debug log messages about SSE and internal operations
-                        let should_skip = match &msg {
-                            JsonRpcMessage::Notification(n) if n.method == "notifications/message" => {
-                                if let Some(params) = &n.params {
-                                    // Check the log message and logger
-                                    let is_debug = params.get("level")
-                                        .and_then(|l| l.as_str())
-                                        .map_or(false, |l| l == "debug");
-                                    
-                                    let logger = params.get("logger")
-                                        .and_then(|l| l.as_str())
-                                        .unwrap_or("");
-
-                                    let message = params.get("data")
-                                        .and_then(|d| d.get("message"))
-                                        .and_then(|m| m.as_str())
-                                        .unwrap_or("");
-
-                                    is_debug && (
-                                        logger.starts_with("hyper::") ||
-                                        logger.starts_with("mcp_rs::transport") ||
-                                        message.contains("Broadcasting SSE message") ||
-                                        message.contains("Failed to broadcast message")
-                                    )
-                                } else {
-                                    false
-                                }
-                            }
-                            _ => false
-                        };
+        let routes = with_cors(
+            origins,
+            auth::require_bearer(auth)
+                .and(sse_route.or(message_route))
+                .recover(auth::recover),
+        );
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = match warp::serve(routes).try_bind_with_graceful_shutdown((ip, port), async {
+            let _ = shutdown_rx.await;
+        }) {
+            Ok((addr, server)) => {
+                tracing::debug!("SSE server listening on {}", addr);
+                tokio::spawn(server)
+            }
+            Err(e) => {
+                tracing::error!("Failed to bind SSE server: {:?}", e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::ConnectionClosed))
+                    .await;
+                return;
+            }
+        };
 
-                        if !should_skip {
-                            tracing::debug!("Broadcasting SSE message: {:?}", msg);
-                            if let Err(e) = broadcast_tx2.send(msg) {
-                                tracing::error!("Failed to broadcast message: {:?}", e);
-                            }
-                        }
+        // Message forwarding loop
+        while let Some(cmd) = cmd_rx.recv().await {
+            match cmd {
+                TransportCommand::SendMessage(msg) => {
+                    if !Self::is_internal_log(&msg) {
+                        tracing::debug!("Broadcasting SSE message: {:?}", msg);
+                        sessions.broadcast(msg).await;
                     }
-                    TransportCommand::Close => break,
                 }
+                TransportCommand::SendTo(session_id, msg) => {
+                    if let Err(e) = sessions.send_to(&session_id, msg).await {
+                        tracing::debug!("Failed to send to session {}: {:?}", session_id, e);
+                    }
+                }
+                TransportCommand::Close => break,
             }
-        });
+        }
 
-        // Start the server
-        warp::serve(routes)
-            .run((host_clone.parse::<IpAddr>().unwrap(), port))
-            .await;
+        // End every open stream so the graceful shutdown can complete
+        sessions.close_all().await;
+        let _ = shutdown_tx.send(());
+        let _ = server.await;
+        let _ = event_tx.send(TransportEvent::Closed).await;
+    }
+
+    /// Debug log notifications about SSE and transport internals are not worth broadcasting
+    fn is_internal_log(msg: &JsonRpcMessage) -> bool {
+        match msg {
+            JsonRpcMessage::Notification(n) if n.method == "notifications/message" => {
+                if let Some(params) = &n.params {
+                    // Check the log message and logger
+                    let is_debug = params.get("level")
+                        .and_then(|l| l.as_str())
+                        .is_some_and(|l| l == "debug");
+
+                    let logger = params.get("logger")
+                        .and_then(|l| l.as_str())
+                        .unwrap_or("");
+
+                    let message = params.get("data")
+                        .and_then(|d| d.get("message"))
+                        .and_then(|m| m.as_str())
+                        .unwrap_or("");
+
+                    is_debug && (
+                        logger.starts_with("hyper::") ||
+                        logger.starts_with("mcp_rs::transport") ||
+                        message.contains("Broadcasting SSE message") ||
+                        message.contains("Failed to broadcast message")
+                    )
+                } else {
+                    false
+                }
+            }
+            _ => false
+        }
     }
 
     async fn run_client(
         host: String,
         port: u16,
+        bearer_token: Option<String>,
         mut cmd_rx: mpsc::Receiver<TransportCommand>,
         event_tx: mpsc::Sender<TransportEvent>,
     ) {
-        let client = reqwest::Client::new();
+        let client = auth::http_client(bearer_token.as_deref());
         let sse_url = format!("http://{}:{}/sse", host, port);
 
         tracing::debug!("Connecting to SSE endpoint: {}", sse_url);
@@ -372,14 +418,13 @@ impl SseTransport {
             while let Some(Ok(event)) = sse.next().await {
                 match event {
                     Event::Message(m) if m.event == "message" => {
-                        match serde_json::from_str::<JsonRpcMessage>(&m.data) {
-                            Ok(msg) => {
-                                if let Err(e) = event_tx2.send(TransportEvent::Message(msg)).await {
-                                    tracing::error!("Failed to forward SSE message: {:?}", e);
-                                    break;
-                                }
-                            }
-                            Err(e) => tracing::error!("Failed to parse SSE message: {:?}", e),
+                        let event = match parse_message(&m.data) {
+                            Ok(msg) => TransportEvent::Message(msg),
+                            Err(e) => TransportEvent::Malformed(None, e),
+                        };
+                        if let Err(e) = event_tx2.send(event).await {
+                            tracing::error!("Failed to forward SSE message: {:?}", e);
+                            break;
                         }
                     }
                     _ => continue,
@@ -390,7 +435,7 @@ impl SseTransport {
         // Message sending task
         while let Some(cmd) = cmd_rx.recv().await {
             match cmd {
-                TransportCommand::SendMessage(msg) => {
+                TransportCommand::SendMessage(msg) | TransportCommand::SendTo(_, msg) => {
                     tracing::debug!("Sending message to {}: {:?}", endpoint, msg);
                     if let Err(e) = client.post(&endpoint).json(&msg).send().await {
                         tracing::error!("Failed to send message: {:?}", e);
@@ -404,13 +449,22 @@ impl SseTransport {
     }
 
     async fn wait_for_endpoint(sse: &mut EventSource) -> Option<String> {
-        while let Some(Ok(event)) = sse.next().await {
-            if let Event::Message(m) = event {
-                if m.event == "endpoint" {
+        while let Some(event) = sse.next().await {
+            match event {
+                Ok(Event::Message(m)) if m.event == "endpoint" => {
                     if let Ok(endpoint) = serde_json::from_str::<EndpointEvent>(&m.data) {
                         return Some(endpoint.endpoint);
                     }
                 }
+                Ok(_) => continue,
+                Err(reqwest_eventsource::Error::InvalidStatusCode(status, _)) => {
+                    tracing::error!("SSE server refused the connection: {}", status);
+                    return None;
+                }
+                Err(e) => {
+                    tracing::error!("SSE stream failed: {:?}", e);
+                    return None;
+                }
             }
         }
         None
@@ -427,6 +481,7 @@ impl Transport for SseTransport {
             tokio::spawn(Self::run_client(
                 self.host.clone(),
                 self.port,
+                self.bearer_token.clone(),
                 cmd_rx,
                 event_tx,
             ));
@@ -434,6 +489,9 @@ impl Transport for SseTransport {
             tokio::spawn(Self::run_server(
                 self.host.clone(),
                 self.port,
+                self.buffer_size,
+                self.auth.clone(),
+                self.origins.clone(),
                 cmd_rx,
                 event_tx,
             ));
@@ -474,6 +532,11 @@ mod tests {
 
                 match event {
                     Some(TransportEvent::Message(msg)) => println!("Received: {:?}", msg),
+                    Some(TransportEvent::SessionMessage(id, msg)) => {
+                        println!("Received from {}: {:?}", id, msg)
+                    }
+                    Some(TransportEvent::SessionClosed(id)) => println!("Session closed: {}", id),
+                    Some(TransportEvent::Malformed(_, err)) => println!("Malformed: {:?}", err),
                     Some(TransportEvent::Error(err)) => println!("Error: {:?}", err),
                     Some(TransportEvent::Closed) => break,
                     None => break,
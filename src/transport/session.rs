@@ -0,0 +1,77 @@
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{mpsc, RwLock};
+
+use super::{JsonRpcMessage, SessionId, TransportEvent};
+use crate::error::McpError;
+
+/// Outgoing message queues for every connected session of a multi-client server transport.
+#[derive(Clone, Default)]
+pub(crate) struct SessionRegistry {
+    sessions: Arc<RwLock<HashMap<SessionId, mpsc::Sender<JsonRpcMessage>>>>,
+}
+
+impl SessionRegistry {
+    /// Register a new session and return its id with the queue of messages addressed to it
+    pub(crate) async fn open(&self, buffer_size: usize) -> (SessionId, mpsc::Receiver<JsonRpcMessage>) {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let (tx, rx) = mpsc::channel(buffer_size);
+        self.sessions.write().await.insert(session_id.clone(), tx);
+        (session_id, rx)
+    }
+
+    /// Forget a session, returning whether it was still registered
+    pub(crate) async fn close(&self, session_id: &str) -> bool {
+        self.sessions.write().await.remove(session_id).is_some()
+    }
+
+    /// Forget every session, ending their outgoing queues
+    pub(crate) async fn close_all(&self) {
+        self.sessions.write().await.clear();
+    }
+
+    pub(crate) async fn contains(&self, session_id: &str) -> bool {
+        self.sessions.read().await.contains_key(session_id)
+    }
+
+    /// Queue a message for a single session
+    pub(crate) async fn send_to(&self, session_id: &str, msg: JsonRpcMessage) -> Result<(), McpError> {
+        let tx = self
+            .sessions
+            .read()
+            .await
+            .get(session_id)
+            .cloned()
+            .ok_or(McpError::ConnectionClosed)?;
+        tx.send(msg).await.map_err(|_| McpError::ConnectionClosed)
+    }
+
+    /// Queue a message for every session. Sessions that are not keeping up miss it.
+    pub(crate) async fn broadcast(&self, msg: JsonRpcMessage) {
+        for (session_id, tx) in self.sessions.read().await.iter() {
+            if let Err(e) = tx.try_send(msg.clone()) {
+                tracing::warn!("Dropping broadcast for session {}: {}", session_id, e);
+            }
+        }
+    }
+}
+
+/// Closes its session when dropped, e.g. when an SSE client disconnects.
+pub(crate) struct SessionGuard {
+    pub(crate) session_id: SessionId,
+    pub(crate) registry: SessionRegistry,
+    pub(crate) event_tx: mpsc::Sender<TransportEvent>,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        let session_id = self.session_id.clone();
+        let registry = self.registry.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            if registry.close(&session_id).await {
+                tracing::debug!("Session {} disconnected", session_id);
+                let _ = event_tx.send(TransportEvent::SessionClosed(session_id)).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,387 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    sync::{mpsc, watch},
+};
+use tokio_util::{
+    bytes::BytesMut,
+    codec::{Decoder, LinesCodec, LinesCodecError},
+};
+
+use super::{
+    parse_message, session::SessionRegistry, JsonRpcMessage, SessionId, Transport,
+    TransportChannels, TransportCommand, TransportEvent,
+};
+use crate::error::McpError;
+
+/// Longest line the socket servers accept from a client, in bytes
+const MAX_LINE_LENGTH: usize = 16 * 1024 * 1024;
+
+// Stream Transport Implementation
+//
+// Newline-delimited JSON over any reader/writer pair, e.g. both halves of a
+// socket or a `tokio::io::duplex` pipe. Stdio, child processes and the socket
+// transports share this framing.
+pub struct StreamTransport<R, W> {
+    io: Option<(R, W)>,
+    buffer_size: usize,
+}
+
+impl<R, W> StreamTransport<R, W> {
+    pub fn new(reader: R, writer: W, buffer_size: usize) -> Self {
+        Self {
+            io: Some((reader, writer)),
+            buffer_size,
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> StreamTransport<ReadHalf<S>, WriteHalf<S>> {
+    /// Use both directions of a single stream
+    pub fn from_stream(stream: S, buffer_size: usize) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self::new(reader, writer, buffer_size)
+    }
+}
+
+#[async_trait]
+impl<R, W> Transport for StreamTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send + Sync + 'static,
+    W: AsyncWrite + Unpin + Send + Sync + 'static,
+{
+    async fn start(&mut self) -> Result<TransportChannels, McpError> {
+        let (reader, writer) = self
+            .io
+            .take()
+            .ok_or_else(|| McpError::InternalError("Stream transport already started".to_string()))?;
+
+        let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
+        let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
+
+        // Spawn the transport actor
+        tokio::spawn(run(reader, writer, cmd_rx, event_tx));
+
+        let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
+        Ok(TransportChannels { cmd_tx, event_rx })
+    }
+}
+
+/// Exchange messages with a single peer until `Close` or until the peer hangs up
+pub(super) async fn run<R, W>(
+    reader: R,
+    writer: W,
+    mut cmd_rx: mpsc::Receiver<TransportCommand>,
+    event_tx: mpsc::Sender<TransportEvent>,
+) where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (write_tx, writer_handle) = spawn_line_writer(writer);
+    let mut reader_handle = spawn_line_reader(reader, usize::MAX, event_tx.clone(), untagged);
+    let mut peer_closed = false;
+
+    // Main message loop
+    loop {
+        tokio::select! {
+            cmd = cmd_rx.recv() => match cmd {
+                Some(TransportCommand::SendMessage(msg) | TransportCommand::SendTo(_, msg)) => {
+                    match serde_json::to_string(&msg) {
+                        Ok(s) => {
+                            if write_tx.send(s).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => tracing::error!("Failed to serialize message: {:?}", e),
+                    }
+                }
+                Some(TransportCommand::Close) | None => break,
+            },
+            _ = &mut reader_handle => {
+                peer_closed = true;
+                break;
+            }
+        }
+    }
+
+    // Cleanup
+    if !peer_closed {
+        reader_handle.abort();
+    }
+    drop(write_tx);
+    let _ = writer_handle.await;
+    let _ = event_tx.send(TransportEvent::Closed).await;
+}
+
+/// Accepts connections for the socket server transports.
+#[async_trait]
+pub(super) trait Listener: Send + 'static {
+    type Stream: AsyncRead + AsyncWrite + Send + 'static;
+
+    async fn accept(&mut self) -> std::io::Result<Self::Stream>;
+}
+
+/// Serve every accepted connection as its own session until `Close`
+pub(super) async fn serve<L: Listener>(
+    mut listener: L,
+    buffer_size: usize,
+    mut cmd_rx: mpsc::Receiver<TransportCommand>,
+    event_tx: mpsc::Sender<TransportEvent>,
+) {
+    let sessions = SessionRegistry::default();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    loop {
+        tokio::select! {
+            conn = listener.accept() => match conn {
+                Ok(stream) => {
+                    let (session_id, outgoing) = sessions.open(buffer_size).await;
+                    tracing::debug!("Socket session {} connected", session_id);
+                    let sessions = sessions.clone();
+                    let event_tx = event_tx.clone();
+                    let shutdown_rx = shutdown_rx.clone();
+                    tokio::spawn(async move {
+                        handle_connection(stream, session_id.clone(), &event_tx, outgoing, shutdown_rx)
+                            .await;
+                        if sessions.close(&session_id).await {
+                            let _ = event_tx.send(TransportEvent::SessionClosed(session_id)).await;
+                        }
+                    });
+                }
+                Err(e) => tracing::error!("Failed to accept connection: {:?}", e),
+            },
+            cmd = cmd_rx.recv() => match cmd {
+                Some(TransportCommand::SendMessage(msg)) => sessions.broadcast(msg).await,
+                Some(TransportCommand::SendTo(session_id, msg)) => {
+                    if let Err(e) = sessions.send_to(&session_id, msg).await {
+                        tracing::debug!("Failed to send to session {}: {:?}", session_id, e);
+                    }
+                }
+                Some(TransportCommand::Close) | None => break,
+            },
+        }
+    }
+
+    // Stop every connection before reporting the server closed
+    let _ = shutdown_tx.send(true);
+    sessions.close_all().await;
+    let _ = event_tx.send(TransportEvent::Closed).await;
+}
+
+async fn handle_connection<S: AsyncRead + AsyncWrite + Send + 'static>(
+    stream: S,
+    session_id: SessionId,
+    event_tx: &mpsc::Sender<TransportEvent>,
+    mut outgoing: mpsc::Receiver<JsonRpcMessage>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let (reader, writer) = tokio::io::split(stream);
+    let (write_tx, writer_handle) = spawn_line_writer(writer);
+    let mut reader_handle = spawn_line_reader(
+        reader,
+        MAX_LINE_LENGTH,
+        event_tx.clone(),
+        move |message| match message {
+            Ok(msg) => TransportEvent::SessionMessage(session_id.clone(), msg),
+            Err(e) => TransportEvent::Malformed(Some(session_id.clone()), e),
+        },
+    );
+    let mut peer_closed = false;
+
+    loop {
+        tokio::select! {
+            msg = outgoing.recv() => match msg {
+                Some(msg) => match serde_json::to_string(&msg) {
+                    Ok(s) => {
+                        if write_tx.send(s).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => tracing::error!("Failed to serialize message: {:?}", e),
+                },
+                None => break,
+            },
+            _ = &mut reader_handle => {
+                peer_closed = true;
+                break;
+            }
+            _ = shutdown_rx.changed() => break,
+        }
+    }
+
+    // The connection closes once both halves of the stream are dropped
+    if !peer_closed {
+        reader_handle.abort();
+        let _ = reader_handle.await;
+    }
+    drop(write_tx);
+    let _ = writer_handle.await;
+}
+
+/// Write each queued message as one line
+pub(super) fn spawn_line_writer<W>(writer: W) -> (mpsc::Sender<String>, tokio::task::JoinHandle<()>)
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (write_tx, mut write_rx) = mpsc::channel::<String>(32);
+    let handle = tokio::spawn(async move {
+        let mut writer = writer;
+        while let Some(msg) = write_rx.recv().await {
+            // Skip logging for certain types of messages
+            if !msg.contains("notifications/message") && !msg.contains("list_changed") {
+                tracing::debug!("-> {}", msg);
+            }
+
+            if let Err(e) = async {
+                writer.write_all(msg.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+                Ok::<_, std::io::Error>(())
+            }.await {
+                tracing::error!("Write error: {:?}", e);
+                break;
+            }
+        }
+    });
+    (write_tx, handle)
+}
+
+/// Parse every line as a JSON-RPC message until EOF and pass on the event
+/// `tag` makes of it. Lines longer than `max_length` bytes are skipped and
+/// reported as malformed.
+pub(super) fn spawn_line_reader<R, F>(
+    reader: R,
+    max_length: usize,
+    event_tx: mpsc::Sender<TransportEvent>,
+    tag: F,
+) -> tokio::task::JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    F: Fn(Result<JsonRpcMessage, McpError>) -> TransportEvent + Send + 'static,
+{
+    tokio::spawn(async move {
+        // Decoded by hand rather than with `FramedRead`, which stops at the first
+        // over-long line
+        let mut reader = reader;
+        let mut codec = LinesCodec::new_with_max_length(max_length);
+        let mut buffer = BytesMut::new();
+        let mut eof = false;
+        loop {
+            let line = if eof {
+                codec.decode_eof(&mut buffer)
+            } else {
+                codec.decode(&mut buffer)
+            };
+            let message = match line {
+                Ok(None) if eof => break,
+                Ok(None) => {
+                    match reader.read_buf(&mut buffer).await {
+                        Ok(0) => eof = true,
+                        Ok(_) => {}
+                        Err(e) => {
+                            tracing::error!("Read error: {:?}", e);
+                            let _ = event_tx.send(TransportEvent::Error(McpError::IoError)).await;
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                Ok(Some(line)) => {
+                    let trimmed = line.trim();
+                    if !trimmed.contains("notifications/message") && !trimmed.contains("list_changed") {
+                        tracing::debug!("<- {}", trimmed);
+                    }
+                    if trimmed.is_empty() {
+                        continue;
+                    }
+                    parse_message(trimmed)
+                }
+                Err(LinesCodecError::MaxLineLengthExceeded) => Err(McpError::InvalidRequest(
+                    format!("Message longer than {} bytes", max_length),
+                )),
+                Err(LinesCodecError::Io(e)) => {
+                    tracing::error!("Read error: {:?}", e);
+                    let _ = event_tx.send(TransportEvent::Error(McpError::IoError)).await;
+                    break;
+                }
+            };
+            if event_tx.send(tag(message)).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// The event for a message from the only peer of a transport
+pub(super) fn untagged(message: Result<JsonRpcMessage, McpError>) -> TransportEvent {
+    match message {
+        Ok(msg) => TransportEvent::Message(msg),
+        Err(e) => TransportEvent::Malformed(None, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{protocol::JsonRpcNotification, testing::next_event};
+
+    #[tokio::test]
+    async fn test_duplex_round_trip() -> Result<(), McpError> {
+        let (a, b) = tokio::io::duplex(1024);
+        let left = StreamTransport::from_stream(a, 32).start().await?;
+        let right = StreamTransport::from_stream(b, 32).start().await?;
+
+        left.cmd_tx
+            .send(TransportCommand::SendMessage(JsonRpcMessage::Notification(
+                JsonRpcNotification {
+                    jsonrpc: "2.0".to_string(),
+                    method: "hello".to_string(),
+                    params: None,
+                },
+            )))
+            .await
+            .unwrap();
+        match next_event(&right).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "hello")
+            }
+            other => panic!("Expected notification, got {:?}", other),
+        }
+
+        // Closing one side is seen as EOF by the other
+        left.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        assert!(matches!(next_event(&left).await, Some(TransportEvent::Closed)));
+        assert!(matches!(next_event(&right).await, Some(TransportEvent::Closed)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_long_lines_are_skipped() {
+        let (mut peer, stream) = tokio::io::duplex(1024);
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let reader = spawn_line_reader(stream, 64, event_tx, untagged);
+
+        let long = format!("{{\"jsonrpc\":\"2.0\",\"method\":\"{}\"}}\n", "x".repeat(100));
+        peer.write_all(long.as_bytes()).await.unwrap();
+        peer.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"short\"}\n").await.unwrap();
+
+        match event_rx.recv().await {
+            Some(TransportEvent::Malformed(None, McpError::InvalidRequest(message))) => {
+                assert_eq!(message, "Message longer than 64 bytes")
+            }
+            other => panic!("Expected a malformed message, got {:?}", other),
+        }
+        // Reading resumes with the next line
+        match event_rx.recv().await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "short")
+            }
+            other => panic!("Expected notification, got {:?}", other),
+        }
+        drop(peer);
+        reader.await.unwrap();
+        assert!(event_rx.recv().await.is_none());
+    }
+}
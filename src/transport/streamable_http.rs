@@ -0,0 +1,1078 @@
+use async_trait::async_trait;
+use eventsource_stream::Eventsource;
+use futures::StreamExt;
+use std::{collections::HashMap, convert::Infallible, net::IpAddr, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, oneshot, RwLock};
+use warp::{http::StatusCode, reply::Response, Filter, Reply};
+
+use super::{
+    cors::with_cors, parse_body, parse_message, AllowedOrigins, JsonRpcMessage, SessionId,
+    Transport, TransportChannels, TransportCommand, TransportEvent,
+};
+use crate::{
+    auth::{self, BearerAuth},
+    error::McpError,
+    protocol::{CancelledNotification, Protocol, RequestId},
+};
+
+/// Header carrying the session id assigned by the server during initialization.
+pub const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+// Streamable HTTP Transport Implementation
+//
+// Everything goes through a single `http://host:port/mcp` endpoint. Clients POST
+// one JSON-RPC message at a time. Requests are answered with either a JSON body
+// or an SSE stream that ends with the response, depending on the `Accept`
+// header. A GET opens a stream for server-initiated messages and a DELETE ends
+// the session. A POST that is not a JSON-RPC message is answered right away
+// with a 400 carrying the JSON-RPC error, since no response can be matched to it.
+pub struct StreamableHttpTransport {
+    host: String,
+    port: u16,
+    client_mode: bool,
+    buffer_size: usize,
+    auth: Option<BearerAuth>,
+    origins: AllowedOrigins,
+    bearer_token: Option<String>,
+}
+
+/// A POST waiting for the response to its request
+struct PendingRequest {
+    tx: mpsc::Sender<JsonRpcMessage>,
+    /// Whether the POST is an SSE stream that can also carry related messages
+    streaming: bool,
+}
+
+#[derive(Default)]
+struct HttpSession {
+    stream: Option<mpsc::Sender<JsonRpcMessage>>,
+    pending: HashMap<RequestId, PendingRequest>,
+}
+
+/// Open HTTP sessions and the streams their messages can be delivered on
+#[derive(Clone, Default)]
+struct HttpSessions {
+    sessions: Arc<RwLock<HashMap<SessionId, HttpSession>>>,
+}
+
+impl HttpSessions {
+    async fn open(&self) -> SessionId {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        self.sessions
+            .write()
+            .await
+            .insert(session_id.clone(), HttpSession::default());
+        session_id
+    }
+
+    async fn close(&self, session_id: &str) -> bool {
+        self.sessions.write().await.remove(session_id).is_some()
+    }
+
+    /// Drop every session, ending all of their open streams
+    async fn close_all(&self) {
+        self.sessions.write().await.clear();
+    }
+
+    async fn contains(&self, session_id: &str) -> bool {
+        self.sessions.read().await.contains_key(session_id)
+    }
+
+    async fn set_stream(&self, session_id: &str, tx: mpsc::Sender<JsonRpcMessage>) -> bool {
+        match self.sessions.write().await.get_mut(session_id) {
+            Some(session) => {
+                session.stream = Some(tx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn add_pending(
+        &self,
+        session_id: &str,
+        request_id: RequestId,
+        streaming: bool,
+        buffer_size: usize,
+    ) -> Option<mpsc::Receiver<JsonRpcMessage>> {
+        let (tx, rx) = mpsc::channel(buffer_size);
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(session_id)?;
+        session
+            .pending
+            .insert(request_id, PendingRequest { tx, streaming });
+        Some(rx)
+    }
+
+    /// Deliver a message to a session. Responses go back on the POST that carried
+    /// their request. Anything else uses the GET stream, or failing that any open
+    /// SSE response stream.
+    async fn route(&self, session_id: &str, msg: JsonRpcMessage) -> Result<(), McpError> {
+        let tx = {
+            let mut sessions = self.sessions.write().await;
+            let session = sessions
+                .get_mut(session_id)
+                .ok_or(McpError::ConnectionClosed)?;
+            session.pending.retain(|_, pending| !pending.tx.is_closed());
+
+            let pending_id = response_ids(&msg)
+                .into_iter()
+                .find(|id| session.pending.contains_key(id));
+            match pending_id {
+                Some(id) => session.pending.remove(&id).map(|pending| pending.tx),
+                None => session
+                    .stream
+                    .clone()
+                    .filter(|tx| !tx.is_closed())
+                    .or_else(|| {
+                        session
+                            .pending
+                            .values()
+                            .find(|pending| pending.streaming)
+                            .map(|pending| pending.tx.clone())
+                    }),
+            }
+        };
+
+        match tx {
+            Some(tx) => tx.send(msg).await.map_err(|_| McpError::ConnectionClosed),
+            None => Err(McpError::InternalError(format!(
+                "No open stream for session {}",
+                session_id
+            ))),
+        }
+    }
+
+    /// Stop waiting for responses the client has cancelled, ending their POSTs
+    async fn cancel_pending(&self, session_id: &str, request_ids: &[RequestId]) {
+        if let Some(session) = self.sessions.write().await.get_mut(session_id) {
+            for request_id in request_ids {
+                session.pending.remove(request_id);
+            }
+        }
+    }
+
+    async fn broadcast(&self, msg: JsonRpcMessage) {
+        let session_ids: Vec<SessionId> = self.sessions.read().await.keys().cloned().collect();
+        for session_id in session_ids {
+            if let Err(e) = self.route(&session_id, msg.clone()).await {
+                tracing::debug!("Dropping broadcast for session {}: {:?}", session_id, e);
+            }
+        }
+    }
+}
+
+/// Ids of the requests a message answers. A batch answers all of its responses' requests.
+fn response_ids(msg: &JsonRpcMessage) -> Vec<RequestId> {
+    match msg {
+        JsonRpcMessage::Response(resp) => resp.id.iter().cloned().collect(),
+        JsonRpcMessage::Batch(messages) => messages.iter().flat_map(response_ids).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_response_to(msg: &JsonRpcMessage, request_id: &RequestId) -> bool {
+    response_ids(msg).contains(request_id)
+}
+
+/// Ids of the requests a message cancels, which will never be answered
+fn cancelled_ids(msg: &JsonRpcMessage) -> Vec<RequestId> {
+    match msg {
+        JsonRpcMessage::Notification(n) if n.method == "notifications/cancelled" => n
+            .params
+            .clone()
+            .and_then(|params| serde_json::from_value::<CancelledNotification>(params).ok())
+            .map(|cancelled| cancelled.request_id)
+            .into_iter()
+            .collect(),
+        JsonRpcMessage::Batch(messages) => messages.iter().flat_map(cancelled_ids).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_error_response(msg: &JsonRpcMessage) -> bool {
+    matches!(msg, JsonRpcMessage::Response(resp) if resp.error.is_some())
+}
+
+fn status(code: StatusCode) -> Response {
+    code.into_response()
+}
+
+/// A 400 carrying the JSON-RPC error for a body that is not a message
+fn malformed(e: McpError) -> Response {
+    let error = Protocol::error(None, e);
+    warp::reply::with_status(warp::reply::json(&error), StatusCode::BAD_REQUEST).into_response()
+}
+
+fn with_session(reply: impl Reply, session_id: &str) -> Response {
+    warp::reply::with_header(reply, SESSION_HEADER, session_id).into_response()
+}
+
+fn sse_event(msg: &JsonRpcMessage) -> Result<warp::sse::Event, Infallible> {
+    Ok(warp::sse::Event::default()
+        .event("message")
+        .json_data(msg)
+        .unwrap())
+}
+
+impl StreamableHttpTransport {
+    pub fn new_server(host: String, port: u16, buffer_size: usize) -> Self {
+        Self {
+            host,
+            port,
+            client_mode: false,
+            buffer_size,
+            auth: None,
+            origins: AllowedOrigins::default(),
+            bearer_token: None,
+        }
+    }
+
+    pub fn new_client(host: String, port: u16, buffer_size: usize) -> Self {
+        Self {
+            host,
+            port,
+            client_mode: true,
+            buffer_size,
+            auth: None,
+            origins: AllowedOrigins::default(),
+            bearer_token: None,
+        }
+    }
+
+    /// Require clients to present a bearer token `auth` accepts. Others get 401 or 403.
+    pub fn with_auth(mut self, auth: BearerAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Only accept browser requests from `origins`. By default only loopback
+    /// origins are accepted; with `AllowedOrigins::any()` the server only
+    /// listens on loopback.
+    pub fn with_allowed_origins(mut self, origins: AllowedOrigins) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    /// Send `token` in an `Authorization: Bearer` header when connecting as a client
+    pub fn with_bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    async fn run_server(
+        host: String,
+        port: u16,
+        buffer_size: usize,
+        auth: Option<BearerAuth>,
+        origins: AllowedOrigins,
+        mut cmd_rx: mpsc::Receiver<TransportCommand>,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) {
+        let ip = match host.parse::<IpAddr>() {
+            Ok(ip) => origins.bind_ip(ip),
+            Err(e) => {
+                tracing::error!("Invalid HTTP host {}: {}", host, e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::InvalidRequest(format!(
+                        "Invalid host: {}",
+                        host
+                    ))))
+                    .await;
+                return;
+            }
+        };
+
+        let sessions = HttpSessions::default();
+        let endpoint = warp::path("mcp").and(warp::path::end());
+
+        let post_route = endpoint
+            .and(warp::post())
+            .and(warp::header::optional::<String>(SESSION_HEADER))
+            .and(warp::header::optional::<String>("accept"))
+            .and(warp::body::bytes())
+            .then({
+                let sessions = sessions.clone();
+                let event_tx = event_tx.clone();
+                move |session_id, accept, body: warp::hyper::body::Bytes| {
+                    let sessions = sessions.clone();
+                    let event_tx = event_tx.clone();
+                    async move {
+                        let message = match parse_body(&body) {
+                            Ok(message) => message,
+                            Err(e) => return malformed(e),
+                        };
+                        Self::handle_post(sessions, event_tx, buffer_size, session_id, accept, message)
+                            .await
+                    }
+                }
+            });
+
+        let get_route = endpoint
+            .and(warp::get())
+            .and(warp::header::optional::<String>(SESSION_HEADER))
+            .then({
+                let sessions = sessions.clone();
+                move |session_id: Option<SessionId>| {
+                    let sessions = sessions.clone();
+                    async move {
+                        let Some(session_id) = session_id else {
+                            return status(StatusCode::BAD_REQUEST);
+                        };
+                        let (tx, mut rx) = mpsc::channel(buffer_size);
+                        if !sessions.set_stream(&session_id, tx).await {
+                            return status(StatusCode::NOT_FOUND);
+                        }
+
+                        let stream = async_stream::stream! {
+                            while let Some(msg) = rx.recv().await {
+                                yield sse_event(&msg);
+                            }
+                        };
+                        let reply = warp::sse::reply(
+                            warp::sse::keep_alive()
+                                .interval(Duration::from_secs(30))
+                                .stream(stream),
+                        );
+                        with_session(reply, &session_id)
+                    }
+                }
+            });
+
+        let delete_route = endpoint
+            .and(warp::delete())
+            .and(warp::header::optional::<String>(SESSION_HEADER))
+            .then({
+                let sessions = sessions.clone();
+                let event_tx = event_tx.clone();
+                move |session_id: Option<SessionId>| {
+                    let sessions = sessions.clone();
+                    let event_tx = event_tx.clone();
+                    async move {
+                        let Some(session_id) = session_id else {
+                            return status(StatusCode::BAD_REQUEST);
+                        };
+                        if !Self::end_session(&sessions, &event_tx, &session_id).await {
+                            return status(StatusCode::NOT_FOUND);
+                        }
+                        tracing::debug!("HTTP session {} ended by client", session_id);
+                        status(StatusCode::OK)
+                    }
+                }
+            });
+
+        let routes = with_cors(
+            origins,
+            auth::require_bearer(auth)
+                .and(post_route.or(get_route).or(delete_route))
+                .recover(auth::recover),
+        );
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = match warp::serve(routes).try_bind_with_graceful_shutdown((ip, port), async {
+            let _ = shutdown_rx.await;
+        }) {
+            Ok((addr, server)) => {
+                tracing::debug!("Streamable HTTP server listening on {}", addr);
+                tokio::spawn(server)
+            }
+            Err(e) => {
+                tracing::error!("Failed to bind Streamable HTTP server: {:?}", e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::ConnectionClosed))
+                    .await;
+                return;
+            }
+        };
+
+        // Message forwarding loop
+        while let Some(cmd) = cmd_rx.recv().await {
+            match cmd {
+                TransportCommand::SendMessage(msg) => sessions.broadcast(msg).await,
+                TransportCommand::SendTo(session_id, msg) => {
+                    if let Err(e) = sessions.route(&session_id, msg).await {
+                        tracing::debug!("Failed to send to session {}: {:?}", session_id, e);
+                    }
+                }
+                TransportCommand::Close => break,
+            }
+        }
+
+        // End every open stream so the graceful shutdown can complete
+        sessions.close_all().await;
+        let _ = shutdown_tx.send(());
+        let _ = server.await;
+        let _ = event_tx.send(TransportEvent::Closed).await;
+    }
+
+    /// Drop a session and tell the protocol it is gone. False if there was no such session.
+    async fn end_session(
+        sessions: &HttpSessions,
+        event_tx: &mpsc::Sender<TransportEvent>,
+        session_id: &str,
+    ) -> bool {
+        if !sessions.close(session_id).await {
+            return false;
+        }
+        let _ = event_tx
+            .send(TransportEvent::SessionClosed(session_id.to_string()))
+            .await;
+        true
+    }
+
+    async fn handle_post(
+        sessions: HttpSessions,
+        event_tx: mpsc::Sender<TransportEvent>,
+        buffer_size: usize,
+        session_id: Option<SessionId>,
+        accept: Option<String>,
+        message: JsonRpcMessage,
+    ) -> Response {
+        let is_initialize =
+            matches!(&message, JsonRpcMessage::Request(req) if req.method == "initialize");
+
+        // Only an initialize request may arrive without a session
+        let session_id = match session_id {
+            Some(session_id) if sessions.contains(&session_id).await => session_id,
+            Some(_) => return status(StatusCode::NOT_FOUND),
+            None if is_initialize => {
+                let session_id = sessions.open().await;
+                tracing::debug!("HTTP session {} created", session_id);
+                session_id
+            }
+            None => return status(StatusCode::BAD_REQUEST),
+        };
+
+        let streaming = accept.is_some_and(|accept| accept.contains("text/event-stream"));
+        // A batch is answered with one batch, which is waited for under the id
+        // of its first request
+        let request_id = match &message {
+            JsonRpcMessage::Request(req) => Some(req.id.clone()),
+            JsonRpcMessage::Batch(messages) => messages.iter().find_map(|msg| match msg {
+                JsonRpcMessage::Request(req) => Some(req.id.clone()),
+                _ => None,
+            }),
+            _ => None,
+        };
+        let pending = match request_id {
+            Some(request_id) => sessions
+                .add_pending(&session_id, request_id.clone(), streaming, buffer_size)
+                .await
+                .map(|rx| (request_id, rx)),
+            None => None,
+        };
+
+        // Cancelled requests are never answered, so their POSTs end here
+        let cancelled = cancelled_ids(&message);
+        if !cancelled.is_empty() {
+            sessions.cancel_pending(&session_id, &cancelled).await;
+        }
+
+        let event = TransportEvent::SessionMessage(session_id.clone(), message);
+        if let Err(e) = event_tx.send(event).await {
+            tracing::error!("Failed to forward message: {:?}", e);
+            return status(StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        // Notifications and responses only need an acknowledgement
+        let Some((request_id, mut rx)) = pending else {
+            return with_session(StatusCode::ACCEPTED, &session_id);
+        };
+
+        // A session only outlives an initialize request the server accepted
+        let rejected = move |msg: &JsonRpcMessage| is_initialize && is_error_response(msg);
+
+        if streaming {
+            let session = session_id.clone();
+            let stream = async_stream::stream! {
+                while let Some(msg) = rx.recv().await {
+                    let done = is_response_to(&msg, &request_id);
+                    if done && rejected(&msg) {
+                        Self::end_session(&sessions, &event_tx, &session).await;
+                    }
+                    yield sse_event(&msg);
+                    if done {
+                        break;
+                    }
+                }
+            };
+            with_session(warp::sse::reply(stream), &session_id)
+        } else {
+            while let Some(msg) = rx.recv().await {
+                if is_response_to(&msg, &request_id) {
+                    if rejected(&msg) {
+                        Self::end_session(&sessions, &event_tx, &session_id).await;
+                    }
+                    return with_session(warp::reply::json(&msg), &session_id);
+                }
+            }
+            // Cancelled, or the session ended before the response came
+            status(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
+
+    async fn run_client(
+        host: String,
+        port: u16,
+        bearer_token: Option<String>,
+        mut cmd_rx: mpsc::Receiver<TransportCommand>,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) {
+        let client = auth::http_client(bearer_token.as_deref());
+        let url = format!("http://{}:{}/mcp", host, port);
+        tracing::debug!("Using Streamable HTTP endpoint: {}", url);
+
+        let mut session_id: Option<SessionId> = None;
+        let mut listener = None;
+
+        // Message sending loop
+        while let Some(cmd) = cmd_rx.recv().await {
+            match cmd {
+                TransportCommand::SendMessage(msg) | TransportCommand::SendTo(_, msg) => {
+                    // POSTs go out one at a time so the server sees messages in
+                    // order. Only the headers are waited for; bodies are read in
+                    // the background.
+                    let assigned = Self::post(
+                        client.clone(),
+                        url.clone(),
+                        session_id.clone(),
+                        msg,
+                        event_tx.clone(),
+                    )
+                    .await;
+
+                    if let (None, Some(assigned)) = (&session_id, assigned) {
+                        // Later messages carry the session from the first response
+                        tracing::debug!("Joined HTTP session {}", assigned);
+                        listener = Some(tokio::spawn(Self::listen(
+                            client.clone(),
+                            url.clone(),
+                            assigned.clone(),
+                            event_tx.clone(),
+                        )));
+                        session_id = Some(assigned);
+                    }
+                }
+                TransportCommand::Close => break,
+            }
+        }
+
+        if let Some(listener) = listener {
+            listener.abort();
+        }
+        if let Some(session_id) = session_id {
+            if let Err(e) = client
+                .delete(&url)
+                .header(SESSION_HEADER, session_id)
+                .send()
+                .await
+            {
+                tracing::debug!("Failed to end HTTP session: {:?}", e);
+            }
+        }
+        let _ = event_tx.send(TransportEvent::Closed).await;
+    }
+
+    /// POST one message and forward whatever comes back. Returns the session id
+    /// the server put on the response.
+    async fn post(
+        client: reqwest::Client,
+        url: String,
+        session_id: Option<SessionId>,
+        msg: JsonRpcMessage,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) -> Option<SessionId> {
+        let mut request = client
+            .post(&url)
+            .header(reqwest::header::ACCEPT, "application/json, text/event-stream")
+            .json(&msg);
+        if let Some(session_id) = session_id {
+            request = request.header(SESSION_HEADER, session_id);
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::error!("Failed to send message: {:?}", e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::ConnectionClosed))
+                    .await;
+                return None;
+            }
+        };
+
+        if !response.status().is_success() {
+            tracing::error!("Server rejected message with status {}", response.status());
+            let _ = event_tx
+                .send(TransportEvent::Error(McpError::InternalError(format!(
+                    "HTTP status {}",
+                    response.status()
+                ))))
+                .await;
+            return None;
+        }
+
+        let assigned = response
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        if response.status() == reqwest::StatusCode::ACCEPTED {
+            return assigned;
+        }
+
+        let is_sse = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+
+        // Read the body in the background so the caller can move on
+        tokio::spawn(async move {
+            if is_sse {
+                Self::forward_sse(response, &event_tx).await;
+            } else {
+                let event = match response.bytes().await {
+                    Ok(body) => match parse_body(&body) {
+                        Ok(msg) => TransportEvent::Message(msg),
+                        Err(e) => TransportEvent::Malformed(None, e),
+                    },
+                    Err(e) => {
+                        tracing::error!("Failed to read response body: {:?}", e);
+                        TransportEvent::Error(McpError::ConnectionClosed)
+                    }
+                };
+                let _ = event_tx.send(event).await;
+            }
+        });
+
+        assigned
+    }
+
+    /// Hold the GET stream open for messages the server sends on its own
+    async fn listen(
+        client: reqwest::Client,
+        url: String,
+        session_id: SessionId,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) {
+        let response = client
+            .get(&url)
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .header(SESSION_HEADER, session_id)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => {
+                Self::forward_sse(response, &event_tx).await;
+                // The server ended the session
+                let _ = event_tx.send(TransportEvent::Closed).await;
+            }
+            Ok(response) => {
+                tracing::debug!("Server offers no GET stream: {}", response.status());
+            }
+            Err(e) => tracing::error!("Failed to open GET stream: {:?}", e),
+        }
+    }
+
+    async fn forward_sse(response: reqwest::Response, event_tx: &mpsc::Sender<TransportEvent>) {
+        let mut events = response.bytes_stream().eventsource();
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(event) if event.event == "message" => {
+                    let event = match parse_message(&event.data) {
+                        Ok(msg) => TransportEvent::Message(msg),
+                        Err(e) => TransportEvent::Malformed(None, e),
+                    };
+                    if event_tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::debug!("SSE stream ended: {:?}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for StreamableHttpTransport {
+    async fn start(&mut self) -> Result<TransportChannels, McpError> {
+        let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
+        let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
+
+        if self.client_mode {
+            tokio::spawn(Self::run_client(
+                self.host.clone(),
+                self.port,
+                self.bearer_token.clone(),
+                cmd_rx,
+                event_tx,
+            ));
+        } else {
+            tokio::spawn(Self::run_server(
+                self.host.clone(),
+                self.port,
+                self.buffer_size,
+                self.auth.clone(),
+                self.origins.clone(),
+                cmd_rx,
+                event_tx,
+            ));
+        }
+
+        let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
+
+        Ok(TransportChannels { cmd_tx, event_rx })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse};
+    use crate::testing::{free_port, next_event};
+
+    fn request(id: i64, method: &str) -> JsonRpcMessage {
+        JsonRpcMessage::Request(JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: id.into(),
+            method: method.to_string(),
+            params: None,
+        })
+    }
+
+    fn response(id: i64) -> JsonRpcMessage {
+        JsonRpcMessage::Response(JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: Some(id.into()),
+            result: Some(serde_json::json!({})),
+            error: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_round_trip() -> Result<(), McpError> {
+        let port = free_port();
+        let server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let client = StreamableHttpTransport::new_client("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+
+        // The initialize request opens the session
+        client
+            .cmd_tx
+            .send(TransportCommand::SendMessage(request(1, "initialize")))
+            .await
+            .unwrap();
+        let session_id = match next_event(&server).await {
+            Some(TransportEvent::SessionMessage(session_id, JsonRpcMessage::Request(req))) => {
+                assert_eq!(req.method, "initialize");
+                session_id
+            }
+            other => panic!("Expected initialize on server, got {:?}", other),
+        };
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(session_id.clone(), response(1)))
+            .await
+            .unwrap();
+        match next_event(&client).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Response(resp))) => assert_eq!(resp.id, Some(1.into())),
+            other => panic!("Expected response on client, got {:?}", other),
+        }
+
+        // Later requests reuse the session
+        client
+            .cmd_tx
+            .send(TransportCommand::SendMessage(request(2, "ping")))
+            .await
+            .unwrap();
+        match next_event(&server).await {
+            Some(TransportEvent::SessionMessage(id, JsonRpcMessage::Request(req))) => {
+                assert_eq!(id, session_id);
+                assert_eq!(req.id, 2.into());
+            }
+            other => panic!("Expected ping on server, got {:?}", other),
+        }
+
+        // Server-initiated messages use the GET stream
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(
+                session_id.clone(),
+                JsonRpcMessage::Notification(JsonRpcNotification {
+                    jsonrpc: "2.0".to_string(),
+                    method: "server/hello".to_string(),
+                    params: None,
+                }),
+            ))
+            .await
+            .unwrap();
+        match next_event(&client).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "server/hello")
+            }
+            other => panic!("Expected notification on client, got {:?}", other),
+        }
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(session_id.clone(), response(2)))
+            .await
+            .unwrap();
+        match next_event(&client).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Response(resp))) => assert_eq!(resp.id, Some(2.into())),
+            other => panic!("Expected response on client, got {:?}", other),
+        }
+
+        // Closing the client ends the session with a DELETE
+        client.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        match next_event(&server).await {
+            Some(TransportEvent::SessionClosed(id)) => assert_eq!(id, session_id),
+            other => panic!("Expected session close on server, got {:?}", other),
+        }
+
+        server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        assert!(matches!(next_event(&server).await, Some(TransportEvent::Closed)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_batch() -> Result<(), McpError> {
+        let port = free_port();
+        let server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("http://127.0.0.1:{}/mcp", port);
+        let client = reqwest::Client::new();
+        let post = |body: JsonRpcMessage, session_id: Option<SessionId>| {
+            let mut request = client
+                .post(&url)
+                .header(reqwest::header::ACCEPT, "application/json")
+                .json(&body);
+            if let Some(session_id) = session_id {
+                request = request.header(SESSION_HEADER, session_id);
+            }
+            tokio::spawn(request.send())
+        };
+
+        let initialize = post(request(1, "initialize"), None);
+        let session_id = match next_event(&server).await {
+            Some(TransportEvent::SessionMessage(session_id, _)) => session_id,
+            other => panic!("Expected initialize on server, got {:?}", other),
+        };
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(session_id.clone(), response(1)))
+            .await
+            .unwrap();
+        assert!(initialize.await.unwrap().unwrap().status().is_success());
+
+        // A batch of requests is answered by one batch on the same POST
+        let batch = post(
+            JsonRpcMessage::Batch(vec![request(2, "ping"), request(3, "ping")]),
+            Some(session_id.clone()),
+        );
+        match next_event(&server).await {
+            Some(TransportEvent::SessionMessage(_, JsonRpcMessage::Batch(messages))) => {
+                assert_eq!(messages.len(), 2)
+            }
+            other => panic!("Expected batch on server, got {:?}", other),
+        }
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(
+                session_id,
+                JsonRpcMessage::Batch(vec![response(2), response(3)]),
+            ))
+            .await
+            .unwrap();
+        let body: serde_json::Value = batch.await.unwrap().unwrap().json().await.unwrap();
+        let ids: Vec<u64> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|resp| resp["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, vec![2, 3]);
+
+        server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_rejects_unknown_session() {
+        let port = free_port();
+        let _server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("http://127.0.0.1:{}/mcp", port);
+        let client = reqwest::Client::new();
+
+        let missing = client.post(&url).json(&request(1, "ping")).send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let unknown = client
+            .post(&url)
+            .header(SESSION_HEADER, "nope")
+            .json(&request(1, "ping"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unknown.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    async fn next_session_message(channels: &TransportChannels) -> (SessionId, JsonRpcMessage) {
+        match next_event(channels).await {
+            Some(TransportEvent::SessionMessage(session_id, msg)) => (session_id, msg),
+            other => panic!("Expected session message, got {:?}", other),
+        }
+    }
+
+    /// POST `msg` as JSON in the background, as a client without SSE support
+    fn post_json(
+        url: &str,
+        session_id: Option<&str>,
+        msg: JsonRpcMessage,
+    ) -> tokio::task::JoinHandle<reqwest::Response> {
+        let mut request = reqwest::Client::new().post(url).json(&msg);
+        if let Some(session_id) = session_id {
+            request = request.header(SESSION_HEADER, session_id);
+        }
+        tokio::spawn(async move { request.send().await.unwrap() })
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_session_lifecycle() {
+        let port = free_port();
+        let server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let url = format!("http://127.0.0.1:{}/mcp", port);
+
+        // A rejected initialize leaves no session behind
+        let post = post_json(&url, None, request(1, "initialize"));
+        let (rejected_id, _) = next_session_message(&server).await;
+        let error = Protocol::error(Some(1.into()), McpError::InvalidRequest("Full".to_string()));
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(rejected_id.clone(), error))
+            .await
+            .unwrap();
+        assert!(post.await.unwrap().status().is_success());
+        match next_event(&server).await {
+            Some(TransportEvent::SessionClosed(session_id)) => assert_eq!(session_id, rejected_id),
+            other => panic!("Expected session to close, got {:?}", other),
+        }
+        let gone = post_json(&url, Some(&rejected_id), request(2, "ping")).await.unwrap();
+        assert_eq!(gone.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let post = post_json(&url, None, request(1, "initialize"));
+        let (session_id, _) = next_session_message(&server).await;
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(session_id.clone(), response(1)))
+            .await
+            .unwrap();
+        assert!(post.await.unwrap().status().is_success());
+
+        // Cancelling a request ends its POST, which will never get a response
+        let post = post_json(&url, Some(&session_id), request(2, "slow"));
+        next_session_message(&server).await;
+        let cancelled = JsonRpcMessage::Notification(JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/cancelled".to_string(),
+            params: Some(serde_json::json!({ "requestId": 2 })),
+        });
+        let ack = post_json(&url, Some(&session_id), cancelled).await.unwrap();
+        assert_eq!(ack.status(), reqwest::StatusCode::ACCEPTED);
+        let ended = tokio::time::timeout(Duration::from_secs(5), post)
+            .await
+            .expect("Cancelled POST still waiting")
+            .unwrap();
+        assert!(!ended.status().is_success());
+
+        server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_keeps_message_order() -> Result<(), McpError> {
+        let port = free_port();
+        let server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let client = StreamableHttpTransport::new_client("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+
+        client
+            .cmd_tx
+            .send(TransportCommand::SendMessage(request(1, "initialize")))
+            .await
+            .unwrap();
+        let (session_id, _) = next_session_message(&server).await;
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(session_id, response(1)))
+            .await
+            .unwrap();
+
+        for i in 0..20 {
+            let notification = JsonRpcMessage::Notification(JsonRpcNotification {
+                jsonrpc: "2.0".to_string(),
+                method: format!("note/{}", i),
+                params: None,
+            });
+            client
+                .cmd_tx
+                .send(TransportCommand::SendMessage(notification))
+                .await
+                .unwrap();
+        }
+        for i in 0..20 {
+            match next_session_message(&server).await {
+                (_, JsonRpcMessage::Notification(n)) => assert_eq!(n.method, format!("note/{}", i)),
+                (_, other) => panic!("Expected notification, got {:?}", other),
+            }
+        }
+
+        server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_answers_malformed_posts() {
+        let port = free_port();
+        let _server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let url = format!("http://127.0.0.1:{}/mcp", port);
+        let client = reqwest::Client::new();
+        for (body, code) in [("{not json", -32700), (r#"{"hello": "world"}"#, -32600)] {
+            let response = client.post(&url).body(body).send().await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+            let error: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(error["id"], serde_json::Value::Null);
+            assert_eq!(error["error"]["code"], code);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+use super::{
+    stream::{self, Listener},
+    Transport, TransportChannels,
+};
+use crate::error::McpError;
+
+// TCP Transport Implementation
+//
+// Newline-delimited JSON over plain TCP. The server treats every accepted
+// connection as its own session.
+pub struct TcpTransport {
+    host: String,
+    port: u16,
+    client_mode: bool,
+    buffer_size: usize,
+}
+
+impl TcpTransport {
+    pub fn new_server(host: String, port: u16, buffer_size: usize) -> Self {
+        Self {
+            host,
+            port,
+            client_mode: false,
+            buffer_size,
+        }
+    }
+
+    pub fn new_client(host: String, port: u16, buffer_size: usize) -> Self {
+        Self {
+            host,
+            port,
+            client_mode: true,
+            buffer_size,
+        }
+    }
+}
+
+#[async_trait]
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    async fn accept(&mut self) -> std::io::Result<TcpStream> {
+        let (stream, addr) = TcpListener::accept(self).await?;
+        tracing::debug!("Accepted TCP connection from {}", addr);
+        Ok(stream)
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn start(&mut self) -> Result<TransportChannels, McpError> {
+        let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
+        let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
+        let addr = (self.host.as_str(), self.port);
+
+        if self.client_mode {
+            let stream = TcpStream::connect(addr).await.map_err(|e| {
+                tracing::error!("Failed to connect to {}:{}: {:?}", self.host, self.port, e);
+                McpError::ConnectionClosed
+            })?;
+            let (reader, writer) = stream.into_split();
+            tokio::spawn(stream::run(reader, writer, cmd_rx, event_tx));
+        } else {
+            let listener = TcpListener::bind(addr).await.map_err(|e| {
+                tracing::error!("Failed to bind {}:{}: {:?}", self.host, self.port, e);
+                McpError::ConnectionClosed
+            })?;
+            tracing::debug!("TCP server listening on {}", listener.local_addr()?);
+            tokio::spawn(stream::serve(listener, self.buffer_size, cmd_rx, event_tx));
+        }
+
+        let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
+        Ok(TransportChannels { cmd_tx, event_rx })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{free_port, next_event, notification},
+        transport::{JsonRpcMessage, TransportCommand, TransportEvent},
+    };
+
+    #[tokio::test]
+    async fn test_tcp_sessions() -> Result<(), McpError> {
+        let port = free_port();
+        let server = TcpTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+        let first = TcpTransport::new_client("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+        let second = TcpTransport::new_client("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+
+        first
+            .cmd_tx
+            .send(TransportCommand::SendMessage(notification("first")))
+            .await
+            .unwrap();
+        let first_id = match next_event(&server).await {
+            Some(TransportEvent::SessionMessage(id, _)) => id,
+            other => panic!("Expected session message, got {:?}", other),
+        };
+        second
+            .cmd_tx
+            .send(TransportCommand::SendMessage(notification("second")))
+            .await
+            .unwrap();
+        assert!(matches!(
+            next_event(&server).await,
+            Some(TransportEvent::SessionMessage(id, _)) if id != first_id
+        ));
+
+        // Replies reach only the addressed connection
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(first_id.clone(), notification("to/first")))
+            .await
+            .unwrap();
+        server
+            .cmd_tx
+            .send(TransportCommand::SendMessage(notification("to/everyone")))
+            .await
+            .unwrap();
+        for (channels, expected) in [
+            (&first, "to/first"),
+            (&first, "to/everyone"),
+            (&second, "to/everyone"),
+        ] {
+            match next_event(channels).await {
+                Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                    assert_eq!(n.method, expected)
+                }
+                other => panic!("Expected {}, got {:?}", expected, other),
+            }
+        }
+
+        // A client hanging up ends its session
+        first.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        match next_event(&server).await {
+            Some(TransportEvent::SessionClosed(id)) => assert_eq!(id, first_id),
+            other => panic!("Expected session close, got {:?}", other),
+        }
+
+        // Stopping the server disconnects the remaining clients
+        server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        assert!(matches!(next_event(&server).await, Some(TransportEvent::Closed)));
+        assert!(matches!(next_event(&second).await, Some(TransportEvent::Closed)));
+
+        Ok(())
+    }
+}
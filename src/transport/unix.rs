@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use std::{path::PathBuf, sync::Arc};
+use tokio::{
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+
+use super::{
+    stream::{self, Listener},
+    Transport, TransportChannels,
+};
+use crate::error::McpError;
+
+// Unix Domain Socket Transport Implementation
+//
+// Newline-delimited JSON over a socket file, for local daemons. The server
+// treats every accepted connection as its own session.
+pub struct UnixSocketTransport {
+    path: PathBuf,
+    client_mode: bool,
+    buffer_size: usize,
+}
+
+impl UnixSocketTransport {
+    pub fn new_server(path: impl Into<PathBuf>, buffer_size: usize) -> Self {
+        Self {
+            path: path.into(),
+            client_mode: false,
+            buffer_size,
+        }
+    }
+
+    pub fn new_client(path: impl Into<PathBuf>, buffer_size: usize) -> Self {
+        Self {
+            path: path.into(),
+            client_mode: true,
+            buffer_size,
+        }
+    }
+}
+
+/// Removes the socket file once the server stops listening
+struct SocketFile {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[async_trait]
+impl Listener for SocketFile {
+    type Stream = UnixStream;
+
+    async fn accept(&mut self) -> std::io::Result<UnixStream> {
+        let (stream, _) = self.listener.accept().await?;
+        Ok(stream)
+    }
+}
+
+#[async_trait]
+impl Transport for UnixSocketTransport {
+    async fn start(&mut self) -> Result<TransportChannels, McpError> {
+        let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
+        let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
+
+        if self.client_mode {
+            let stream = UnixStream::connect(&self.path).await.map_err(|e| {
+                tracing::error!("Failed to connect to {}: {:?}", self.path.display(), e);
+                McpError::ConnectionClosed
+            })?;
+            let (reader, writer) = stream.into_split();
+            tokio::spawn(stream::run(reader, writer, cmd_rx, event_tx));
+        } else {
+            // A socket file left behind by a previous run would make bind fail
+            match std::fs::remove_file(&self.path) {
+                Ok(()) => tracing::debug!("Removed stale socket {}", self.path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            let listener = UnixListener::bind(&self.path).map_err(|e| {
+                tracing::error!("Failed to bind {}: {:?}", self.path.display(), e);
+                McpError::ConnectionClosed
+            })?;
+            tracing::debug!("Unix socket server listening on {}", self.path.display());
+
+            let listener = SocketFile {
+                listener,
+                path: self.path.clone(),
+            };
+            tokio::spawn(stream::serve(listener, self.buffer_size, cmd_rx, event_tx));
+        }
+
+        let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
+        Ok(TransportChannels { cmd_tx, event_rx })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        protocol::JsonRpcNotification,
+        testing::next_event,
+        transport::{JsonRpcMessage, TransportCommand, TransportEvent},
+    };
+
+    #[tokio::test]
+    async fn test_unix_socket_round_trip() -> Result<(), McpError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("mcp.sock");
+
+        let server = UnixSocketTransport::new_server(&path, 32).start().await?;
+        let client = UnixSocketTransport::new_client(&path, 32).start().await?;
+
+        client
+            .cmd_tx
+            .send(TransportCommand::SendMessage(JsonRpcMessage::Notification(
+                JsonRpcNotification {
+                    jsonrpc: "2.0".to_string(),
+                    method: "hello".to_string(),
+                    params: None,
+                },
+            )))
+            .await
+            .unwrap();
+        let (session_id, msg) = match next_event(&server).await {
+            Some(TransportEvent::SessionMessage(id, msg)) => (id, msg),
+            other => panic!("Expected session message, got {:?}", other),
+        };
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(session_id, msg))
+            .await
+            .unwrap();
+        match next_event(&client).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "hello")
+            }
+            other => panic!("Expected echoed notification, got {:?}", other),
+        }
+
+        // The socket file goes away with the server
+        server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        assert!(matches!(next_event(&server).await, Some(TransportEvent::Closed)));
+        assert!(matches!(next_event(&client).await, Some(TransportEvent::Closed)));
+        assert!(!path.exists());
+
+        Ok(())
+    }
+}
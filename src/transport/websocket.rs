@@ -0,0 +1,422 @@
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use std::{net::IpAddr, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::{
+    client::IntoClientRequest, http::HeaderValue, Message as ClientMessage,
+};
+use warp::{
+    ws::{Message, WebSocket},
+    Filter,
+};
+
+use super::{
+    cors::with_cors, parse_message, session::SessionRegistry, AllowedOrigins, JsonRpcMessage,
+    SessionId, Transport, TransportChannels, TransportCommand, TransportEvent,
+};
+use crate::{
+    auth::{self, BearerAuth},
+    error::McpError,
+};
+
+/// How long the client waits for the server to finish the close handshake.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// WebSocket Transport Implementation
+//
+// Every JSON-RPC message travels as a single text frame on `ws://host:port/ws`.
+// Each server-side connection is its own session. Pings are answered by the
+// underlying tungstenite stream while it is polled.
+pub struct WebSocketTransport {
+    host: String,
+    port: u16,
+    client_mode: bool,
+    buffer_size: usize,
+    auth: Option<BearerAuth>,
+    origins: AllowedOrigins,
+    bearer_token: Option<String>,
+}
+
+impl WebSocketTransport {
+    pub fn new_server(host: String, port: u16, buffer_size: usize) -> Self {
+        Self {
+            host,
+            port,
+            client_mode: false,
+            buffer_size,
+            auth: None,
+            origins: AllowedOrigins::default(),
+            bearer_token: None,
+        }
+    }
+
+    pub fn new_client(host: String, port: u16, buffer_size: usize) -> Self {
+        Self {
+            host,
+            port,
+            client_mode: true,
+            buffer_size,
+            auth: None,
+            origins: AllowedOrigins::default(),
+            bearer_token: None,
+        }
+    }
+
+    /// Require clients to present a bearer token `auth` accepts. Others get 401 or 403.
+    pub fn with_auth(mut self, auth: BearerAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Only accept browser requests from `origins`. By default only loopback
+    /// origins are accepted; with `AllowedOrigins::any()` the server only
+    /// listens on loopback.
+    pub fn with_allowed_origins(mut self, origins: AllowedOrigins) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    /// Send `token` in an `Authorization: Bearer` header when connecting as a client
+    pub fn with_bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    async fn run_server(
+        host: String,
+        port: u16,
+        buffer_size: usize,
+        auth: Option<BearerAuth>,
+        origins: AllowedOrigins,
+        mut cmd_rx: mpsc::Receiver<TransportCommand>,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) {
+        let ip = match host.parse::<IpAddr>() {
+            Ok(ip) => origins.bind_ip(ip),
+            Err(e) => {
+                tracing::error!("Invalid WebSocket host {}: {}", host, e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::InvalidRequest(format!(
+                        "Invalid host: {}",
+                        host
+                    ))))
+                    .await;
+                return;
+            }
+        };
+
+        // Every connection is a session with its own outgoing queue
+        let sessions = SessionRegistry::default();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let ws_route = warp::path("ws").and(warp::ws()).map({
+            let sessions = sessions.clone();
+            let event_tx = event_tx.clone();
+            move |ws: warp::ws::Ws| {
+                let sessions = sessions.clone();
+                let event_tx = event_tx.clone();
+                let shutdown_rx = shutdown_rx.clone();
+                ws.on_upgrade(move |socket| async move {
+                    let (session_id, outgoing) = sessions.open(buffer_size).await;
+                    tracing::debug!("WebSocket session {} connected", session_id);
+                    Self::handle_connection(
+                        socket,
+                        session_id.clone(),
+                        event_tx.clone(),
+                        outgoing,
+                        shutdown_rx,
+                    )
+                    .await;
+                    if sessions.close(&session_id).await {
+                        let _ = event_tx.send(TransportEvent::SessionClosed(session_id)).await;
+                    }
+                })
+            }
+        });
+
+        // The origin and token are checked on the upgrade request, before any frames flow
+        let routes = with_cors(
+            origins,
+            auth::require_bearer(auth).and(ws_route).recover(auth::recover),
+        );
+
+        let mut server_shutdown = shutdown_tx.subscribe();
+        let server = match warp::serve(routes).try_bind_with_graceful_shutdown(
+            (ip, port),
+            async move {
+                let _ = server_shutdown.changed().await;
+            },
+        ) {
+            Ok((addr, server)) => {
+                tracing::debug!("WebSocket server listening on {}", addr);
+                tokio::spawn(server)
+            }
+            Err(e) => {
+                tracing::error!("Failed to bind WebSocket server: {:?}", e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::ConnectionClosed))
+                    .await;
+                return;
+            }
+        };
+
+        // Message forwarding loop
+        while let Some(cmd) = cmd_rx.recv().await {
+            match cmd {
+                TransportCommand::SendMessage(msg) => sessions.broadcast(msg).await,
+                TransportCommand::SendTo(session_id, msg) => {
+                    if let Err(e) = sessions.send_to(&session_id, msg).await {
+                        tracing::debug!("Failed to send to session {}: {:?}", session_id, e);
+                    }
+                }
+                TransportCommand::Close => break,
+            }
+        }
+
+        // Close every connection, then stop accepting new ones
+        let _ = shutdown_tx.send(true);
+        let _ = server.await;
+        let _ = event_tx.send(TransportEvent::Closed).await;
+    }
+
+    async fn handle_connection(
+        socket: WebSocket,
+        session_id: SessionId,
+        event_tx: mpsc::Sender<TransportEvent>,
+        mut outgoing: mpsc::Receiver<JsonRpcMessage>,
+        mut shutdown_rx: watch::Receiver<bool>,
+    ) {
+        let (mut sink, mut stream) = socket.split();
+
+        loop {
+            tokio::select! {
+                frame = stream.next() => match frame {
+                    Some(Ok(frame)) if frame.is_text() => {
+                        let text = frame.to_str().unwrap_or_default();
+                        let event = match parse_message(text) {
+                            Ok(msg) => TransportEvent::SessionMessage(session_id.clone(), msg),
+                            Err(e) => TransportEvent::Malformed(Some(session_id.clone()), e),
+                        };
+                        if event_tx.send(event).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(frame)) if frame.is_close() => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        tracing::error!("WebSocket read error: {:?}", e);
+                        break;
+                    }
+                    None => break,
+                },
+                msg = outgoing.recv() => match msg {
+                    Some(msg) => match serde_json::to_string(&msg) {
+                        Ok(text) => {
+                            if let Err(e) = sink.send(Message::text(text)).await {
+                                tracing::error!("WebSocket write error: {:?}", e);
+                                break;
+                            }
+                        }
+                        Err(e) => tracing::error!("Failed to serialize message: {:?}", e),
+                    },
+                    None => break,
+                },
+                _ = shutdown_rx.changed() => {
+                    let _ = sink.send(Message::close()).await;
+                    break;
+                }
+            }
+        }
+
+        let _ = sink.close().await;
+    }
+
+    async fn run_client(
+        host: String,
+        port: u16,
+        bearer_token: Option<String>,
+        mut cmd_rx: mpsc::Receiver<TransportCommand>,
+        event_tx: mpsc::Sender<TransportEvent>,
+    ) {
+        let url = format!("ws://{}:{}/ws", host, port);
+        tracing::debug!("Connecting to WebSocket endpoint: {}", url);
+
+        let mut request = match url.as_str().into_client_request() {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::error!("Invalid WebSocket URL {}: {:?}", url, e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::ConnectionClosed))
+                    .await;
+                return;
+            }
+        };
+        if let Some(token) = bearer_token {
+            match HeaderValue::from_str(&format!("Bearer {}", token)) {
+                Ok(value) => {
+                    request.headers_mut().insert("Authorization", value);
+                }
+                Err(e) => tracing::error!("Bearer token is not a valid header value: {}", e),
+            }
+        }
+
+        let socket = match tokio_tungstenite::connect_async(request).await {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                tracing::error!("Failed to connect to {}: {:?}", url, e);
+                let _ = event_tx
+                    .send(TransportEvent::Error(McpError::ConnectionClosed))
+                    .await;
+                return;
+            }
+        };
+        let (mut sink, mut stream) = socket.split();
+
+        // Message receiving task
+        let mut reader_handle = tokio::spawn({
+            let event_tx = event_tx.clone();
+            async move {
+                while let Some(frame) = stream.next().await {
+                    match frame {
+                        Ok(ClientMessage::Text(text)) => {
+                            let event = match parse_message(&text) {
+                                Ok(msg) => TransportEvent::Message(msg),
+                                Err(e) => TransportEvent::Malformed(None, e),
+                            };
+                            if event_tx.send(event).await.is_err() {
+                                break;
+                            }
+                        }
+                        Ok(ClientMessage::Close(_)) => break,
+                        Ok(_) => continue,
+                        Err(e) => {
+                            tracing::error!("WebSocket read error: {:?}", e);
+                            break;
+                        }
+                    }
+                }
+                let _ = event_tx.send(TransportEvent::Closed).await;
+            }
+        });
+
+        // Message sending loop
+        loop {
+            tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(TransportCommand::SendMessage(msg) | TransportCommand::SendTo(_, msg)) => match serde_json::to_string(&msg) {
+                        Ok(text) => {
+                            if let Err(e) = sink.send(ClientMessage::Text(text)).await {
+                                tracing::error!("WebSocket write error: {:?}", e);
+                                break;
+                            }
+                        }
+                        Err(e) => tracing::error!("Failed to serialize message: {:?}", e),
+                    },
+                    Some(TransportCommand::Close) | None => {
+                        let _ = sink.send(ClientMessage::Close(None)).await;
+                        break;
+                    }
+                },
+                // The server went away; the reader already reported it
+                _ = &mut reader_handle => return,
+            }
+        }
+
+        // Wait for the server to acknowledge the close frame
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut reader_handle)
+            .await
+            .is_err()
+        {
+            reader_handle.abort();
+            let _ = event_tx.send(TransportEvent::Closed).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn start(&mut self) -> Result<TransportChannels, McpError> {
+        let (cmd_tx, cmd_rx) = mpsc::channel(self.buffer_size);
+        let (event_tx, event_rx) = mpsc::channel(self.buffer_size);
+
+        if self.client_mode {
+            tokio::spawn(Self::run_client(
+                self.host.clone(),
+                self.port,
+                self.bearer_token.clone(),
+                cmd_rx,
+                event_tx,
+            ));
+        } else {
+            tokio::spawn(Self::run_server(
+                self.host.clone(),
+                self.port,
+                self.buffer_size,
+                self.auth.clone(),
+                self.origins.clone(),
+                cmd_rx,
+                event_tx,
+            ));
+        }
+
+        let event_rx = Arc::new(tokio::sync::Mutex::new(event_rx));
+
+        Ok(TransportChannels { cmd_tx, event_rx })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{free_port, next_event, notification};
+
+    #[tokio::test]
+    async fn test_websocket_round_trip() -> Result<(), McpError> {
+        let port = free_port();
+        let server = WebSocketTransport::new_server("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+
+        // Give the listener a moment to bind
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let client = WebSocketTransport::new_client("127.0.0.1".to_string(), port, 32)
+            .start()
+            .await?;
+
+        client
+            .cmd_tx
+            .send(TransportCommand::SendMessage(notification("client/hello")))
+            .await
+            .unwrap();
+        let session_id = match next_event(&server).await {
+            Some(TransportEvent::SessionMessage(session_id, JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "client/hello");
+                session_id
+            }
+            other => panic!("Expected notification on server, got {:?}", other),
+        };
+
+        server
+            .cmd_tx
+            .send(TransportCommand::SendTo(session_id, notification("server/hello")))
+            .await
+            .unwrap();
+        match next_event(&client).await {
+            Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => {
+                assert_eq!(n.method, "server/hello")
+            }
+            other => panic!("Expected notification on client, got {:?}", other),
+        }
+
+        // Closing the server sends a close frame to the client
+        server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+        assert!(matches!(next_event(&client).await, Some(TransportEvent::Closed)));
+        assert!(matches!(
+            next_event(&server).await,
+            Some(TransportEvent::SessionClosed(_))
+        ));
+        assert!(matches!(next_event(&server).await, Some(TransportEvent::Closed)));
+
+        Ok(())
+    }
+}
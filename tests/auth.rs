@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use mcp_rs::{
+    auth::{BearerAuth, TokenClaims},
+    testing::{free_port, next_event, notification},
+    transport::{
+        JsonRpcMessage, SseTransport, StreamableHttpTransport, Transport, TransportChannels,
+        TransportCommand, TransportEvent, WebSocketTransport,
+    },
+};
+
+const SECRET: &str = "s3cret";
+const SIGNING_KEY: &str = "signing-key";
+
+fn auth() -> BearerAuth {
+    BearerAuth::new()
+        .with_static_token(SECRET)
+        .with_signing_key(SIGNING_KEY)
+        .with_required_scopes(vec!["mcp".to_string()])
+}
+
+/// Send a notification from `client` and check that it reaches `server`
+async fn assert_delivered(client: &TransportChannels, server: &TransportChannels) {
+    client
+        .cmd_tx
+        .send(TransportCommand::SendMessage(notification("hello")))
+        .await
+        .unwrap();
+    match next_event(server).await {
+        Some(TransportEvent::SessionMessage(_, JsonRpcMessage::Notification(n))) => {
+            assert_eq!(n.method, "hello")
+        }
+        other => panic!("Expected notification, got {:?}", other),
+    }
+}
+
+/// GET `url` with `authorization`, returning the status and `WWW-Authenticate` challenge
+async fn get(url: &str, authorization: Option<&str>) -> (u16, Option<String>) {
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    let response = request.send().await.unwrap();
+    let challenge = response
+        .headers()
+        .get("WWW-Authenticate")
+        .map(|value| value.to_str().unwrap().to_string());
+    (response.status().as_u16(), challenge)
+}
+
+#[tokio::test]
+async fn test_sse_requires_bearer_token() {
+    let port = free_port();
+    let server = SseTransport::new_server("127.0.0.1".to_string(), port, 32)
+        .with_auth(auth())
+        .start()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let url = format!("http://127.0.0.1:{}/sse", port);
+
+    let (status, challenge) = get(&url, None).await;
+    assert_eq!(status, 401);
+    assert_eq!(challenge.as_deref(), Some("Bearer"));
+
+    let (status, challenge) = get(&url, Some("Bearer wrong")).await;
+    assert_eq!(status, 401);
+    assert!(challenge.unwrap().contains("invalid_token"));
+
+    let expired = auth()
+        .issue(&TokenClaims {
+            exp: Some(1),
+            scopes: vec!["mcp".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+    let (status, _) = get(&url, Some(&format!("Bearer {}", expired))).await;
+    assert_eq!(status, 401);
+
+    let unscoped = auth().issue(&TokenClaims::default()).unwrap();
+    let (status, challenge) = get(&url, Some(&format!("Bearer {}", unscoped))).await;
+    assert_eq!(status, 403);
+    assert!(challenge.unwrap().contains("insufficient_scope"));
+
+    // The message endpoint is guarded too
+    let response = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{}/message/unknown", port))
+        .json(&notification("hello"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Clients with a valid token, static or signed, get through
+    let client = SseTransport::new_client("127.0.0.1".to_string(), port, 32)
+        .with_bearer_token(SECRET)
+        .start()
+        .await
+        .unwrap();
+    assert_delivered(&client, &server).await;
+
+    let signed = auth()
+        .issue(&TokenClaims {
+            sub: Some("alice".to_string()),
+            scopes: vec!["mcp".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+    let client = SseTransport::new_client("127.0.0.1".to_string(), port, 32)
+        .with_bearer_token(signed)
+        .start()
+        .await
+        .unwrap();
+    assert_delivered(&client, &server).await;
+
+    server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_websocket_and_http_require_bearer_token() {
+    let ws_port = free_port();
+    let ws_server = WebSocketTransport::new_server("127.0.0.1".to_string(), ws_port, 32)
+        .with_auth(auth())
+        .start()
+        .await
+        .unwrap();
+    let http_port = free_port();
+    let http_server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), http_port, 32)
+        .with_auth(auth())
+        .start()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The WebSocket upgrade is refused before any frames flow
+    let (status, _) = get(&format!("http://127.0.0.1:{}/ws", ws_port), None).await;
+    assert_eq!(status, 401);
+    let (status, _) = get(&format!("http://127.0.0.1:{}/mcp", http_port), None).await;
+    assert_eq!(status, 401);
+
+    let client = WebSocketTransport::new_client("127.0.0.1".to_string(), ws_port, 32)
+        .with_bearer_token(SECRET)
+        .start()
+        .await
+        .unwrap();
+    assert_delivered(&client, &ws_server).await;
+
+    ws_server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+    http_server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+}
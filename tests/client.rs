@@ -1,43 +1,242 @@
+use std::{sync::Arc, time::Duration};
+use async_trait::async_trait;
+use eventsource_stream::Eventsource;
 use futures::StreamExt;
-use reqwest::Client;
-use reqwest_eventsource::{Event, EventSource};
-use serde_json::json;
+
+use mcp_rs::{
+    client::{Client, ClientInfo},
+    error::McpError,
+    protocol::LATEST_PROTOCOL_VERSION,
+    server::{config::ServerConfig, ClientPeer, McpServer},
+    testing::{free_port, next_event, notification, TestHarness},
+    tools::{AutoApprove, Tool, ToolContent, ToolInputSchema, ToolProvider, ToolResult},
+    transport::{
+        JsonRpcMessage, SessionId, SseTransport, Transport, TransportChannels, TransportCommand,
+        TransportEvent,
+    },
+};
+
+async fn next_method(channels: &TransportChannels) -> String {
+    match next_event(channels).await {
+        Some(TransportEvent::Message(JsonRpcMessage::Notification(n))) => n.method,
+        other => panic!("Expected notification, got {:?}", other),
+    }
+}
+
+async fn next_session(channels: &TransportChannels) -> (SessionId, String) {
+    match next_event(channels).await {
+        Some(TransportEvent::SessionMessage(id, JsonRpcMessage::Notification(n))) => (id, n.method),
+        other => panic!("Expected session notification, got {:?}", other),
+    }
+}
 
 #[tokio::test]
 async fn test_sse_client() {
-    // Create EventSource client
-    let url = "http://127.0.0.1:3000/events";
-    let request_builder = Client::new().get(url);
-    let mut event_source = EventSource::new(request_builder).unwrap();
-
-    // Send a test request using regular HTTP client
-    let client = Client::new();
-    let response = client.post("http://127.0.0.1:3000/rpc")
-        .json(&json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "resources/list",
-            "params": { "cursor": null }
-        }))
+    let port = free_port();
+    let server = SseTransport::new_server("127.0.0.1".to_string(), port, 32)
+        .start()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let alice = SseTransport::new_client("127.0.0.1".to_string(), port, 32)
+        .start()
+        .await
+        .unwrap();
+    let bob = SseTransport::new_client("127.0.0.1".to_string(), port, 32)
+        .start()
+        .await
+        .unwrap();
+
+    alice
+        .cmd_tx
+        .send(TransportCommand::SendMessage(notification("alice/hello")))
+        .await
+        .unwrap();
+    let (alice_id, method) = next_session(&server).await;
+    assert_eq!(method, "alice/hello");
+
+    bob.cmd_tx
+        .send(TransportCommand::SendMessage(notification("bob/hello")))
+        .await
+        .unwrap();
+    let (bob_id, method) = next_session(&server).await;
+    assert_eq!(method, "bob/hello");
+    assert_ne!(alice_id, bob_id);
+
+    // Targeted messages only reach their own session
+    server
+        .cmd_tx
+        .send(TransportCommand::SendTo(alice_id, notification("to/alice")))
+        .await
+        .unwrap();
+    server
+        .cmd_tx
+        .send(TransportCommand::SendTo(bob_id, notification("to/bob")))
+        .await
+        .unwrap();
+
+    // Broadcasts reach every session
+    server
+        .cmd_tx
+        .send(TransportCommand::SendMessage(notification("to/everyone")))
+        .await
+        .unwrap();
+
+    assert_eq!(next_method(&alice).await, "to/alice");
+    assert_eq!(next_method(&alice).await, "to/everyone");
+    assert_eq!(next_method(&bob).await, "to/bob");
+    assert_eq!(next_method(&bob).await, "to/everyone");
+
+    server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+    loop {
+        match next_event(&server).await {
+            Some(TransportEvent::Closed) => break,
+            Some(TransportEvent::SessionClosed(_)) => continue,
+            other => panic!("Expected server to close, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_sse_server_reports_malformed_posts() {
+    let port = free_port();
+    let server = SseTransport::new_server("127.0.0.1".to_string(), port, 32)
+        .start()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let response = reqwest::get(format!("http://127.0.0.1:{}/sse", port))
+        .await
+        .unwrap();
+    let mut events = response.bytes_stream().eventsource();
+    let endpoint = events.next().await.unwrap().unwrap();
+    assert_eq!(endpoint.event, "endpoint");
+    let endpoint: serde_json::Value = serde_json::from_str(&endpoint.data).unwrap();
+    let endpoint = endpoint["endpoint"].as_str().unwrap().to_string();
+
+    // The protocol answers malformed posts on the stream, so they are accepted
+    let response = reqwest::Client::new()
+        .post(&endpoint)
+        .body("{not json")
         .send()
         .await
         .unwrap();
+    assert_eq!(response.status().as_u16(), 202);
+    match next_event(&server).await {
+        Some(TransportEvent::Malformed(Some(session_id), McpError::ParseError)) => {
+            assert!(endpoint.ends_with(&session_id))
+        }
+        other => panic!("Expected malformed input, got {:?}", other),
+    }
 
-    println!("Response: {:?}", response.text().await.unwrap());
+    server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+}
+
+// Reports the protocol version the server negotiated with the calling client
+struct VersionTool;
 
-    // Listen for SSE events
-    while let Some(event) = event_source.next().await {
-        match event {
-            Ok(Event::Message(message)) => {
-                println!("Received: {}", message.data);
-            }
-            Ok(Event::Open) => {
-                println!("Connection opened");
-            }
-            Err(error) => {
-                println!("Error: {}", error);
-                break;
-            }
+#[async_trait]
+impl ToolProvider for VersionTool {
+    async fn get_tool(&self) -> Tool {
+        Tool {
+            name: "version".to_string(),
+            description: "Negotiated protocol version".to_string(),
+            input_schema: ToolInputSchema {
+                schema_type: "object".to_string(),
+                properties: Default::default(),
+                required: vec![],
+            },
         }
     }
+
+    async fn execute(&self, _arguments: serde_json::Value) -> Result<ToolResult, McpError> {
+        Err(McpError::InvalidRequest("Needs a client".to_string()))
+    }
+
+    async fn execute_with_client(
+        &self,
+        _arguments: serde_json::Value,
+        client: ClientPeer,
+    ) -> Result<ToolResult, McpError> {
+        Ok(ToolResult {
+            content: vec![ToolContent::Text {
+                text: client.protocol_version().unwrap_or_default().to_string(),
+            }],
+            is_error: false,
+        })
+    }
+}
+
+async fn versioned_server(versions: Option<&[&str]>) -> McpServer {
+    let mut server = McpServer::new(ServerConfig::default()).await;
+    if let Some(versions) = versions {
+        server.set_supported_versions(versions.iter().map(|v| v.to_string()).collect());
+    }
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
+    server.tool_manager.register_tool(Arc::new(VersionTool)).await;
+    server
+}
+
+fn versioned_client(versions: &[&str]) -> Client {
+    let mut client = Client::new();
+    client.set_supported_versions(versions.iter().map(|v| v.to_string()).collect());
+    client
+}
+
+fn client_info() -> ClientInfo {
+    ClientInfo {
+        name: "versioned-client".to_string(),
+        version: "1.0.0".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_version_negotiation() -> Result<(), McpError> {
+    // Both sides on the newest version
+    let harness = TestHarness::start(versioned_server(None).await).await?;
+    assert_eq!(
+        harness.client.protocol_version().await.as_deref(),
+        Some(LATEST_PROTOCOL_VERSION)
+    );
+    harness
+        .assert_tool_text("version", serde_json::json!({}), LATEST_PROTOCOL_VERSION)
+        .await;
+    harness.shutdown().await?;
+
+    // An older server answers with its own version, which the client also speaks
+    let harness = TestHarness::start_with_client(
+        versioned_server(Some(&["2024-11-05"])).await,
+        versioned_client(&["2024-11-05", "2025-06-18"]),
+        client_info(),
+    )
+    .await?;
+    assert_eq!(harness.client.protocol_version().await.as_deref(), Some("2024-11-05"));
+    harness
+        .assert_tool_text("version", serde_json::json!({}), "2024-11-05")
+        .await;
+    harness.shutdown().await?;
+
+    // No version in common
+    let result = TestHarness::start_with_client(
+        versioned_server(Some(&["2024-11-05"])).await,
+        versioned_client(&["2025-06-18"]),
+        client_info(),
+    )
+    .await;
+    match result {
+        Err(McpError::InvalidRequest(message)) => assert!(message.contains("2024-11-05")),
+        Err(e) => panic!("Expected InvalidRequest, got {:?}", e),
+        Ok(_) => panic!("Expected initialization to fail"),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ping() -> Result<(), McpError> {
+    let harness = TestHarness::start(McpServer::new(ServerConfig::default()).await).await?;
+    harness.client.ping().await?;
+    harness.shutdown().await
 }
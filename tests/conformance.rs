@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use mcp_rs::{
+    error::McpError,
+    logging::{LogMessage, SetLevelRequest},
+    prompts::{ListPromptsResponse, PromptResult},
+    protocol::{CancelledNotification, ProgressNotification},
+    resource::{ListResourcesResponse, ListTemplatesResponse, ReadResourceResponse},
+    server::{config::ServerConfig, McpServer},
+    testing::{next_message, send_raw},
+    tools::{CallToolRequest, ListToolsResponse, ToolResult},
+    transport::{InMemoryTransport, Transport, TransportChannels},
+    types::{InitializeParams, InitializeResult},
+};
+
+// The examples below are taken from the MCP specification
+
+/// Parse `example` as `T` and check that it serializes back unchanged
+fn assert_round_trip<T: Serialize + DeserializeOwned>(example: Value) {
+    let parsed: T = serde_json::from_value(example.clone())
+        .unwrap_or_else(|e| panic!("Failed to parse {}: {}", example, e));
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), example);
+}
+
+#[test]
+fn test_initialize_wire_format() {
+    assert_round_trip::<InitializeParams>(json!({
+        "protocolVersion": "2024-11-05",
+        "capabilities": {
+            "roots": { "listChanged": true },
+            "sampling": {}
+        },
+        "clientInfo": { "name": "ExampleClient", "version": "1.0.0" }
+    }));
+    assert_round_trip::<InitializeResult>(json!({
+        "protocolVersion": "2024-11-05",
+        "capabilities": {
+            "logging": {},
+            "prompts": { "listChanged": true },
+            "resources": { "subscribe": true, "listChanged": true },
+            "tools": { "listChanged": true }
+        },
+        "serverInfo": { "name": "ExampleServer", "version": "1.0.0" }
+    }));
+}
+
+#[test]
+fn test_tools_wire_format() {
+    assert_round_trip::<ListToolsResponse>(json!({
+        "tools": [{
+            "name": "get_weather",
+            "description": "Get current weather information for a location",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "location": { "type": "string", "description": "City name or zip code" }
+                },
+                "required": ["location"]
+            }
+        }],
+        "nextCursor": "next-page-cursor"
+    }));
+    assert_round_trip::<CallToolRequest>(json!({
+        "name": "get_weather",
+        "arguments": { "location": "New York" }
+    }));
+    assert_round_trip::<ToolResult>(json!({
+        "content": [
+            { "type": "text", "text": "Current weather in New York: 72°F" },
+            { "type": "image", "data": "base64-encoded-data", "mimeType": "image/png" },
+            {
+                "type": "resource",
+                "resource": { "uri": "resource://example", "mimeType": "text/plain", "text": "Resource content" }
+            }
+        ],
+        "isError": false
+    }));
+
+    // isError is optional
+    let result: ToolResult = serde_json::from_value(json!({ "content": [] })).unwrap();
+    assert!(!result.is_error);
+}
+
+#[test]
+fn test_resources_wire_format() {
+    assert_round_trip::<ListResourcesResponse>(json!({
+        "resources": [{
+            "uri": "file:///project/src/main.rs",
+            "name": "main.rs",
+            "description": "Primary application entry point",
+            "mimeType": "text/x-rust"
+        }],
+        "nextCursor": "next-page-cursor"
+    }));
+    assert_round_trip::<ReadResourceResponse>(json!({
+        "contents": [
+            { "uri": "file:///project/src/main.rs", "mimeType": "text/x-rust", "text": "fn main() {}" },
+            { "uri": "file:///project/logo.png", "mimeType": "image/png", "blob": "base64-encoded-data" }
+        ]
+    }));
+    assert_round_trip::<ListTemplatesResponse>(json!({
+        "resourceTemplates": [{
+            "uriTemplate": "file:///{path}",
+            "name": "Project Files",
+            "description": "Access files in the project directory",
+            "mimeType": "application/octet-stream"
+        }]
+    }));
+}
+
+#[test]
+fn test_prompts_wire_format() {
+    assert_round_trip::<ListPromptsResponse>(json!({
+        "prompts": [{
+            "name": "code_review",
+            "description": "Asks the LLM to analyze code quality and suggest improvements",
+            "arguments": [{ "name": "code", "description": "The code to review", "required": true }]
+        }],
+        "nextCursor": "next-page-cursor"
+    }));
+    assert_round_trip::<PromptResult>(json!({
+        "description": "Code review prompt",
+        "messages": [
+            {
+                "role": "user",
+                "content": { "type": "text", "text": "Please review this Python code" }
+            },
+            {
+                "role": "user",
+                "content": {
+                    "type": "resource",
+                    "resource": { "uri": "resource://example", "mimeType": "text/plain", "text": "Resource content" }
+                }
+            }
+        ]
+    }));
+}
+
+#[test]
+fn test_notifications_wire_format() {
+    assert_round_trip::<CancelledNotification>(json!({
+        "requestId": "123",
+        "reason": "User requested cancellation"
+    }));
+    assert_round_trip::<ProgressNotification>(json!({
+        "progressToken": "abc123",
+        "progress": 50.0,
+        "total": 100.0
+    }));
+    assert_round_trip::<SetLevelRequest>(json!({ "level": "info" }));
+    assert_round_trip::<LogMessage>(json!({
+        "level": "error",
+        "logger": "database",
+        "data": { "error": "Connection failed", "details": { "host": "localhost", "port": 5432 } }
+    }));
+}
+
+#[tokio::test]
+async fn test_server_answers_spec_initialize() -> Result<(), McpError> {
+    let server = McpServer::new(ServerConfig::default()).await;
+    let params = serde_json::from_value(json!({
+        "protocolVersion": "2025-03-26",
+        "capabilities": { "roots": { "listChanged": true } },
+        "clientInfo": { "name": "ExampleClient", "version": "1.0.0" }
+    }))
+    .unwrap();
+
+    let result = serde_json::to_value(server.handle_initialize("", params).await?).unwrap();
+    assert_eq!(result["protocolVersion"], "2025-03-26");
+    assert!(result["serverInfo"]["name"].is_string());
+    assert!(result["capabilities"]["tools"]["listChanged"].is_boolean());
+    Ok(())
+}
+
+/// Send `message` as raw JSON-RPC and return the next message that comes back
+async fn exchange(peer: &TransportChannels, message: Value) -> Value {
+    send_raw(peer, message).await;
+    receive(peer).await
+}
+
+async fn receive(peer: &TransportChannels) -> Value {
+    serde_json::to_value(next_message(peer).await).unwrap()
+}
+
+#[tokio::test]
+async fn test_spec_session() -> Result<(), McpError> {
+    let mut server = McpServer::new(ServerConfig::default()).await;
+    let resource_manager = Arc::clone(&server.resource_manager);
+    let (server_transport, mut client_transport) = InMemoryTransport::pair();
+    let server_task = tokio::spawn(async move { server.run_transport(server_transport).await });
+    let peer = client_transport.start().await?;
+
+    let response = exchange(&peer, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "ExampleClient", "version": "1.0.0" }
+        }
+    }))
+    .await;
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+    send_raw(&peer, json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+
+    let response = exchange(&peer, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "resources/subscribe",
+        "params": { "uri": "file:///project/src/main.rs" }
+    }))
+    .await;
+    assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 2, "result": {} }));
+
+    resource_manager
+        .notify_resource_updated("file:///project/src/main.rs")
+        .await?;
+    assert_eq!(
+        receive(&peer).await,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/resources/updated",
+            "params": { "uri": "file:///project/src/main.rs" }
+        })
+    );
+
+    let response = exchange(&peer, json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "resources/unsubscribe",
+        "params": { "uri": "file:///project/src/main.rs" }
+    }))
+    .await;
+    assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 3, "result": {} }));
+
+    // Unsubscribed clients hear nothing more, so the ping answer comes next
+    resource_manager
+        .notify_resource_updated("file:///project/src/main.rs")
+        .await?;
+    let response = exchange(&peer, json!({ "jsonrpc": "2.0", "id": 4, "method": "ping" })).await;
+    assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 4, "result": {} }));
+
+    // The URI goes in an object, not as bare params
+    let response = exchange(&peer, json!({
+        "jsonrpc": "2.0",
+        "id": 5,
+        "method": "resources/subscribe",
+        "params": "file:///project/src/main.rs"
+    }))
+    .await;
+    assert_eq!(response["error"]["code"], -32602);
+
+    server_task.abort();
+    Ok(())
+}
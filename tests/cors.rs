@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use mcp_rs::testing::free_port;
+use mcp_rs::transport::{
+    AllowedOrigins, SseTransport, StreamableHttpTransport, Transport, TransportCommand,
+    WebSocketTransport,
+};
+use reqwest::{Method, Response};
+
+fn origins() -> AllowedOrigins {
+    AllowedOrigins::new(vec![
+        "https://app.example.com".to_string(),
+        "http://localhost:*".to_string(),
+    ])
+}
+
+async fn request(method: Method, url: &str, origin: Option<&str>) -> Response {
+    let mut request = reqwest::Client::new().request(method, url);
+    if let Some(origin) = origin {
+        request = request.header("Origin", origin);
+    }
+    request.send().await.unwrap()
+}
+
+fn allowed_origin(response: &Response) -> Option<&str> {
+    response
+        .headers()
+        .get("Access-Control-Allow-Origin")
+        .map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn test_sse_validates_origin() {
+    let port = free_port();
+    let server = SseTransport::new_server("127.0.0.1".to_string(), port, 32)
+        .with_allowed_origins(origins())
+        .start()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let url = format!("http://127.0.0.1:{}/sse", port);
+
+    // Preflights from allowed origins are answered
+    let response = request(Method::OPTIONS, &url, Some("http://localhost:5173")).await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(allowed_origin(&response), Some("http://localhost:5173"));
+    let allowed_headers = response.headers()["Access-Control-Allow-Headers"]
+        .to_str()
+        .unwrap();
+    assert!(allowed_headers.contains("Mcp-Session-Id"));
+
+    // Disallowed origins are refused before reaching the routes
+    let response = request(Method::OPTIONS, &url, Some("https://evil.com")).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = request(Method::GET, &url, Some("https://evil.com")).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(allowed_origin(&response), None);
+    let response = request(
+        Method::POST,
+        &format!("http://127.0.0.1:{}/message/unknown", port),
+        Some("http://app.example.com.evil.com"),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    // Allowed origins and non-browser clients get through
+    let response = request(Method::GET, &url, Some("https://app.example.com")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(allowed_origin(&response), Some("https://app.example.com"));
+    let response = request(Method::GET, &url, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(allowed_origin(&response), None);
+
+    server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_websocket_and_http_validate_origin() {
+    let ws_port = free_port();
+    let ws_server = WebSocketTransport::new_server("127.0.0.1".to_string(), ws_port, 32)
+        .with_allowed_origins(origins())
+        .start()
+        .await
+        .unwrap();
+    let http_port = free_port();
+    let http_server = StreamableHttpTransport::new_server("127.0.0.1".to_string(), http_port, 32)
+        .with_allowed_origins(origins())
+        .start()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let ws_url = format!("http://127.0.0.1:{}/ws", ws_port);
+    let response = request(Method::GET, &ws_url, Some("https://evil.com")).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let http_url = format!("http://127.0.0.1:{}/mcp", http_port);
+    let response = request(Method::DELETE, &http_url, Some("https://evil.com")).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = request(Method::OPTIONS, &http_url, Some("https://app.example.com")).await;
+    assert_eq!(response.status().as_u16(), 204);
+    // Without a session the route itself answers, with CORS headers added
+    let response = request(Method::DELETE, &http_url, Some("https://app.example.com")).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(allowed_origin(&response), Some("https://app.example.com"));
+    assert_eq!(
+        response.headers()["Access-Control-Expose-Headers"],
+        "Mcp-Session-Id"
+    );
+
+    ws_server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+    http_server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_default_and_unrestricted_origins() {
+    // By default only pages served from this machine are accepted
+    let port = free_port();
+    let server = SseTransport::new_server("127.0.0.1".to_string(), port, 32)
+        .start()
+        .await
+        .unwrap();
+    let any_port = free_port();
+    let any_server = SseTransport::new_server("127.0.0.1".to_string(), any_port, 32)
+        .with_allowed_origins(AllowedOrigins::any())
+        .start()
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let url = format!("http://127.0.0.1:{}/sse", port);
+    let response = request(Method::GET, &url, Some("https://evil.com")).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = request(Method::GET, &url, Some("http://localhost:5173")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(allowed_origin(&response), Some("http://localhost:5173"));
+
+    // Unrestricted servers accept any origin but never let pages read the responses
+    let url = format!("http://127.0.0.1:{}/sse", any_port);
+    let response = request(Method::GET, &url, Some("https://evil.com")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(allowed_origin(&response), None);
+    let response = request(Method::OPTIONS, &url, Some("https://evil.com")).await;
+    assert_eq!(allowed_origin(&response), None);
+
+    server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+    any_server.cmd_tx.send(TransportCommand::Close).await.unwrap();
+}
@@ -1,6 +1,8 @@
 use mcp_rs::{
-    error::McpError, prompts::{Prompt, PromptCapabilities}, protocol::JsonRpcNotification, resource::{FileSystemProvider, ResourceCapabilities}, server::{config::{LoggingSettings, ResourceSettings, SecuritySettings, ServerConfig, ServerSettings, ToolSettings, TransportType}, McpServer}, NotificationSender
+    error::McpError, prompts::Prompt, protocol::JsonRpcNotification, resource::FileSystemProvider, server::{config::{LoggingSettings, ResourceSettings, SecuritySettings, ServerConfig, ServerSettings, ToolSettings, TransportType}, McpServer}, testing::{next_message, send_raw, TestHarness}, NotificationSender
 };
+use mcp_rs::transport::{InMemoryTransport, JsonRpcMessage, Transport};
+use serde_json::json;
 use tokio::sync::mpsc;
 use std::{sync::Arc, time::Duration};
 use tempfile::TempDir;
@@ -173,3 +175,98 @@ async fn test_multiple_subscribers() -> Result<(), McpError> {
     
     Ok(())
 }
+
+#[tokio::test]
+async fn test_notifications_reach_client() -> Result<(), McpError> {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = ServerConfig::default();
+    config.resources.root_path = temp_dir.path().to_path_buf();
+    let harness = TestHarness::start(McpServer::new(config).await).await?;
+
+    let mut list_changed = harness
+        .capture_notifications("notifications/prompts/list_changed")
+        .await;
+    let mut updated = harness
+        .capture_notifications("notifications/resources/updated")
+        .await;
+
+    harness
+        .prompt_manager
+        .register_prompt(Prompt {
+            name: "test-prompt".to_string(),
+            description: "Test prompt".to_string(),
+            arguments: vec![],
+        })
+        .await;
+    list_changed.expect().await;
+
+    // Updates only reach clients subscribed to the resource
+    harness
+        .client
+        .subscribe_to_resource("file:///watched.txt".to_string())
+        .await?;
+    harness
+        .resource_manager
+        .notify_resource_updated("file:///other.txt")
+        .await?;
+    harness
+        .resource_manager
+        .notify_resource_updated("file:///watched.txt")
+        .await?;
+    let notification = updated.expect().await;
+    assert_eq!(
+        notification.params.unwrap()["uri"],
+        "file:///watched.txt"
+    );
+    updated.assert_none(Duration::from_millis(100)).await;
+
+    harness.shutdown().await
+}
+
+#[tokio::test]
+async fn test_spec_initialized_notification() -> Result<(), McpError> {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = ServerConfig::default();
+    config.resources.root_path = temp_dir.path().to_path_buf();
+    let mut server = McpServer::new(config).await;
+    let prompt_manager = Arc::clone(&server.prompt_manager);
+
+    let (server_transport, mut client_transport) = InMemoryTransport::pair();
+    let server_task = tokio::spawn(async move { server.run_transport(server_transport).await });
+    let peer = client_transport.start().await?;
+
+    // Handshake the way spec clients do, without the crate's Client
+    send_raw(&peer, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "spec-client", "version": "1.0.0" }
+        }
+    }))
+    .await;
+    assert!(matches!(next_message(&peer).await, JsonRpcMessage::Response(_)));
+    send_raw(&peer, json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+    send_raw(&peer, json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" })).await;
+    assert!(matches!(next_message(&peer).await, JsonRpcMessage::Response(_)));
+
+    // Only running sessions receive notifications
+    prompt_manager
+        .register_prompt(Prompt {
+            name: "test-prompt".to_string(),
+            description: "Test prompt".to_string(),
+            arguments: vec![],
+        })
+        .await;
+    match next_message(&peer).await {
+        JsonRpcMessage::Notification(notification) => {
+            assert_eq!(notification.method, "notifications/prompts/list_changed")
+        }
+        other => panic!("Expected notification, got {:?}", other),
+    }
+
+    server_task.abort();
+    Ok(())
+}
@@ -1,10 +1,10 @@
-use std::{env, path::PathBuf};
-use tokio;
+use std::env;
 
 use mcp_rs::{
     error::McpError,
     resource::FileSystemProvider,
     server::{config::ServerConfig, McpServer},
+    testing::TestHarness,
 };
 
 #[tokio::test]
@@ -12,7 +12,7 @@ async fn test_resource_loading_integration() -> Result<(), McpError> {
     // Create test server config
     let mut config = ServerConfig::default();
     let root_dir = env::current_dir().unwrap();
-    config.resources.root_path = PathBuf::from(root_dir.join("tests/resources/test"));
+    config.resources.root_path = root_dir.join("tests/resources/test");
 
     // Initialize server
     let server = McpServer::new(config).await;
@@ -28,7 +28,7 @@ async fn test_resource_loading_integration() -> Result<(), McpError> {
     // Test listing resources
     let list_result = server.resource_manager.list_resources(None).await?;
     assert!(
-        list_result.resources.len() > 0,
+        !list_result.resources.is_empty(),
         "Should find test resources"
     );
 
@@ -82,7 +82,7 @@ async fn test_resource_templates() -> Result<(), McpError> {
     // Create test server config
     let mut config = ServerConfig::default();
     let root_dir = env::current_dir().unwrap();
-    config.resources.root_path = PathBuf::from(root_dir.join("tests/resources/test"));
+    config.resources.root_path = root_dir.join("tests/resources/test");
     config.resources.enable_templates = true;
 
     // Initialize server
@@ -99,7 +99,7 @@ async fn test_resource_templates() -> Result<(), McpError> {
     // Test listing templates
     let templates = server.resource_manager.list_templates().await?;
     assert!(
-        templates.resource_templates.len() > 0,
+        !templates.resource_templates.is_empty(),
         "Should have default templates"
     );
 
@@ -111,7 +111,7 @@ async fn test_resource_errors() -> Result<(), McpError> {
     // Create test server config
     let mut config = ServerConfig::default();
     let root_dir = env::current_dir().unwrap();
-    config.resources.root_path = PathBuf::from(root_dir.join("tests/resources/test"));
+    config.resources.root_path = root_dir.join("tests/resources/test");
 
     // Initialize server
     let server = McpServer::new(config).await;
@@ -149,3 +149,31 @@ async fn test_resource_errors() -> Result<(), McpError> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_resource_errors_reach_client() -> Result<(), McpError> {
+    let mut config = ServerConfig::default();
+    config.resources.root_path = env::current_dir().unwrap().join("tests/resources/test");
+    let server = McpServer::new(config).await;
+    let fs_provider =
+        std::sync::Arc::new(FileSystemProvider::new(&server.config.resources.root_path));
+    server
+        .resource_manager
+        .register_provider("file".to_string(), fs_provider)
+        .await;
+    let harness = TestHarness::start(server).await?;
+
+    // The server's error codes come back as the matching variants
+    match harness.client.read_resource("file:///nonexistent.txt".to_string()).await {
+        Err(McpError::ResourceNotFound(_)) => (),
+        other => panic!("Expected ResourceNotFound error, got {:?}", other),
+    }
+    match harness.client.read_resource("file:///../secret.txt".to_string()).await {
+        Err(McpError::AccessDenied(message)) => {
+            assert_eq!(message, "Path traversal attempt detected")
+        }
+        other => panic!("Expected AccessDenied error, got {:?}", other),
+    }
+
+    harness.shutdown().await
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use async_trait::async_trait;
+use serde_json::json;
+use tempfile::TempDir;
+
+use mcp_rs::{
+    client::{Client, ClientInfo},
+    error::McpError,
+    roots::Root,
+    server::{config::ServerConfig, ClientPeer, McpServer},
+    testing::{tool_text, TestHarness},
+    tools::{file_system::FileSystemTools, AutoApprove, Tool, ToolContent, ToolInputSchema, ToolProvider, ToolResult},
+};
+
+// Reports the roots the server knows for the calling client, or asks for fresh ones
+struct RootsTool;
+
+#[async_trait]
+impl ToolProvider for RootsTool {
+    async fn get_tool(&self) -> Tool {
+        Tool {
+            name: "roots".to_string(),
+            description: "Lists the client's roots".to_string(),
+            input_schema: ToolInputSchema {
+                schema_type: "object".to_string(),
+                properties: HashMap::from([("fresh".to_string(), json!({ "type": "boolean" }))]),
+                required: vec![],
+            },
+        }
+    }
+
+    async fn execute(&self, _arguments: serde_json::Value) -> Result<ToolResult, McpError> {
+        Err(McpError::InvalidRequest("Needs a client".to_string()))
+    }
+
+    async fn execute_with_client(
+        &self,
+        arguments: serde_json::Value,
+        client: ClientPeer,
+    ) -> Result<ToolResult, McpError> {
+        let roots = if arguments["fresh"].as_bool() == Some(true) {
+            client.list_roots().await?
+        } else {
+            client.roots().to_vec()
+        };
+        let names: Vec<String> = roots.into_iter().filter_map(|root| root.name).collect();
+        Ok(ToolResult {
+            content: vec![ToolContent::Text {
+                text: names.join(","),
+            }],
+            is_error: false,
+        })
+    }
+}
+
+fn root(dir: &TempDir, name: &str) -> Root {
+    Root::from_path(dir.path(), Some(name.to_string())).unwrap()
+}
+
+/// A server whose file system tools are sandboxed to `sandbox`
+async fn server(sandbox: &Path) -> McpServer {
+    let server = McpServer::new(ServerConfig::default()).await;
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
+    server.tool_manager.register_tool(Arc::new(RootsTool)).await;
+    let tools = FileSystemTools::with_allowed_directories(vec![sandbox.to_path_buf()]);
+    server
+        .tool_manager
+        .register_tool(Arc::new(tools.with_client_roots()))
+        .await;
+    server
+}
+
+async fn start(roots: Option<Vec<Root>>) -> Result<TestHarness, McpError> {
+    start_in(roots, &std::env::temp_dir()).await
+}
+
+async fn start_in(roots: Option<Vec<Root>>, sandbox: &Path) -> Result<TestHarness, McpError> {
+    let client = Client::new();
+    if let Some(roots) = roots {
+        client.set_roots(roots).await?;
+    }
+    let client_info = ClientInfo {
+        name: "roots-client".to_string(),
+        version: "1.0.0".to_string(),
+    };
+    TestHarness::start_with_client(server(sandbox).await, client, client_info).await
+}
+
+/// The server fetches roots in the background, so poll until it has `expected`
+async fn wait_for_roots(harness: &TestHarness, expected: &str) {
+    for _ in 0..100 {
+        let result = harness.client.call_tool("roots".to_string(), json!({})).await.unwrap();
+        if tool_text(&result) == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Server never saw roots {:?}", expected);
+}
+
+#[tokio::test]
+async fn test_server_tracks_client_roots() -> Result<(), McpError> {
+    let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let harness = start(Some(vec![root(&a, "a")])).await?;
+
+    wait_for_roots(&harness, "a").await;
+    harness.assert_tool_text("roots", json!({ "fresh": true }), "a").await;
+
+    // A change reaches the server through notifications/roots/list_changed
+    harness.client.set_roots(vec![root(&a, "a"), root(&b, "b")]).await?;
+    wait_for_roots(&harness, "a,b").await;
+
+    harness.shutdown().await
+}
+
+#[tokio::test]
+async fn test_file_system_tools_confined_to_roots() -> Result<(), McpError> {
+    let (inside, outside) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let harness = start(Some(vec![root(&inside, "inside")])).await?;
+    wait_for_roots(&harness, "inside").await;
+
+    let allowed = inside.path().join("note.txt");
+    harness
+        .client
+        .call_tool(
+            "read_file".to_string(),
+            json!({ "operation": "write_file", "path": allowed, "content": "hello" }),
+        )
+        .await?;
+    harness
+        .assert_tool_text("read_file", json!({ "operation": "read_file", "path": allowed }), "hello")
+        .await;
+
+    let denied = outside.path().join("note.txt");
+    match harness
+        .client
+        .call_tool(
+            "read_file".to_string(),
+            json!({ "operation": "write_file", "path": denied, "content": "hello" }),
+        )
+        .await
+    {
+        Err(McpError::AccessDenied(_)) => assert!(!denied.exists()),
+        other => panic!("Expected AccessDenied, got {:?}", other),
+    }
+
+    harness.shutdown().await
+}
+
+/// Write `content` to `path` with the `write_file` operation
+async fn write(harness: &TestHarness, path: &Path) -> Result<ToolResult, McpError> {
+    harness
+        .client
+        .call_tool(
+            "read_file".to_string(),
+            json!({ "operation": "write_file", "path": path, "content": "hello" }),
+        )
+        .await
+}
+
+#[tokio::test]
+async fn test_roots_cannot_widen_the_sandbox() -> Result<(), McpError> {
+    let sandbox = TempDir::new().unwrap();
+    let inside = sandbox.path().join("inside");
+    std::fs::create_dir(&inside).unwrap();
+    let outside = TempDir::new().unwrap();
+
+    // Roots outside the sandbox are dropped, the ones inside narrow it
+    let roots = vec![
+        Root::from_path(&inside, Some("inside".to_string())).unwrap(),
+        root(&outside, "outside"),
+    ];
+    let harness = start_in(Some(roots), sandbox.path()).await?;
+    wait_for_roots(&harness, "inside,outside").await;
+    assert!(!write(&harness, &inside.join("note.txt")).await?.is_error);
+    for denied in [outside.path().join("note.txt"), sandbox.path().join("note.txt")] {
+        match write(&harness, &denied).await {
+            Err(McpError::AccessDenied(_)) => assert!(!denied.exists()),
+            other => panic!("Expected AccessDenied for {:?}, got {:?}", denied, other),
+        }
+    }
+    harness.shutdown().await?;
+
+    // With no root inside the sandbox, the sandbox itself applies
+    let everything = Root::from_path(Path::new("/"), Some("everything".to_string())).unwrap();
+    let harness = start_in(Some(vec![everything]), sandbox.path()).await?;
+    wait_for_roots(&harness, "everything").await;
+    assert!(!write(&harness, &sandbox.path().join("note.txt")).await?.is_error);
+    let denied = outside.path().join("note.txt");
+    match write(&harness, &denied).await {
+        Err(McpError::AccessDenied(_)) => assert!(!denied.exists()),
+        other => panic!("Expected AccessDenied, got {:?}", other),
+    }
+
+    harness.shutdown().await
+}
+
+#[tokio::test]
+async fn test_roots_require_client_capability() -> Result<(), McpError> {
+    let harness = start(None).await?;
+
+    match harness
+        .client
+        .call_tool("roots".to_string(), json!({ "fresh": true }))
+        .await
+    {
+        Err(McpError::CapabilityNotSupported(capability)) => assert_eq!(capability, "roots"),
+        other => panic!("Expected CapabilityNotSupported, got {:?}", other),
+    }
+
+    harness.shutdown().await
+}
@@ -0,0 +1,195 @@
+use std::{collections::HashMap, sync::Arc};
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use mcp_rs::{
+    client::{Client, ClientInfo},
+    error::McpError,
+    prompts::{MessageContent, Prompt, PromptGenerator, PromptMessage, PromptResult},
+    sampling::{
+        CreateMessageRequest, CreateMessageResult, Role, SamplingContent, SamplingHandler,
+        StopReason,
+    },
+    server::{config::ServerConfig, ClientPeer, McpServer},
+    testing::TestHarness,
+    tools::{AutoApprove, Tool, ToolContent, ToolInputSchema, ToolProvider, ToolResult},
+};
+
+// Stands in for the client's model by replying with canned text
+struct CannedSampler {
+    reply: String,
+    requests: Mutex<Vec<CreateMessageRequest>>,
+}
+
+#[async_trait]
+impl SamplingHandler for CannedSampler {
+    async fn create_message(
+        &self,
+        request: CreateMessageRequest,
+    ) -> Result<CreateMessageResult, McpError> {
+        self.requests.lock().await.push(request);
+        Ok(CreateMessageResult {
+            role: Role::Assistant,
+            content: SamplingContent::Text {
+                text: self.reply.clone(),
+            },
+            model: "canned-model".to_string(),
+            stop_reason: Some(StopReason::EndTurn),
+        })
+    }
+}
+
+fn sampled_text(result: &CreateMessageResult) -> String {
+    match &result.content {
+        SamplingContent::Text { text } => text.clone(),
+        other => panic!("Expected text, got {:?}", other),
+    }
+}
+
+// Summarizes its input with the client's model
+struct SummarizeTool;
+
+#[async_trait]
+impl ToolProvider for SummarizeTool {
+    async fn get_tool(&self) -> Tool {
+        Tool {
+            name: "summarize".to_string(),
+            description: "Summarizes text".to_string(),
+            input_schema: ToolInputSchema {
+                schema_type: "object".to_string(),
+                properties: HashMap::from([("text".to_string(), json!({ "type": "string" }))]),
+                required: vec!["text".to_string()],
+            },
+        }
+    }
+
+    async fn execute(&self, _arguments: serde_json::Value) -> Result<ToolResult, McpError> {
+        Err(McpError::CapabilityNotSupported("sampling".to_string()))
+    }
+
+    async fn execute_with_client(
+        &self,
+        arguments: serde_json::Value,
+        client: ClientPeer,
+    ) -> Result<ToolResult, McpError> {
+        let text = arguments["text"].as_str().unwrap_or_default();
+        let mut request = CreateMessageRequest::user_text(format!("Summarize: {}", text), 50);
+        request.system_prompt = Some("Be brief".to_string());
+        let result = client.create_message(request).await?;
+        Ok(ToolResult {
+            content: vec![ToolContent::Text {
+                text: sampled_text(&result),
+            }],
+            is_error: false,
+        })
+    }
+}
+
+// Writes a prompt's opening line with the client's model
+struct SampledPrompt;
+
+#[async_trait]
+impl PromptGenerator for SampledPrompt {
+    async fn generate(
+        &self,
+        prompt: &Prompt,
+        _arguments: Option<serde_json::Value>,
+        client: ClientPeer,
+    ) -> Result<PromptResult, McpError> {
+        let result = client
+            .create_message(CreateMessageRequest::user_text("Write an opening line", 20))
+            .await?;
+        Ok(PromptResult {
+            description: prompt.description.clone(),
+            messages: vec![PromptMessage {
+                role: "user".to_string(),
+                content: MessageContent::Text {
+                    text: sampled_text(&result),
+                },
+            }],
+        })
+    }
+}
+
+async fn server() -> McpServer {
+    let server = McpServer::new(ServerConfig::default()).await;
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
+    server.tool_manager.register_tool(Arc::new(SummarizeTool)).await;
+    server
+        .prompt_manager
+        .register_prompt_generator(
+            Prompt {
+                name: "opener".to_string(),
+                description: "A sampled opening line".to_string(),
+                arguments: vec![],
+            },
+            Arc::new(SampledPrompt),
+        )
+        .await;
+    server
+}
+
+fn client_info() -> ClientInfo {
+    ClientInfo {
+        name: "sampling-client".to_string(),
+        version: "1.0.0".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_tool_samples_from_client() -> Result<(), McpError> {
+    let sampler = Arc::new(CannedSampler {
+        reply: "A short summary".to_string(),
+        requests: Mutex::new(Vec::new()),
+    });
+    let mut client = Client::new();
+    client.set_sampling_handler(sampler.clone()).await?;
+    let harness = TestHarness::start_with_client(server().await, client, client_info()).await?;
+
+    harness
+        .assert_tool_text("summarize", json!({ "text": "A long story" }), "A short summary")
+        .await;
+    harness.assert_prompt_text("opener", None, "A short summary").await;
+
+    let requests = sampler.requests.lock().await;
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].system_prompt.as_deref(), Some("Be brief"));
+    assert_eq!(requests[0].max_tokens, 50);
+    assert_eq!(
+        requests[0].messages[0].content,
+        SamplingContent::Text {
+            text: "Summarize: A long story".to_string()
+        }
+    );
+    drop(requests);
+
+    harness.shutdown().await
+}
+
+#[tokio::test]
+async fn test_sampling_requires_client_capability() -> Result<(), McpError> {
+    // Without a handler the client does not declare sampling
+    let mut harness = TestHarness::start(server().await).await?;
+
+    match harness
+        .client
+        .call_tool("summarize".to_string(), json!({ "text": "A long story" }))
+        .await
+    {
+        Err(McpError::CapabilityNotSupported(capability)) => assert_eq!(capability, "sampling"),
+        other => panic!("Expected CapabilityNotSupported, got {:?}", other),
+    }
+
+    // Nor can it start handling sampling after initialize
+    let sampler = Arc::new(CannedSampler {
+        reply: "Too late".to_string(),
+        requests: Mutex::new(Vec::new()),
+    });
+    match harness.client.set_sampling_handler(sampler).await {
+        Err(McpError::CapabilityNotSupported(capability)) => assert_eq!(capability, "sampling"),
+        other => panic!("Expected CapabilityNotSupported, got {:?}", other),
+    }
+
+    harness.shutdown().await
+}
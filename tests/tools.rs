@@ -1,10 +1,9 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::Duration};
 use async_trait::async_trait;
 use serde_json::json;
-use tokio;
 
 use mcp_rs::{
-    error::McpError, server::{config::ServerConfig, McpServer}, tools::{Tool, ToolContent, ToolInputSchema, ToolProvider, ToolResult}
+    error::McpError, protocol::Protocol, server::{config::{RateLimitSettings, ServerConfig}, McpServer}, testing::{tool_text, TestHarness}, tools::{Approval, AutoApprove, Tool, ToolApprover, ToolCall, ToolContent, ToolInputSchema, ToolProvider, ToolResult}
 };
 
 // Mock tool provider for testing
@@ -41,7 +40,7 @@ impl ToolProvider for MockCalculatorTool {
             input_schema: ToolInputSchema {
                 schema_type: "object".to_string(),
                 properties,
-                required: vec!["operation", "a", "b"].iter().map(|s| s.to_string()).collect(),
+                required: ["operation", "a", "b"].iter().map(|s| s.to_string()).collect(),
             },
         }
     }
@@ -92,6 +91,7 @@ async fn test_tool_registration_and_listing() {
     
     // Register mock tool
     let tool_provider = Arc::new(MockCalculatorTool);
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
     server.tool_manager.register_tool(tool_provider).await;
 
     // Test listing tools
@@ -108,6 +108,7 @@ async fn test_tool_execution() {
     
     // Register mock tool
     let tool_provider = Arc::new(MockCalculatorTool);
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
     server.tool_manager.register_tool(tool_provider).await;
 
     // Test addition
@@ -172,6 +173,7 @@ async fn test_invalid_arguments() {
     
     // Register mock tool
     let tool_provider = Arc::new(MockCalculatorTool);
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
     server.tool_manager.register_tool(tool_provider).await;
 
     // Test invalid operation
@@ -186,3 +188,218 @@ async fn test_invalid_arguments() {
 
     assert!(result.is_error);
 }
+
+#[tokio::test]
+async fn test_tool_calls_are_rate_limited() {
+    let mut config = ServerConfig::default();
+    config.tool_settings.rate_limit = RateLimitSettings {
+        requests_per_minute: 1,
+        burst_size: 2,
+    };
+    let server = McpServer::new(config).await;
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
+    server.tool_manager.register_tool(Arc::new(MockCalculatorTool)).await;
+    let harness = TestHarness::start(server).await.unwrap();
+
+    let add = json!({ "operation": "add", "a": 1, "b": 2 });
+    harness.assert_tool_text("calculator", add.clone(), "3").await;
+    harness.assert_tool_text("calculator", add.clone(), "3").await;
+
+    // The third call exceeds the burst and says when to come back
+    match harness.client.call_tool("calculator".to_string(), add).await {
+        Err(e @ McpError::RateLimited { .. }) => {
+            assert_eq!(e.code(), -32009);
+            let McpError::RateLimited { retry_after } = e else { unreachable!() };
+            assert!(retry_after > Duration::from_secs(50), "{:?}", retry_after);
+        }
+        other => panic!("Expected RateLimited, got {:?}", other),
+    }
+
+    // Other requests have their own budget
+    assert_eq!(harness.client.list_tools(None).await.unwrap().tools.len(), 1);
+    harness.shutdown().await.unwrap();
+}
+
+// Sleeps for `ms` milliseconds
+struct MockSleepTool;
+
+#[async_trait]
+impl ToolProvider for MockSleepTool {
+    async fn get_tool(&self) -> Tool {
+        Tool {
+            name: "sleep".to_string(),
+            description: "Sleeps for a while".to_string(),
+            input_schema: ToolInputSchema {
+                schema_type: "object".to_string(),
+                properties: HashMap::new(),
+                required: vec![],
+            },
+        }
+    }
+
+    async fn execute(&self, arguments: serde_json::Value) -> Result<ToolResult, McpError> {
+        let ms = arguments["ms"].as_u64().ok_or(McpError::InvalidParams)?;
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Ok(ToolResult {
+            content: vec![ToolContent::Text { text: "awake".to_string() }],
+            is_error: false,
+        })
+    }
+}
+
+async fn server_with_tools(config: ServerConfig) -> McpServer {
+    let server = McpServer::new(config).await;
+    server.tool_manager.set_approver(Arc::new(AutoApprove)).await;
+    server.tool_manager.register_tool(Arc::new(MockCalculatorTool)).await;
+    server.tool_manager.register_tool(Arc::new(MockSleepTool)).await;
+    server
+}
+
+#[tokio::test]
+async fn test_allowed_tools() {
+    let mut config = ServerConfig::default();
+    config.tool_settings.allowed_tools = vec!["calc*".to_string()];
+    let harness = TestHarness::start(server_with_tools(config).await).await.unwrap();
+
+    let tools = harness.client.list_tools(None).await.unwrap().tools;
+    assert_eq!(tools.len(), 1);
+    assert_eq!(tools[0].name, "calculator");
+
+    harness
+        .assert_tool_text("calculator", json!({ "operation": "add", "a": 1, "b": 2 }), "3")
+        .await;
+    match harness.client.call_tool("sleep".to_string(), json!({ "ms": 1 })).await {
+        Err(McpError::AccessDenied(message)) => assert!(message.contains("sleep")),
+        other => panic!("Expected AccessDenied, got {:?}", other),
+    }
+    harness.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_tools_disabled() {
+    let mut config = ServerConfig::default();
+    config.tool_settings.enabled = false;
+    let server = server_with_tools(config).await;
+
+    let params = serde_json::from_value(json!({
+        "protocolVersion": "2024-11-05",
+        "capabilities": {},
+        "clientInfo": { "name": "test-client", "version": "1.0.0" }
+    }))
+    .unwrap();
+    let result = server.handle_initialize("", params).await.unwrap();
+    assert!(result.capabilities.tools.is_none());
+    assert!(result.capabilities.resources.is_some());
+
+    assert!(server.tool_manager.list_tools(None).await.unwrap().tools.is_empty());
+    match server.tool_manager.call_tool("calculator", json!({})).await {
+        Err(McpError::CapabilityNotSupported(capability)) => assert_eq!(capability, "tools"),
+        other => panic!("Expected CapabilityNotSupported, got {:?}", other),
+    }
+
+    // Clients asking anyway find no such methods
+    let protocol = server.register_resource_handlers(Protocol::builder(None)).build();
+    let handlers = protocol.request_handlers.read().await;
+    assert!(!handlers.contains_key("tools/list"));
+    assert!(!handlers.contains_key("tools/call"));
+    assert!(handlers.contains_key("resources/list"));
+}
+
+#[tokio::test]
+async fn test_tool_execution_time_limit() {
+    let mut config = ServerConfig::default();
+    config.tool_settings.max_execution_time_ms = 100;
+    let server = server_with_tools(config).await;
+
+    let result = server.tool_manager.call_tool("sleep", json!({ "ms": 10 })).await.unwrap();
+    assert!(!result.is_error);
+
+    let result = server.tool_manager.call_tool("sleep", json!({ "ms": 5000 })).await.unwrap();
+    assert!(result.is_error);
+    match &result.content[0] {
+        ToolContent::Text { text } => assert_eq!(text, "Tool sleep timed out after 100ms"),
+        _ => panic!("Expected text content"),
+    }
+}
+
+// Denies division, halves `a` of everything else and records what it saw
+struct RecordingApprover {
+    calls: Mutex<Vec<ToolCall>>,
+}
+
+#[async_trait]
+impl ToolApprover for RecordingApprover {
+    async fn review(&self, call: &ToolCall) -> Approval {
+        self.calls.lock().unwrap().push(call.clone());
+        if call.arguments["operation"] == "divide" {
+            return Approval::Deny("No dividing".to_string());
+        }
+        let mut arguments = call.arguments.clone();
+        arguments["a"] = json!(arguments["a"].as_f64().unwrap() / 2.0);
+        Approval::ApproveWith(arguments)
+    }
+}
+
+#[tokio::test]
+async fn test_tool_approval() {
+    let server = server_with_tools(ServerConfig::default()).await;
+    let approver = Arc::new(RecordingApprover {
+        calls: Mutex::new(Vec::new()),
+    });
+    server.tool_manager.set_approver(approver.clone()).await;
+    let harness = TestHarness::start(server).await.unwrap();
+
+    harness
+        .assert_tool_text("calculator", json!({ "operation": "add", "a": 4, "b": 1 }), "3")
+        .await;
+
+    let result = harness
+        .client
+        .call_tool(
+            "calculator".to_string(),
+            json!({ "operation": "divide", "a": 4, "b": 2 }),
+        )
+        .await
+        .unwrap();
+    assert!(result.is_error);
+    assert_eq!(tool_text(&result), "Tool call denied: No dividing");
+
+    let calls = approver.calls.lock().unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].name, "calculator");
+    assert_eq!(calls[0].arguments["a"], 4);
+    assert_eq!(calls[0].client_info.as_ref().unwrap().name, "test-client");
+}
+
+#[tokio::test]
+async fn test_tool_approval_not_required() {
+    let mut config = ServerConfig::default();
+    config.tool_settings.require_confirmation = false;
+    let server = server_with_tools(config).await;
+    let approver = Arc::new(RecordingApprover {
+        calls: Mutex::new(Vec::new()),
+    });
+    server.tool_manager.set_approver(approver.clone()).await;
+
+    let result = server
+        .tool_manager
+        .call_tool("calculator", json!({ "operation": "divide", "a": 4, "b": 2 }))
+        .await
+        .unwrap();
+    assert!(!result.is_error);
+    assert!(approver.calls.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_tool_calls_denied_without_approver() {
+    let server = McpServer::new(ServerConfig::default()).await;
+    server.tool_manager.register_tool(Arc::new(MockCalculatorTool)).await;
+
+    let result = server
+        .tool_manager
+        .call_tool("calculator", json!({ "operation": "add", "a": 1, "b": 2 }))
+        .await
+        .unwrap();
+    assert!(result.is_error);
+    assert_eq!(tool_text(&result), "Tool call denied: No approver configured");
+}